@@ -0,0 +1,115 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.ArrayList;
+import java.util.Comparator;
+import java.util.List;
+import java.util.function.Function;
+import java.util.function.Supplier;
+
+// Compiled with `javac -g -parameters Fixture.java` to exercise every
+// attribute the class reader decodes
+@Fixture.Marker(name = "fixture", values = {1, 2}, kind = ElementType.TYPE, type = String.class, nested = @Deprecated)
+public class Fixture<T extends Comparable<T>> {
+    @Retention(RetentionPolicy.RUNTIME)
+    @Target({ElementType.TYPE, ElementType.METHOD, ElementType.PARAMETER, ElementType.FIELD, ElementType.TYPE_USE})
+    @interface Marker {
+        String name() default "";
+        int[] values() default {};
+        ElementType kind() default ElementType.FIELD;
+        Class<?> type() default Object.class;
+        Deprecated nested() default @Deprecated;
+    }
+
+    @Retention(RetentionPolicy.CLASS)
+    @Target({ElementType.METHOD, ElementType.PARAMETER, ElementType.TYPE_USE})
+    @interface Hidden {}
+
+    enum Color { RED, GREEN, BLUE }
+
+    record Point(@Marker int x, int y) implements Comparable<Point> {
+        public int compareTo(Point other) {
+            return Integer.compare(x * x + y * y, other.x * other.x + other.y * other.y);
+        }
+    }
+
+    class Inner {
+        T value;
+    }
+
+    private final List<@Hidden T> items = new ArrayList<>();
+    static final long BIG = 1L << 40;
+    static final String NAME = "fixture";
+
+    @Deprecated
+    @Marker(name = "max")
+    T max(@Marker @Hidden Comparator<? super T> comparator) throws IllegalStateException {
+        T best = null;
+        for (T item : items) {
+            if (best == null || comparator.compare(item, best) > 0) {
+                best = item;
+            }
+        }
+        if (best == null) {
+            throw new IllegalStateException("empty");
+        }
+        return best;
+    }
+
+    @Hidden
+    static int dense(int n) {
+        switch (n) {
+            case 0: return 10;
+            case 1: return 11;
+            case 2: return 12;
+            case 3: return 13;
+            default: return -1;
+        }
+    }
+
+    static int sparse(String s) {
+        switch (s) {
+            case "a": return 1;
+            case "bb": return 2;
+            case "ccc": return 3;
+            default: return 0;
+        }
+    }
+
+    static int color(Color color) {
+        switch (color) {
+            case RED: return 0xff0000;
+            case GREEN: return 0x00ff00;
+            default: return 0x0000ff;
+        }
+    }
+
+    static Supplier<String> lambda(int x) {
+        Function<Integer, String> f = i -> "v" + (i + x);
+        return () -> f.apply(x);
+    }
+
+    static double average(double[] values) {
+        double sum = 0;
+        long count = 0;
+        for (double v : values) {
+            if (v < 0) {
+                continue;
+            }
+            sum += v;
+            count++;
+        }
+        return count == 0 ? 0 : sum / count;
+    }
+
+    static int parse(String s) {
+        try {
+            return Integer.parseInt(s);
+        } catch (NumberFormatException e) {
+            return -1;
+        } finally {
+            System.out.println(s);
+        }
+    }
+}
@@ -0,0 +1,4 @@
+package fixture.api;
+
+public interface Api {
+}
@@ -0,0 +1,6 @@
+package fixture.impl;
+
+public class Task implements Runnable {
+    public void run() {
+    }
+}
@@ -0,0 +1,8 @@
+// Compiled with `javac module-info.java fixture/api/Api.java fixture/impl/Task.java`
+module fixture {
+    requires java.logging;
+    exports fixture.api;
+    opens fixture.impl to java.base;
+    uses java.lang.Runnable;
+    provides java.lang.Runnable with fixture.impl.Task;
+}
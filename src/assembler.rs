@@ -0,0 +1,1571 @@
+//! Builds classes in memory, so that tests and tools can construct exact
+//! bytecode without `javac`.
+//!
+//! A `ClassBuilder` owns the constant pool and hands out indexes for the
+//! constants that instructions refer to. Method bodies are written into a
+//! `CodeBuilder` as `Instruction`s, with branches aimed at `Label`s instead
+//! of offsets, e.g. `code.branch(Instruction::Goto, top)`.
+//!
+//! When a method is added, branch offsets are resolved and `max_stack`,
+//! `max_locals` and the `StackMapTable` are computed by inferring the type
+//! of every local and stack entry at every instruction. Every operand is
+//! checked against the type its instruction takes, so `iadd` on a float is a
+//! `TypeMismatch`. Without the class hierarchy at hand, though, any reference
+//! is accepted where a reference is expected, and two different class types
+//! meeting at a branch target are merged to `java/lang/Object`.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+
+use crate::class::BytecodeClass;
+use crate::class::ConstantPoolEnum;
+use crate::class::ConstantUtf8Class;
+use crate::class::ConstantClassClass;
+use crate::class::ConstantStringClass;
+use crate::class::ConstantIntegerClass;
+use crate::class::ConstantFloatClass;
+use crate::class::ConstantLongClass;
+use crate::class::ConstantDoubleClass;
+use crate::class::ConstantNameAndTypeClass;
+use crate::class::ConstantFieldrefClass;
+use crate::class::ConstantMethodrefClass;
+use crate::class::ConstantInterfaceMethodrefClass;
+use crate::class::FieldInfo;
+use crate::class::MethodInfo;
+use crate::class::AttributeEnum;
+use crate::class::CodeClass;
+use crate::class::ExceptionTableEntry;
+use crate::class::LineNumberTableClass;
+use crate::class::LineNumberTableElem;
+use crate::class::StackMapTableClass;
+use crate::class::StackMapFrame;
+use crate::class::VerificationTypeInfo;
+use crate::class::SourceFileClass;
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::Utf8Index;
+use crate::constant_pool::ClassIndex;
+use crate::constant_pool::NameAndTypeIndex;
+use crate::constant_pool::MemberRefIndex;
+use crate::descriptor::BaseType;
+use crate::descriptor::DescriptorError;
+use crate::descriptor::FieldType;
+use crate::descriptor::MethodDescriptor;
+use crate::instruction::Instruction;
+
+pub const ACC_PUBLIC: u16 = 0x0001;
+pub const ACC_PRIVATE: u16 = 0x0002;
+pub const ACC_PROTECTED: u16 = 0x0004;
+pub const ACC_STATIC: u16 = 0x0008;
+pub const ACC_FINAL: u16 = 0x0010;
+pub const ACC_SUPER: u16 = 0x0020;
+pub const ACC_ABSTRACT: u16 = 0x0400;
+
+/// Why a method could not be assembled. `pc` is the address of the
+/// offending instruction.
+#[derive(Debug)]
+pub enum AssembleError {
+    UnplacedLabel,
+    LabelPlacedTwice,
+    BranchTooFar { pc: usize },
+    /// A `tableswitch` with no targets, or with more than there are keys
+    /// from `low` up
+    BadSwitchRange { pc: usize },
+    /// A `lookupswitch` with two targets for the same key
+    DuplicateSwitchKey { pc: usize, key: i32 },
+    CodeTooLarge { length: usize },
+    BadDescriptor { descriptor: String },
+    BadConstant { pc: usize, index: u16 },
+    StackUnderflow { pc: usize },
+    /// Two paths reach `pc` with operand stacks of different shapes
+    StackMismatch { pc: usize },
+    /// An instruction used a value of the wrong type
+    TypeMismatch { pc: usize },
+    FallsOffEnd { pc: usize },
+    /// Dead code, which would need a stack map frame that cannot be inferred
+    UnreachableCode { pc: usize },
+    Unsupported { pc: usize, mnemonic: &'static str },
+    /// The constant pool has no room for another constant
+    ConstantPoolFull,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssembleError::UnplacedLabel => write!(f, "Label used but never placed"),
+            AssembleError::LabelPlacedTwice => write!(f, "Label placed more than once"),
+            AssembleError::BranchTooFar { pc } => write!(f, "Branch target out of range at {}", pc),
+            AssembleError::BadSwitchRange { pc } => write!(f, "tableswitch has no targets or too many at {}", pc),
+            AssembleError::DuplicateSwitchKey { pc, key } => write!(f, "lookupswitch has key {} twice at {}", key, pc),
+            AssembleError::CodeTooLarge { length } => write!(f, "Code of {} bytes is too large", length),
+            AssembleError::BadDescriptor { descriptor } => write!(f, "Bad descriptor {}", descriptor),
+            AssembleError::BadConstant { pc, index } =>
+                write!(f, "Constant {} has the wrong kind for the instruction at {}", index, pc),
+            AssembleError::StackUnderflow { pc } => write!(f, "Operand stack underflow at {}", pc),
+            AssembleError::StackMismatch { pc } => write!(f, "Inconsistent operand stack at {}", pc),
+            AssembleError::TypeMismatch { pc } => write!(f, "Wrong operand type at {}", pc),
+            AssembleError::FallsOffEnd { pc } => write!(f, "Execution falls off the end of the code at {}", pc),
+            AssembleError::UnreachableCode { pc } => write!(f, "Unreachable code at {}", pc),
+            AssembleError::Unsupported { pc, mnemonic } => write!(f, "{} is not supported, at {}", mnemonic, pc),
+            AssembleError::ConstantPoolFull => write!(f, "Constant pool is full"),
+        }
+    }
+}
+
+impl error::Error for AssembleError {}
+
+impl From<DescriptorError> for AssembleError {
+    fn from(e: DescriptorError) -> AssembleError {
+        AssembleError::BadDescriptor { descriptor: e.descriptor }
+    }
+}
+
+// Constants are shared: asking twice for the same one gives the same index
+#[derive(PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Utf8(String),
+    Integer(i32),
+    Float(u32),
+    Long(i64),
+    Double(u64),
+    Class(u16),
+    String(u16),
+    NameAndType(u16, u16),
+    Fieldref(u16, u16),
+    Methodref(u16, u16),
+    InterfaceMethodref(u16, u16),
+}
+
+pub struct ClassBuilder {
+    major_version: u16,
+    minor_version: u16,
+    access_flags: u16,
+    constant_pool: ConstantPool,
+    constants: HashMap<ConstantKey, u16>,
+    this_class: ClassIndex,
+    this_class_name: String,
+    super_class: ClassIndex,
+    super_class_name: String,
+    interfaces: Vec<ClassIndex>,
+    interface_names: Vec<String>,
+    fields: Vec<FieldInfo>,
+    methods: Vec<MethodInfo>,
+    attributes: Vec<AttributeEnum>,
+}
+
+impl ClassBuilder {
+    /// Starts a public class targeting Java 17. Names are internal names,
+    /// such as `java/lang/Object`.
+    pub fn new(name: &str, super_name: &str) -> ClassBuilder {
+        let mut class = ClassBuilder {
+            major_version: 61,
+            minor_version: 0,
+            access_flags: ACC_PUBLIC | ACC_SUPER,
+            constant_pool: ConstantPool::new(),
+            constants: HashMap::new(),
+            this_class: ClassIndex(0),
+            this_class_name: name.to_string(),
+            super_class: ClassIndex(0),
+            super_class_name: super_name.to_string(),
+            interfaces: vec![],
+            interface_names: vec![],
+            fields: vec![],
+            methods: vec![],
+            attributes: vec![],
+        };
+        // The first constants always fit
+        class.this_class = ClassIndex(class.class(name).unwrap());
+        class.super_class = ClassIndex(class.class(super_name).unwrap());
+        class
+    }
+
+    pub fn set_version(&mut self, major_version: u16, minor_version: u16) {
+        self.major_version = major_version;
+        self.minor_version = minor_version;
+    }
+
+    pub fn set_access_flags(&mut self, access_flags: u16) {
+        self.access_flags = access_flags;
+    }
+
+    pub fn set_source_file(&mut self, source_file: &str) -> Result<(), AssembleError> {
+        let attribute_name_index = Utf8Index(self.utf8("SourceFile")?);
+        let sourcefile_index = Utf8Index(self.utf8(source_file)?);
+        self.attributes.push(AttributeEnum::SourceFileItem(SourceFileClass {
+            attribute_name_index,
+            attribute_name: String::from("SourceFile"),
+            sourcefile_index,
+        }));
+        Ok(())
+    }
+
+    pub fn add_interface(&mut self, name: &str) -> Result<(), AssembleError> {
+        let index = ClassIndex(self.class(name)?);
+        self.interfaces.push(index);
+        self.interface_names.push(name.to_string());
+        Ok(())
+    }
+
+    fn constant(&mut self, key: ConstantKey, constant: ConstantPoolEnum) -> Result<u16, AssembleError> {
+        if let Some(index) = self.constants.get(&key) {
+            return Ok(*index);
+        }
+        let index = self.constant_pool.push(constant).map_err(|_| AssembleError::ConstantPoolFull)?;
+        self.constants.insert(key, index);
+        Ok(index)
+    }
+
+    pub fn utf8(&mut self, s: &str) -> Result<u16, AssembleError> {
+        self.constant(ConstantKey::Utf8(s.to_string()), ConstantPoolEnum::ConstantUtf8Item(
+            ConstantUtf8Class { length: crate::mutf8::encode(s).len() as u16, bytes: s.to_string(), original: None }))
+    }
+
+    pub fn class(&mut self, name: &str) -> Result<u16, AssembleError> {
+        let name_index = self.utf8(name)?;
+        self.constant(ConstantKey::Class(name_index), ConstantPoolEnum::ConstantClassItem(
+            ConstantClassClass { name_index: Utf8Index(name_index) }))
+    }
+
+    pub fn string(&mut self, s: &str) -> Result<u16, AssembleError> {
+        let string_index = self.utf8(s)?;
+        self.constant(ConstantKey::String(string_index), ConstantPoolEnum::ConstantStringItem(
+            ConstantStringClass { string_index: Utf8Index(string_index) }))
+    }
+
+    pub fn integer(&mut self, value: i32) -> Result<u16, AssembleError> {
+        self.constant(ConstantKey::Integer(value), ConstantPoolEnum::ConstantIntegerItem(
+            ConstantIntegerClass { value }))
+    }
+
+    pub fn float(&mut self, value: f32) -> Result<u16, AssembleError> {
+        self.constant(ConstantKey::Float(value.to_bits()), ConstantPoolEnum::ConstantFloatItem(
+            ConstantFloatClass { value }))
+    }
+
+    pub fn long(&mut self, value: i64) -> Result<u16, AssembleError> {
+        self.constant(ConstantKey::Long(value), ConstantPoolEnum::ConstantLongItem(
+            ConstantLongClass { value }))
+    }
+
+    pub fn double(&mut self, value: f64) -> Result<u16, AssembleError> {
+        self.constant(ConstantKey::Double(value.to_bits()), ConstantPoolEnum::ConstantDoubleItem(
+            ConstantDoubleClass { value }))
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16, AssembleError> {
+        let name_index = self.utf8(name)?;
+        let descriptor_index = self.utf8(descriptor)?;
+        self.constant(ConstantKey::NameAndType(name_index, descriptor_index),
+            ConstantPoolEnum::ConstantNameAndTypeItem(ConstantNameAndTypeClass {
+                name_index: Utf8Index(name_index),
+                descriptor_index: Utf8Index(descriptor_index),
+            }))
+    }
+
+    pub fn field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, AssembleError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.constant(ConstantKey::Fieldref(class_index, name_and_type_index),
+            ConstantPoolEnum::ConstantFieldrefItem(ConstantFieldrefClass {
+                class_index: ClassIndex(class_index),
+                name_and_type_index: NameAndTypeIndex(name_and_type_index),
+            }))
+    }
+
+    pub fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, AssembleError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.constant(ConstantKey::Methodref(class_index, name_and_type_index),
+            ConstantPoolEnum::ConstantMethodrefItem(ConstantMethodrefClass {
+                class_index: ClassIndex(class_index),
+                name_and_type_index: NameAndTypeIndex(name_and_type_index),
+            }))
+    }
+
+    pub fn interface_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, AssembleError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.constant(ConstantKey::InterfaceMethodref(class_index, name_and_type_index),
+            ConstantPoolEnum::ConstantInterfaceMethodrefItem(
+                ConstantInterfaceMethodrefClass {
+                    class_index: ClassIndex(class_index),
+                    name_and_type_index: NameAndTypeIndex(name_and_type_index),
+                }))
+    }
+
+    pub fn add_field(&mut self, access_flags: u16, name: &str, descriptor: &str) -> Result<(), AssembleError> {
+        let name_index = Utf8Index(self.utf8(name)?);
+        let descriptor_index = Utf8Index(self.utf8(descriptor)?);
+        self.fields.push(FieldInfo {
+            access_flags,
+            name_index,
+            name: name.to_string(),
+            descriptor_index,
+            descriptor: descriptor.to_string(),
+            attributes_count: 0,
+            attributes: vec![],
+        });
+        Ok(())
+    }
+
+    /// Assembles `code` as the body of a new method.
+    pub fn add_method(&mut self, access_flags: u16, name: &str, descriptor: &str, code: CodeBuilder)
+        -> Result<(), AssembleError> {
+        let (instructions, handlers, line_numbers) = code.layout()?;
+        let end = instructions.last().map_or(0, |(pc, instruction)| pc + instruction.length(*pc));
+        if end == 0 || end > 65535 {
+            return Err(AssembleError::CodeTooLarge { length: end });
+        }
+        let mut bytes: Vec<u8> = Vec::with_capacity(end);
+        for (pc, instruction) in &instructions {
+            instruction.encode(*pc, &mut bytes);
+        }
+
+        let mut exception_table: Vec<ExceptionTableEntry> = vec![];
+        for (start_pc, end_pc, handler_pc, catch_type_name) in handlers {
+            let catch_type = match &catch_type_name {
+                Some(name) => ClassIndex(self.class(name)?),
+                None => ClassIndex(0),
+            };
+            exception_table.push(ExceptionTableEntry {
+                start_pc: start_pc as u16,
+                end_pc: end_pc as u16,
+                handler_pc: handler_pc as u16,
+                catch_type,
+                catch_type_name,
+            });
+        }
+
+        let method = MethodType {
+            is_static: access_flags & ACC_STATIC != 0,
+            name,
+            descriptor,
+            class_name: &self.this_class_name,
+        };
+        let analysis = analyze(&instructions, &exception_table, &self.constant_pool, &method)?;
+
+        let mut attributes: Vec<AttributeEnum> = vec![];
+        if !line_numbers.is_empty() {
+            let attribute_name_index = Utf8Index(self.utf8("LineNumberTable")?);
+            attributes.push(AttributeEnum::LineNumberTableItem(LineNumberTableClass {
+                attribute_name_index,
+                attribute_name: String::from("LineNumberTable"),
+                attribute_length: 2 + 4 * line_numbers.len() as u32,
+                line_number_table_length: line_numbers.len() as u16,
+                line_number_table: line_numbers.iter()
+                    .map(|(pc, line)| LineNumberTableElem { start_pc: *pc as u16, line_number: *line })
+                    .collect(),
+            }));
+        }
+        // Class files before version 50 are checked by type inference instead
+        if !analysis.frames.is_empty() && self.major_version >= 50 {
+            let entries = self.stack_map_frames(&analysis.initial_locals, &analysis.frames)?;
+            let attribute_name_index = Utf8Index(self.utf8("StackMapTable")?);
+            attributes.push(AttributeEnum::StackMapTableItem(StackMapTableClass {
+                attribute_name_index,
+                attribute_name: String::from("StackMapTable"),
+                // Recomputed when the class is written
+                attribute_length: 0,
+                number_of_entries: entries.len() as u16,
+                entries,
+            }));
+        }
+
+        let attribute_name_index = Utf8Index(self.utf8("Code")?);
+        let code_attribute = CodeClass {
+            attribute_name_index,
+            attribute_name: String::from("Code"),
+            attribute_length: 0,
+            max_stack: analysis.max_stack,
+            max_locals: analysis.max_locals,
+            code_length: bytes.len() as u32,
+            code: bytes,
+            exception_table_length: exception_table.len() as u16,
+            exception_table,
+            attributes_count: attributes.len() as u16,
+            attributes,
+        };
+        let name_index = Utf8Index(self.utf8(name)?);
+        let descriptor_index = Utf8Index(self.utf8(descriptor)?);
+        self.methods.push(MethodInfo {
+            access_flags,
+            name_index,
+            name: name.to_string(),
+            descriptor_index,
+            descriptor: descriptor.to_string(),
+            attributes_count: 1,
+            attributes: vec![AttributeEnum::CodeItem(code_attribute)],
+        });
+        Ok(())
+    }
+
+    pub fn build(self) -> BytecodeClass {
+        BytecodeClass {
+            magic: 0xCAFEBABE,
+            minor_version: self.minor_version,
+            major_version: self.major_version,
+            constant_pool: self.constant_pool,
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            this_class_name: self.this_class_name,
+            super_class: self.super_class,
+            super_class_name: self.super_class_name,
+            interfaces: self.interfaces,
+            interface_names: self.interface_names,
+            fields: self.fields,
+            methods: self.methods,
+            attributes: self.attributes,
+        }
+    }
+
+    // Encodes each frame in the most compact form relative to the previous
+    fn stack_map_frames(&mut self, initial_locals: &[Type], frames: &[(usize, State)])
+        -> Result<Vec<StackMapFrame>, AssembleError> {
+        let mut entries: Vec<StackMapFrame> = vec![];
+        let mut previous_locals = frame_locals(initial_locals);
+        let mut previous_pc: Option<usize> = None;
+        for (pc, state) in frames {
+            let offset_delta = match previous_pc {
+                Some(previous_pc) => (pc - previous_pc - 1) as u16,
+                None => *pc as u16,
+            };
+            let locals = frame_locals(&state.locals);
+            let stack = &state.stack;
+            let same_locals = locals == previous_locals;
+            let entry = if same_locals && stack.is_empty() && offset_delta < 64 {
+                StackMapFrame::SameFrame { frame_type: offset_delta as u8 }
+            } else if same_locals && stack.is_empty() {
+                StackMapFrame::SameFrameExtended { frame_type: 251, offset_delta }
+            } else if same_locals && stack.len() == 1 && offset_delta < 64 {
+                StackMapFrame::SameLocals1StackItemFrame {
+                    frame_type: 64 + offset_delta as u8,
+                    stack: self.verification_type_info(&stack[0])?,
+                }
+            } else if same_locals && stack.len() == 1 {
+                StackMapFrame::SameLocals1StackItemFrameExtended {
+                    frame_type: 247,
+                    offset_delta,
+                    stack: self.verification_type_info(&stack[0])?,
+                }
+            } else if stack.is_empty() && locals.len() < previous_locals.len()
+                && previous_locals.len() - locals.len() <= 3 && previous_locals.starts_with(&locals) {
+                StackMapFrame::ChopFrame {
+                    frame_type: 251 - (previous_locals.len() - locals.len()) as u8,
+                    offset_delta,
+                }
+            } else if stack.is_empty() && locals.len() > previous_locals.len()
+                && locals.len() - previous_locals.len() <= 3 && locals.starts_with(&previous_locals) {
+                StackMapFrame::AppendFrame {
+                    frame_type: 251 + (locals.len() - previous_locals.len()) as u8,
+                    offset_delta,
+                    locals: locals[previous_locals.len()..].iter()
+                        .map(|local| self.verification_type_info(local))
+                        .collect::<Result<_, _>>()?,
+                }
+            } else {
+                StackMapFrame::FullFrame {
+                    frame_type: 255,
+                    offset_delta,
+                    number_of_locals: locals.len() as u16,
+                    locals: locals.iter().map(|local| self.verification_type_info(local)).collect::<Result<_, _>>()?,
+                    number_of_stack_items: stack.len() as u16,
+                    stack: stack.iter().map(|item| self.verification_type_info(item)).collect::<Result<_, _>>()?,
+                }
+            };
+            entries.push(entry);
+            previous_locals = locals;
+            previous_pc = Some(*pc);
+        }
+        Ok(entries)
+    }
+
+    fn verification_type_info(&mut self, t: &Type) -> Result<VerificationTypeInfo, AssembleError> {
+        Ok(match t {
+            Type::Top => VerificationTypeInfo::Top,
+            Type::Int => VerificationTypeInfo::Integer,
+            Type::Float => VerificationTypeInfo::Float,
+            Type::Long => VerificationTypeInfo::Long,
+            Type::Double => VerificationTypeInfo::Double,
+            Type::Null => VerificationTypeInfo::Null,
+            Type::UninitializedThis => VerificationTypeInfo::UninitializedThis,
+            Type::Object(class_name) => VerificationTypeInfo::Object {
+                cpool_index: ClassIndex(self.class(class_name)?),
+                class_name: class_name.clone(),
+            },
+            Type::Uninitialized(offset) => VerificationTypeInfo::Uninitialized { offset: *offset as u16 },
+        })
+    }
+}
+
+/// A place in the code that branches can target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Label(usize);
+
+enum Item {
+    Instruction(Instruction),
+    Branch(fn(i16) -> Instruction, Label),
+    WideBranch(fn(i32) -> Instruction, Label),
+    Tableswitch { low: i32, targets: Vec<Label>, default: Label },
+    Lookupswitch { pairs: Vec<(i32, Label)>, default: Label },
+    Label(Label),
+    LineNumber(u16),
+}
+
+struct Handler {
+    start: Label,
+    end: Label,
+    handler: Label,
+    catch_type: Option<String>,
+}
+
+/// The body of a method, as instructions and labels.
+#[derive(Default)]
+pub struct CodeBuilder {
+    items: Vec<Item>,
+    labels: usize,
+    handlers: Vec<Handler>,
+}
+
+// An instruction with its address, a handler as start, end, handler and
+// catch type, and a line number entry as address and line
+type Layout = (Vec<(usize, Instruction)>, Vec<(usize, usize, usize, Option<String>)>, Vec<(usize, u16)>);
+
+impl CodeBuilder {
+    pub fn new() -> CodeBuilder {
+        CodeBuilder::default()
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels += 1;
+        Label(self.labels - 1)
+    }
+
+    /// Attaches `label` to the next instruction.
+    pub fn place(&mut self, label: Label) {
+        self.items.push(Item::Label(label));
+    }
+
+    /// Appends an instruction. Branches should go through `branch` instead,
+    /// so that their offsets are computed.
+    pub fn emit(&mut self, instruction: Instruction) {
+        self.items.push(Item::Instruction(instruction));
+    }
+
+    /// Appends a branch to `target`, e.g. `branch(Instruction::Goto, label)`.
+    pub fn branch(&mut self, instruction: fn(i16) -> Instruction, target: Label) {
+        self.items.push(Item::Branch(instruction, target));
+    }
+
+    /// Appends a `goto_w` or `jsr_w` to `target`.
+    pub fn branch_wide(&mut self, instruction: fn(i32) -> Instruction, target: Label) {
+        self.items.push(Item::WideBranch(instruction, target));
+    }
+
+    /// Appends a `tableswitch` jumping to `targets[i]` for `low + i`.
+    pub fn tableswitch(&mut self, low: i32, targets: Vec<Label>, default: Label) {
+        self.items.push(Item::Tableswitch { low, targets, default });
+    }
+
+    /// Appends a `lookupswitch`. The pairs may be given in any order.
+    pub fn lookupswitch(&mut self, mut pairs: Vec<(i32, Label)>, default: Label) {
+        pairs.sort_by_key(|(key, _)| *key);
+        self.items.push(Item::Lookupswitch { pairs, default });
+    }
+
+    /// Marks the next instruction as the start of source line `line`.
+    pub fn line_number(&mut self, line: u16) {
+        self.items.push(Item::LineNumber(line));
+    }
+
+    /// Adds a handler for exceptions of class `catch_type`, or of any class
+    /// for `None`, thrown between `start` and `end`.
+    pub fn exception_handler(&mut self, start: Label, end: Label, handler: Label, catch_type: Option<&str>) {
+        self.handlers.push(Handler { start, end, handler, catch_type: catch_type.map(String::from) });
+    }
+
+    // Gives every instruction its address, then resolves branch offsets
+    fn layout(self) -> Result<Layout, AssembleError> {
+        let mut addresses: Vec<Option<usize>> = vec![None; self.labels];
+        let mut pc = 0;
+        for item in &self.items {
+            pc += match item {
+                Item::Instruction(instruction) => instruction.length(pc),
+                Item::Branch(_, _) => 3,
+                Item::WideBranch(_, _) => 5,
+                Item::Tableswitch { low, targets, .. } => Instruction::Tableswitch {
+                    default: 0,
+                    low: *low,
+                    high: switch_high(pc, *low, targets)?,
+                    offsets: vec![0; targets.len()],
+                }.length(pc),
+                Item::Lookupswitch { pairs, .. } => {
+                    if let Some(pair) = pairs.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+                        return Err(AssembleError::DuplicateSwitchKey { pc, key: pair[0].0 });
+                    }
+                    Instruction::Lookupswitch {
+                        default: 0,
+                        pairs: vec![(0, 0); pairs.len()],
+                    }.length(pc)
+                },
+                Item::Label(label) => {
+                    if addresses[label.0].is_some() {
+                        return Err(AssembleError::LabelPlacedTwice);
+                    }
+                    addresses[label.0] = Some(pc);
+                    0
+                },
+                Item::LineNumber(_) => 0,
+            }
+        }
+        let address = |label: &Label| addresses[label.0].ok_or(AssembleError::UnplacedLabel);
+        let offset = |pc: usize, label: &Label| Ok(address(label)? as i32 - pc as i32);
+
+        let mut instructions: Vec<(usize, Instruction)> = vec![];
+        let mut line_numbers: Vec<(usize, u16)> = vec![];
+        let mut pc = 0;
+        for item in self.items {
+            let instruction = match item {
+                Item::Instruction(instruction) => instruction,
+                Item::Branch(instruction, target) => {
+                    let offset = offset(pc, &target)?;
+                    if offset < i16::MIN as i32 || offset > i16::MAX as i32 {
+                        return Err(AssembleError::BranchTooFar { pc });
+                    }
+                    instruction(offset as i16)
+                },
+                Item::WideBranch(instruction, target) => instruction(offset(pc, &target)?),
+                Item::Tableswitch { low, targets, default } => Instruction::Tableswitch {
+                    default: offset(pc, &default)?,
+                    low,
+                    high: switch_high(pc, low, &targets)?,
+                    offsets: targets.iter()
+                        .map(|target| offset(pc, target))
+                        .collect::<Result<Vec<i32>, AssembleError>>()?,
+                },
+                Item::Lookupswitch { pairs, default } => Instruction::Lookupswitch {
+                    default: offset(pc, &default)?,
+                    pairs: pairs.iter()
+                        .map(|(key, target)| Ok((*key, offset(pc, target)?)))
+                        .collect::<Result<Vec<(i32, i32)>, AssembleError>>()?,
+                },
+                Item::Label(_) => continue,
+                Item::LineNumber(line) => {
+                    line_numbers.push((pc, line));
+                    continue;
+                },
+            };
+            let length = instruction.length(pc);
+            instructions.push((pc, instruction));
+            pc += length;
+        }
+
+        let mut handlers = vec![];
+        for handler in self.handlers {
+            handlers.push((address(&handler.start)?, address(&handler.end)?, address(&handler.handler)?,
+                handler.catch_type));
+        }
+        Ok((instructions, handlers, line_numbers))
+    }
+}
+
+// The last key of a `tableswitch` at `pc` with a target for each key from `low`
+fn switch_high(pc: usize, low: i32, targets: &[Label]) -> Result<i32, AssembleError> {
+    targets.len().checked_sub(1)
+        .and_then(|n| i32::try_from(n).ok())
+        .and_then(|n| low.checked_add(n))
+        .ok_or(AssembleError::BadSwitchRange { pc })
+}
+
+// The type of a local variable or operand stack entry during inference. A
+// `Long` or `Double` local is followed by a `Top` for its second slot
+#[derive(Clone, Debug, PartialEq)]
+enum Type {
+    Top,
+    Int,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    Object(String),
+    /// Created by the `new` at this address
+    Uninitialized(usize),
+}
+
+impl Type {
+    fn size(&self) -> usize {
+        match self {
+            Type::Long | Type::Double => 2,
+            _ => 1,
+        }
+    }
+
+    fn is_reference(&self) -> bool {
+        matches!(self, Type::Null | Type::UninitializedThis | Type::Object(_) | Type::Uninitialized(_))
+    }
+
+    // Whether a value of this type can be used where `expected` is. Without
+    // the class hierarchy, any reference will do where one is expected
+    fn matches(&self, expected: &Type) -> bool {
+        if expected.is_reference() {
+            self.is_reference()
+        } else {
+            self == expected
+        }
+    }
+
+    fn object(name: &str) -> Type {
+        Type::Object(name.to_string())
+    }
+
+    fn from_field_type(field_type: &FieldType) -> Type {
+        match field_type {
+            FieldType::Base(BaseType::Long) => Type::Long,
+            FieldType::Base(BaseType::Float) => Type::Float,
+            FieldType::Base(BaseType::Double) => Type::Double,
+            // Narrower integral types are ints on the operand stack
+            FieldType::Base(_) => Type::Int,
+            FieldType::Object(class_name) => Type::object(class_name),
+            // Array classes are named by their descriptor
+            FieldType::Array { .. } => Type::Object(field_type.descriptor()),
+        }
+    }
+
+    // The most specific type both can be used as, if any
+    fn merge(&self, other: &Type) -> Option<Type> {
+        match (self, other) {
+            _ if self == other => Some(self.clone()),
+            (Type::Null, Type::Object(_)) => Some(other.clone()),
+            (Type::Object(_), Type::Null) => Some(self.clone()),
+            (Type::Object(_), Type::Object(_)) => Some(Type::object("java/lang/Object")),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct State {
+    locals: Vec<Type>,
+    stack: Vec<Type>,
+}
+
+impl State {
+    // Merges `other` into this state, returning whether anything changed
+    fn merge(&mut self, other: &State, pc: usize) -> Result<bool, AssembleError> {
+        if self.stack.len() != other.stack.len() {
+            return Err(AssembleError::StackMismatch { pc });
+        }
+        let mut changed = false;
+        for (local, other) in self.locals.iter_mut().zip(&other.locals) {
+            let merged = local.merge(other).unwrap_or(Type::Top);
+            changed |= merged != *local;
+            *local = merged;
+        }
+        for (item, other) in self.stack.iter_mut().zip(&other.stack) {
+            let merged = item.merge(other).ok_or(AssembleError::StackMismatch { pc })?;
+            changed |= merged != *item;
+            *item = merged;
+        }
+        Ok(changed)
+    }
+}
+
+// Turns slot-per-entry locals into the form used in stack map frames, where
+// a `Long` or `Double` is one entry and trailing unusable locals are left out
+fn frame_locals(locals: &[Type]) -> Vec<Type> {
+    let mut frame: Vec<Type> = vec![];
+    let mut i = 0;
+    while i < locals.len() {
+        frame.push(locals[i].clone());
+        i += locals[i].size();
+    }
+    while frame.last() == Some(&Type::Top) {
+        frame.pop();
+    }
+    frame
+}
+
+struct MethodType<'a> {
+    is_static: bool,
+    name: &'a str,
+    descriptor: &'a str,
+    class_name: &'a str,
+}
+
+struct Analysis {
+    max_stack: u16,
+    max_locals: u16,
+    initial_locals: Vec<Type>,
+    /// The state at each branch target and handler, in address order
+    frames: Vec<(usize, State)>,
+}
+
+// The name and descriptor of a field, method or dynamic constant reference
+fn member(constant_pool: &ConstantPool, index: u16) -> Option<(&str, &str)> {
+    match constant_pool.get(index).ok()? {
+        ConstantPoolEnum::ConstantDynamicItem(x) => constant_pool.name_and_type(x.name_and_type_index).ok(),
+        ConstantPoolEnum::ConstantInvokeDynamicItem(x) => constant_pool.name_and_type(x.name_and_type_index).ok(),
+        _ => constant_pool.member_ref(MemberRefIndex(index)).ok().map(|(_, name, descriptor)| (name, descriptor)),
+    }
+}
+
+// The local variable an instruction reads or writes, and how many slots
+fn local_access(instruction: &Instruction) -> Option<(usize, usize)> {
+    match instruction {
+        Instruction::Iload(n) | Instruction::Fload(n) | Instruction::Aload(n)
+        | Instruction::Istore(n) | Instruction::Fstore(n) | Instruction::Astore(n)
+        | Instruction::Iinc(n, _) | Instruction::Ret(n) => Some((*n as usize, 1)),
+        Instruction::Lload(n) | Instruction::Dload(n)
+        | Instruction::Lstore(n) | Instruction::Dstore(n) => Some((*n as usize, 2)),
+        Instruction::Iload0 | Instruction::Fload0 | Instruction::Aload0
+        | Instruction::Istore0 | Instruction::Fstore0 | Instruction::Astore0 => Some((0, 1)),
+        Instruction::Iload1 | Instruction::Fload1 | Instruction::Aload1
+        | Instruction::Istore1 | Instruction::Fstore1 | Instruction::Astore1 => Some((1, 1)),
+        Instruction::Iload2 | Instruction::Fload2 | Instruction::Aload2
+        | Instruction::Istore2 | Instruction::Fstore2 | Instruction::Astore2 => Some((2, 1)),
+        Instruction::Iload3 | Instruction::Fload3 | Instruction::Aload3
+        | Instruction::Istore3 | Instruction::Fstore3 | Instruction::Astore3 => Some((3, 1)),
+        Instruction::Lload0 | Instruction::Dload0 | Instruction::Lstore0 | Instruction::Dstore0 => Some((0, 2)),
+        Instruction::Lload1 | Instruction::Dload1 | Instruction::Lstore1 | Instruction::Dstore1 => Some((1, 2)),
+        Instruction::Lload2 | Instruction::Dload2 | Instruction::Lstore2 | Instruction::Dstore2 => Some((2, 2)),
+        Instruction::Lload3 | Instruction::Dload3 | Instruction::Lstore3 | Instruction::Dstore3 => Some((3, 2)),
+        Instruction::Wide(instruction) => local_access(instruction),
+        _ => None,
+    }
+}
+
+fn analyze(instructions: &[(usize, Instruction)], exception_table: &[ExceptionTableEntry],
+    constant_pool: &ConstantPool, method: &MethodType) -> Result<Analysis, AssembleError> {
+    let descriptor = MethodDescriptor::parse(method.descriptor)?;
+    // `this` counts towards the limit of 255 parameter slots
+    if !method.is_static && descriptor.argument_slots() >= 255 {
+        return Err(AssembleError::BadDescriptor { descriptor: method.descriptor.to_string() });
+    }
+    let parameters = descriptor.parameters;
+    let mut initial_locals: Vec<Type> = vec![];
+    if !method.is_static {
+        initial_locals.push(if method.name == "<init>" {
+            Type::UninitializedThis
+        } else {
+            Type::object(method.class_name)
+        });
+    }
+    for parameter in &parameters {
+        initial_locals.push(Type::from_field_type(parameter));
+        if parameter.slots() == 2 {
+            initial_locals.push(Type::Top);
+        }
+    }
+    let max_locals = instructions.iter()
+        .filter_map(|(_, instruction)| local_access(instruction))
+        .map(|(index, size)| index + size)
+        .chain(std::iter::once(initial_locals.len()))
+        .max()
+        .unwrap_or(0);
+    initial_locals.resize(max_locals, Type::Top);
+
+    let index_of: HashMap<usize, usize> = instructions.iter().enumerate()
+        .map(|(i, (pc, _))| (*pc, i))
+        .collect();
+    let mut frame_pcs: Vec<usize> = exception_table.iter().map(|entry| entry.handler_pc as usize).collect();
+    for (pc, instruction) in instructions {
+        frame_pcs.extend(targets(*pc, instruction));
+    }
+    frame_pcs.sort_unstable();
+    frame_pcs.dedup();
+
+    let mut states: Vec<Option<State>> = vec![None; instructions.len()];
+    states[0] = Some(State { locals: initial_locals.clone(), stack: vec![] });
+    let mut worklist: Vec<usize> = vec![0];
+    let mut max_stack = 0;
+    while let Some(i) = worklist.pop() {
+        let (pc, instruction) = &instructions[i];
+        let before = states[i].clone().unwrap();
+        let after = execute(*pc, instruction, &before, instructions, constant_pool, method)?;
+        max_stack = max_stack.max(after.stack.iter().map(Type::size).sum());
+
+        let mut successors: Vec<(usize, State)> = vec![];
+        let branch_targets = targets(*pc, instruction);
+        for target in &branch_targets {
+            successors.push((*target, after.clone()));
+        }
+        let ends_block = matches!(instruction,
+            Instruction::Goto(_) | Instruction::GotoW(_)
+            | Instruction::Tableswitch { .. } | Instruction::Lookupswitch { .. }
+            | Instruction::Ireturn | Instruction::Lreturn | Instruction::Freturn | Instruction::Dreturn
+            | Instruction::Areturn | Instruction::Return | Instruction::Athrow);
+        if !ends_block {
+            match instructions.get(i + 1) {
+                Some((next_pc, _)) => successors.push((*next_pc, after.clone())),
+                None => return Err(AssembleError::FallsOffEnd { pc: *pc }),
+            }
+        }
+        for entry in exception_table.iter().filter(|entry| entry.covers(*pc)) {
+            // The exception may be thrown before or after a local is stored
+            let mut locals = before.locals.clone();
+            for (local, other) in locals.iter_mut().zip(&after.locals) {
+                *local = local.merge(other).unwrap_or(Type::Top);
+            }
+            let catch_type = entry.catch_type_name.as_deref().unwrap_or("java/lang/Throwable");
+            successors.push((entry.handler_pc as usize, State { locals, stack: vec![Type::object(catch_type)] }));
+            max_stack = max_stack.max(1);
+        }
+
+        for (target, state) in successors {
+            let j = *index_of.get(&target).ok_or(AssembleError::BranchTooFar { pc: *pc })?;
+            let changed = match &mut states[j] {
+                Some(existing) => existing.merge(&state, target)?,
+                None => {
+                    states[j] = Some(state);
+                    true
+                },
+            };
+            if changed {
+                worklist.push(j);
+            }
+        }
+    }
+
+    if let Some(i) = states.iter().position(Option::is_none) {
+        return Err(AssembleError::UnreachableCode { pc: instructions[i].0 });
+    }
+    let frames = frame_pcs.iter()
+        .map(|pc| (*pc, states[index_of[pc]].clone().unwrap()))
+        .collect();
+    Ok(Analysis {
+        max_stack: max_stack as u16,
+        max_locals: max_locals as u16,
+        initial_locals,
+        frames,
+    })
+}
+
+// The addresses a branch or switch can jump to
+fn targets(pc: usize, instruction: &Instruction) -> Vec<usize> {
+    let target = |offset: i32| (pc as i64 + offset as i64) as usize;
+    match instruction {
+        Instruction::Tableswitch { default, offsets, .. } =>
+            std::iter::once(default).chain(offsets).map(|offset| target(*offset)).collect(),
+        Instruction::Lookupswitch { default, pairs } =>
+            std::iter::once(default).chain(pairs.iter().map(|(_, offset)| offset))
+                .map(|offset| target(*offset)).collect(),
+        _ => instruction.branch_offset().map(target).into_iter().collect(),
+    }
+}
+
+// Computes the state after `instruction`
+fn execute(pc: usize, instruction: &Instruction, before: &State, instructions: &[(usize, Instruction)],
+    constant_pool: &ConstantPool, method: &MethodType) -> Result<State, AssembleError> {
+    let mut state = before.clone();
+    let bad_constant = |index: u16| AssembleError::BadConstant { pc, index };
+
+    macro_rules! pop {
+        () => { state.stack.pop().ok_or(AssembleError::StackUnderflow { pc })? };
+    }
+    // Pops a value that must match `expected`, where `Type::Null` stands for
+    // any reference
+    macro_rules! pop_as {
+        ($expected:expr) => {{
+            let value = pop!();
+            if !value.matches(&$expected) {
+                return Err(AssembleError::TypeMismatch { pc });
+            }
+            value
+        }};
+    }
+    // Pops entries making up `n` slots, in stack order
+    macro_rules! pop_slots {
+        ($n:expr) => {{
+            let mut items: Vec<Type> = vec![];
+            let mut slots = 0;
+            while slots < $n {
+                let item = state.stack.pop().ok_or(AssembleError::StackUnderflow { pc })?;
+                slots += item.size();
+                items.insert(0, item);
+            }
+            if slots != $n {
+                return Err(AssembleError::TypeMismatch { pc });
+            }
+            items
+        }};
+    }
+
+    let load = |state: &mut State, index: usize, expected: Type| -> Result<(), AssembleError> {
+        let local = state.locals.get(index).cloned().ok_or(AssembleError::TypeMismatch { pc })?;
+        if !local.matches(&expected) {
+            return Err(AssembleError::TypeMismatch { pc });
+        }
+        state.stack.push(local);
+        Ok(())
+    };
+    let store = |state: &mut State, index: usize, expected: Type| -> Result<(), AssembleError> {
+        let value = state.stack.pop().ok_or(AssembleError::StackUnderflow { pc })?;
+        if !value.matches(&expected) {
+            return Err(AssembleError::TypeMismatch { pc });
+        }
+        if index > 0 && state.locals[index - 1].size() == 2 {
+            state.locals[index - 1] = Type::Top;
+        }
+        if value.size() == 2 {
+            state.locals[index + 1] = Type::Top;
+        }
+        state.locals[index] = value;
+        Ok(())
+    };
+
+    match instruction {
+        Instruction::Wide(instruction) =>
+            return execute(pc, instruction, before, instructions, constant_pool, method),
+        Instruction::Nop => {},
+        Instruction::AconstNull => state.stack.push(Type::Null),
+        Instruction::IconstM1 | Instruction::Iconst0 | Instruction::Iconst1 | Instruction::Iconst2
+        | Instruction::Iconst3 | Instruction::Iconst4 | Instruction::Iconst5
+        | Instruction::Bipush(_) | Instruction::Sipush(_) => state.stack.push(Type::Int),
+        Instruction::Lconst0 | Instruction::Lconst1 => state.stack.push(Type::Long),
+        Instruction::Fconst0 | Instruction::Fconst1 | Instruction::Fconst2 => state.stack.push(Type::Float),
+        Instruction::Dconst0 | Instruction::Dconst1 => state.stack.push(Type::Double),
+        Instruction::Ldc(_) | Instruction::LdcW(_) | Instruction::Ldc2W(_) => {
+            let index = match instruction {
+                Instruction::Ldc(index) => *index as u16,
+                Instruction::LdcW(index) | Instruction::Ldc2W(index) => *index,
+                _ => unreachable!(),
+            };
+            let constant = constant_pool.get(index).ok();
+            let t = match constant {
+                Some(ConstantPoolEnum::ConstantIntegerItem(_)) => Type::Int,
+                Some(ConstantPoolEnum::ConstantFloatItem(_)) => Type::Float,
+                Some(ConstantPoolEnum::ConstantLongItem(_)) => Type::Long,
+                Some(ConstantPoolEnum::ConstantDoubleItem(_)) => Type::Double,
+                Some(ConstantPoolEnum::ConstantStringItem(_)) => Type::object("java/lang/String"),
+                Some(ConstantPoolEnum::ConstantClassItem(_)) => Type::object("java/lang/Class"),
+                Some(ConstantPoolEnum::ConstantMethodTypeItem(_)) => Type::object("java/lang/invoke/MethodType"),
+                Some(ConstantPoolEnum::ConstantMethodHandleItem(_)) => Type::object("java/lang/invoke/MethodHandle"),
+                Some(ConstantPoolEnum::ConstantDynamicItem(_)) => {
+                    let (_, descriptor) = member(constant_pool, index).ok_or_else(|| bad_constant(index))?;
+                    Type::from_field_type(&FieldType::parse(descriptor)?)
+                },
+                _ => return Err(bad_constant(index)),
+            };
+            if (t.size() == 2) != matches!(instruction, Instruction::Ldc2W(_)) {
+                return Err(bad_constant(index));
+            }
+            state.stack.push(t);
+        },
+
+        Instruction::Iload(_) | Instruction::Iload0 | Instruction::Iload1 | Instruction::Iload2 | Instruction::Iload3 =>
+            load(&mut state, local_access(instruction).unwrap().0, Type::Int)?,
+        Instruction::Lload(_) | Instruction::Lload0 | Instruction::Lload1 | Instruction::Lload2 | Instruction::Lload3 =>
+            load(&mut state, local_access(instruction).unwrap().0, Type::Long)?,
+        Instruction::Fload(_) | Instruction::Fload0 | Instruction::Fload1 | Instruction::Fload2 | Instruction::Fload3 =>
+            load(&mut state, local_access(instruction).unwrap().0, Type::Float)?,
+        Instruction::Dload(_) | Instruction::Dload0 | Instruction::Dload1 | Instruction::Dload2 | Instruction::Dload3 =>
+            load(&mut state, local_access(instruction).unwrap().0, Type::Double)?,
+        Instruction::Aload(_) | Instruction::Aload0 | Instruction::Aload1 | Instruction::Aload2 | Instruction::Aload3 =>
+            load(&mut state, local_access(instruction).unwrap().0, Type::Null)?,
+        Instruction::Istore(_) | Instruction::Istore0 | Instruction::Istore1 | Instruction::Istore2 | Instruction::Istore3 =>
+            store(&mut state, local_access(instruction).unwrap().0, Type::Int)?,
+        Instruction::Lstore(_) | Instruction::Lstore0 | Instruction::Lstore1 | Instruction::Lstore2 | Instruction::Lstore3 =>
+            store(&mut state, local_access(instruction).unwrap().0, Type::Long)?,
+        Instruction::Fstore(_) | Instruction::Fstore0 | Instruction::Fstore1 | Instruction::Fstore2 | Instruction::Fstore3 =>
+            store(&mut state, local_access(instruction).unwrap().0, Type::Float)?,
+        Instruction::Dstore(_) | Instruction::Dstore0 | Instruction::Dstore1 | Instruction::Dstore2 | Instruction::Dstore3 =>
+            store(&mut state, local_access(instruction).unwrap().0, Type::Double)?,
+        Instruction::Astore(_) | Instruction::Astore0 | Instruction::Astore1 | Instruction::Astore2 | Instruction::Astore3 =>
+            store(&mut state, local_access(instruction).unwrap().0, Type::Null)?,
+        Instruction::Iinc(index, _) => {
+            if state.locals.get(*index as usize) != Some(&Type::Int) {
+                return Err(AssembleError::TypeMismatch { pc });
+            }
+        },
+
+        Instruction::Iaload | Instruction::Baload | Instruction::Caload | Instruction::Saload
+        | Instruction::Laload | Instruction::Faload | Instruction::Daload | Instruction::Aaload => {
+            pop_as!(Type::Int);
+            let array = pop_as!(Type::Null);
+            state.stack.push(match instruction {
+                Instruction::Laload => Type::Long,
+                Instruction::Faload => Type::Float,
+                Instruction::Daload => Type::Double,
+                Instruction::Aaload => match &array {
+                    Type::Object(name) if name.starts_with('[') => Type::from_field_type(
+                        &FieldType::parse(name)?.component_type().ok_or(AssembleError::TypeMismatch { pc })?),
+                    Type::Null => Type::Null,
+                    _ => return Err(AssembleError::TypeMismatch { pc }),
+                },
+                _ => Type::Int,
+            });
+        },
+        Instruction::Iastore | Instruction::Lastore | Instruction::Fastore | Instruction::Dastore
+        | Instruction::Aastore | Instruction::Bastore | Instruction::Castore | Instruction::Sastore => {
+            pop_as!(match instruction {
+                Instruction::Lastore => Type::Long,
+                Instruction::Fastore => Type::Float,
+                Instruction::Dastore => Type::Double,
+                Instruction::Aastore => Type::Null,
+                _ => Type::Int,
+            });
+            pop_as!(Type::Int);
+            pop_as!(Type::Null);
+        },
+
+        Instruction::Pop => { pop_slots!(1); },
+        Instruction::Pop2 => { pop_slots!(2); },
+        Instruction::Dup => {
+            let a = pop_slots!(1);
+            state.stack.extend(a.clone().into_iter().chain(a));
+        },
+        Instruction::DupX1 => {
+            let a = pop_slots!(1);
+            let b = pop_slots!(1);
+            state.stack.extend(a.clone().into_iter().chain(b).chain(a));
+        },
+        Instruction::DupX2 => {
+            let a = pop_slots!(1);
+            let b = pop_slots!(2);
+            state.stack.extend(a.clone().into_iter().chain(b).chain(a));
+        },
+        Instruction::Dup2 => {
+            let a = pop_slots!(2);
+            state.stack.extend(a.clone().into_iter().chain(a));
+        },
+        Instruction::Dup2X1 => {
+            let a = pop_slots!(2);
+            let b = pop_slots!(1);
+            state.stack.extend(a.clone().into_iter().chain(b).chain(a));
+        },
+        Instruction::Dup2X2 => {
+            let a = pop_slots!(2);
+            let b = pop_slots!(2);
+            state.stack.extend(a.clone().into_iter().chain(b).chain(a));
+        },
+        Instruction::Swap => {
+            let a = pop_slots!(1);
+            let b = pop_slots!(1);
+            state.stack.extend(a.into_iter().chain(b));
+        },
+
+        Instruction::Iadd | Instruction::Isub | Instruction::Imul | Instruction::Idiv | Instruction::Irem
+        | Instruction::Ishl | Instruction::Ishr | Instruction::Iushr
+        | Instruction::Iand | Instruction::Ior | Instruction::Ixor => {
+            pop_as!(Type::Int);
+            pop_as!(Type::Int);
+            state.stack.push(Type::Int);
+        },
+        Instruction::Ladd | Instruction::Lsub | Instruction::Lmul | Instruction::Ldiv | Instruction::Lrem
+        | Instruction::Land | Instruction::Lor | Instruction::Lxor => {
+            pop_as!(Type::Long);
+            pop_as!(Type::Long);
+            state.stack.push(Type::Long);
+        },
+        // The shift distance is an int
+        Instruction::Lshl | Instruction::Lshr | Instruction::Lushr => {
+            pop_as!(Type::Int);
+            pop_as!(Type::Long);
+            state.stack.push(Type::Long);
+        },
+        Instruction::Fadd | Instruction::Fsub | Instruction::Fmul | Instruction::Fdiv | Instruction::Frem => {
+            pop_as!(Type::Float);
+            pop_as!(Type::Float);
+            state.stack.push(Type::Float);
+        },
+        Instruction::Dadd | Instruction::Dsub | Instruction::Dmul | Instruction::Ddiv | Instruction::Drem => {
+            pop_as!(Type::Double);
+            pop_as!(Type::Double);
+            state.stack.push(Type::Double);
+        },
+        Instruction::Lcmp | Instruction::Fcmpl | Instruction::Fcmpg | Instruction::Dcmpl | Instruction::Dcmpg => {
+            let t = match instruction {
+                Instruction::Lcmp => Type::Long,
+                Instruction::Fcmpl | Instruction::Fcmpg => Type::Float,
+                _ => Type::Double,
+            };
+            pop_as!(t);
+            pop_as!(t);
+            state.stack.push(Type::Int);
+        },
+        Instruction::Ineg | Instruction::Lneg | Instruction::Fneg | Instruction::Dneg => {
+            let t = match instruction {
+                Instruction::Ineg => Type::Int,
+                Instruction::Lneg => Type::Long,
+                Instruction::Fneg => Type::Float,
+                _ => Type::Double,
+            };
+            let value = pop_as!(t);
+            state.stack.push(value);
+        },
+        Instruction::I2l | Instruction::I2f | Instruction::I2d
+        | Instruction::L2i | Instruction::L2f | Instruction::L2d
+        | Instruction::F2i | Instruction::F2l | Instruction::F2d
+        | Instruction::D2i | Instruction::D2l | Instruction::D2f
+        | Instruction::I2b | Instruction::I2c | Instruction::I2s => {
+            let (from, to) = match instruction {
+                Instruction::I2l => (Type::Int, Type::Long),
+                Instruction::I2f => (Type::Int, Type::Float),
+                Instruction::I2d => (Type::Int, Type::Double),
+                Instruction::L2i => (Type::Long, Type::Int),
+                Instruction::L2f => (Type::Long, Type::Float),
+                Instruction::L2d => (Type::Long, Type::Double),
+                Instruction::F2i => (Type::Float, Type::Int),
+                Instruction::F2l => (Type::Float, Type::Long),
+                Instruction::F2d => (Type::Float, Type::Double),
+                Instruction::D2i => (Type::Double, Type::Int),
+                Instruction::D2l => (Type::Double, Type::Long),
+                Instruction::D2f => (Type::Double, Type::Float),
+                _ => (Type::Int, Type::Int),
+            };
+            pop_as!(from);
+            state.stack.push(to);
+        },
+
+        Instruction::Ifeq(_) | Instruction::Ifne(_) | Instruction::Iflt(_) | Instruction::Ifge(_)
+        | Instruction::Ifgt(_) | Instruction::Ifle(_)
+        | Instruction::Tableswitch { .. } | Instruction::Lookupswitch { .. } => { pop_as!(Type::Int); },
+        Instruction::Ifnull(_) | Instruction::Ifnonnull(_)
+        | Instruction::Monitorenter | Instruction::Monitorexit | Instruction::Athrow => { pop_as!(Type::Null); },
+        Instruction::IfIcmpeq(_) | Instruction::IfIcmpne(_) | Instruction::IfIcmplt(_) | Instruction::IfIcmpge(_)
+        | Instruction::IfIcmpgt(_) | Instruction::IfIcmple(_) => {
+            pop_as!(Type::Int);
+            pop_as!(Type::Int);
+        },
+        Instruction::IfAcmpeq(_) | Instruction::IfAcmpne(_) => {
+            pop_as!(Type::Null);
+            pop_as!(Type::Null);
+        },
+        Instruction::Goto(_) | Instruction::GotoW(_) | Instruction::Return => {},
+        Instruction::Ireturn => { pop_as!(Type::Int); },
+        Instruction::Lreturn => { pop_as!(Type::Long); },
+        Instruction::Freturn => { pop_as!(Type::Float); },
+        Instruction::Dreturn => { pop_as!(Type::Double); },
+        Instruction::Areturn => { pop_as!(Type::Null); },
+
+        Instruction::Getstatic(index) | Instruction::Putstatic(index)
+        | Instruction::Getfield(index) | Instruction::Putfield(index) => {
+            let (_, descriptor) = member(constant_pool, *index).ok_or_else(|| bad_constant(*index))?;
+            let t = Type::from_field_type(&FieldType::parse(descriptor)?);
+            match instruction {
+                Instruction::Getstatic(_) => state.stack.push(t),
+                Instruction::Putstatic(_) => { pop_as!(t); },
+                Instruction::Getfield(_) => {
+                    pop_as!(Type::Null);
+                    state.stack.push(t);
+                },
+                _ => {
+                    pop_as!(t);
+                    pop_as!(Type::Null);
+                },
+            }
+        },
+        Instruction::Invokevirtual(index) | Instruction::Invokespecial(index)
+        | Instruction::Invokestatic(index) | Instruction::Invokeinterface(index, _)
+        | Instruction::Invokedynamic(index) => {
+            let (name, descriptor) = member(constant_pool, *index).ok_or_else(|| bad_constant(*index))?;
+            let method_descriptor = MethodDescriptor::parse(descriptor)?;
+            for parameter in method_descriptor.parameters.iter().rev() {
+                pop_as!(Type::from_field_type(parameter));
+            }
+            if !matches!(instruction, Instruction::Invokestatic(_) | Instruction::Invokedynamic(_)) {
+                let receiver = pop_as!(Type::Null);
+                if name == "<init>" {
+                    // The constructor initializes every copy of the reference
+                    let initialized = match &receiver {
+                        Type::UninitializedThis => Type::object(method.class_name),
+                        Type::Uninitialized(new_pc) => match instructions.iter().find(|(pc, _)| pc == new_pc) {
+                            Some((_, Instruction::New(class_index))) => Type::object(
+                                constant_pool.class_name(ClassIndex(*class_index)).map_err(|_| bad_constant(*class_index))?),
+                            _ => return Err(AssembleError::TypeMismatch { pc }),
+                        },
+                        _ => return Err(AssembleError::TypeMismatch { pc }),
+                    };
+                    for t in state.locals.iter_mut().chain(state.stack.iter_mut()) {
+                        if *t == receiver {
+                            *t = initialized.clone();
+                        }
+                    }
+                }
+            }
+            if let Some(return_type) = &method_descriptor.return_type {
+                state.stack.push(Type::from_field_type(return_type));
+            }
+        },
+        Instruction::New(_) => state.stack.push(Type::Uninitialized(pc)),
+        Instruction::Newarray(atype) => {
+            pop_as!(Type::Int);
+            let element = match atype {
+                4 => "Z",
+                5 => "C",
+                6 => "F",
+                7 => "D",
+                8 => "B",
+                9 => "S",
+                10 => "I",
+                11 => "J",
+                _ => return Err(AssembleError::TypeMismatch { pc }),
+            };
+            state.stack.push(Type::Object(format!("[{}", element)));
+        },
+        Instruction::Anewarray(index) => {
+            pop_as!(Type::Int);
+            let name = constant_pool.class_name(ClassIndex(*index)).map_err(|_| bad_constant(*index))?;
+            state.stack.push(Type::Object(if name.starts_with('[') {
+                format!("[{}", name)
+            } else {
+                format!("[L{};", name)
+            }));
+        },
+        Instruction::Multianewarray(index, dimensions) => {
+            for _ in 0..*dimensions {
+                pop_as!(Type::Int);
+            }
+            let name = constant_pool.class_name(ClassIndex(*index)).map_err(|_| bad_constant(*index))?;
+            state.stack.push(Type::object(name));
+        },
+        Instruction::Arraylength | Instruction::Instanceof(_) => {
+            pop_as!(Type::Null);
+            state.stack.push(Type::Int);
+        },
+        Instruction::Checkcast(index) => {
+            pop_as!(Type::Null);
+            let name = constant_pool.class_name(ClassIndex(*index)).map_err(|_| bad_constant(*index))?;
+            state.stack.push(Type::object(name));
+        },
+        _ => return Err(AssembleError::Unsupported { pc, mnemonic: instruction.mnemonic() }),
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // static int count(int n) {
+    //     int sum = 0;
+    //     for (; n > 0; n--) {
+    //         switch (n) { case 1: sum += 1; break; case 2: sum += 2; break; default: sum += 3; }
+    //     }
+    //     return sum;
+    // }
+    fn count_method() -> CodeBuilder {
+        let mut code = CodeBuilder::new();
+        let top = code.new_label();
+        let done = code.new_label();
+        let one = code.new_label();
+        let two = code.new_label();
+        let other = code.new_label();
+        let next = code.new_label();
+        code.emit(Instruction::Iconst0);
+        code.emit(Instruction::Istore1);
+        code.place(top);
+        code.emit(Instruction::Iload0);
+        code.branch(Instruction::Ifle, done);
+        code.emit(Instruction::Iload0);
+        code.tableswitch(1, vec![one, two], other);
+        code.place(one);
+        code.emit(Instruction::Iinc(1, 1));
+        code.branch(Instruction::Goto, next);
+        code.place(two);
+        code.emit(Instruction::Iinc(1, 2));
+        code.branch(Instruction::Goto, next);
+        code.place(other);
+        code.emit(Instruction::Iinc(1, 3));
+        code.place(next);
+        code.emit(Instruction::Iinc(0, -1));
+        code.branch(Instruction::Goto, top);
+        code.place(done);
+        code.emit(Instruction::Iload1);
+        code.emit(Instruction::Ireturn);
+        code
+    }
+
+    #[test]
+    fn assembles_branches_and_switch() {
+        let mut class = ClassBuilder::new("Count", "java/lang/Object");
+        class.add_method(ACC_STATIC, "count", "(I)I", count_method()).unwrap();
+        let class = class.build();
+        let code = class.methods[0].get_code_attribute();
+
+        assert_eq!(code.code, vec![
+            0x03, // 0: iconst_0
+            0x3c, // 1: istore_1
+            0x1a, // 2: iload_0
+            0x9e, 0, 46, // 3: ifle 49
+            0x1a, // 6: iload_0
+            0xaa, // 7: tableswitch, no padding needed
+            0, 0, 0, 33, // default 40
+            0, 0, 0, 1, // low
+            0, 0, 0, 2, // high
+            0, 0, 0, 21, // 1: 28
+            0, 0, 0, 27, // 2: 34
+            0x84, 1, 1, // 28: iinc 1, 1
+            0xa7, 0, 12, // 31: goto 43
+            0x84, 1, 2, // 34: iinc 1, 2
+            0xa7, 0, 6, // 37: goto 43
+            0x84, 1, 3, // 40: iinc 1, 3
+            0x84, 0, 0xff, // 43: iinc 0, -1
+            0xa7, 0xff, 0xd4, // 46: goto 2
+            0x1b, // 49: iload_1
+            0xac, // 50: ireturn
+        ]);
+        assert_eq!(code.max_stack, 1);
+        assert_eq!(code.max_locals, 2);
+
+        let entries = code.attributes.iter()
+            .find_map(|attribute| match attribute {
+                AttributeEnum::StackMapTableItem(table) => Some(&table.entries),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(entries.len(), 6);
+        // `sum` is added at the loop head, and every other target has the same locals
+        assert!(matches!(&entries[0], StackMapFrame::AppendFrame { frame_type: 252, offset_delta: 2, locals }
+            if matches!(locals[..], [VerificationTypeInfo::Integer])));
+        let offset_deltas: Vec<u16> = entries[1..].iter()
+            .map(|entry| match entry {
+                StackMapFrame::SameFrame { frame_type } => *frame_type as u16,
+                _ => panic!("expected a same_frame"),
+            })
+            .collect();
+        assert_eq!(offset_deltas, vec![25, 5, 5, 2, 5]);
+    }
+
+    #[test]
+    fn rejects_empty_tableswitch() {
+        let mut code = CodeBuilder::new();
+        let default = code.new_label();
+        code.emit(Instruction::Iload0);
+        code.tableswitch(0, vec![], default);
+        code.place(default);
+        code.emit(Instruction::Return);
+        let mut class = ClassBuilder::new("Empty", "java/lang/Object");
+        let result = class.add_method(ACC_STATIC, "f", "(I)V", code);
+        assert!(matches!(result, Err(AssembleError::BadSwitchRange { pc: 1 })));
+    }
+
+    #[test]
+    fn reports_full_constant_pool() {
+        // The two class names take the first four entries
+        let mut class = ClassBuilder::new("Full", "java/lang/Object");
+        for value in 0..65529 {
+            class.integer(value).unwrap();
+        }
+        assert_eq!(class.integer(0).unwrap(), 5);
+        assert!(matches!(class.long(0), Err(AssembleError::ConstantPoolFull)));
+        assert_eq!(class.integer(65529).unwrap(), 65534);
+        assert!(matches!(class.utf8("Full"), Ok(1)));
+        assert!(matches!(class.utf8("More"), Err(AssembleError::ConstantPoolFull)));
+    }
+
+    #[test]
+    fn rejects_duplicate_lookupswitch_key() {
+        let mut code = CodeBuilder::new();
+        let one = code.new_label();
+        let default = code.new_label();
+        code.emit(Instruction::Iload0);
+        code.lookupswitch(vec![(1, one), (1, default)], default);
+        code.place(one);
+        code.place(default);
+        code.emit(Instruction::Return);
+        let mut class = ClassBuilder::new("Duplicate", "java/lang/Object");
+        let result = class.add_method(ACC_STATIC, "f", "(I)V", code);
+        assert!(matches!(result, Err(AssembleError::DuplicateSwitchKey { pc: 1, key: 1 })));
+    }
+
+    #[test]
+    fn rejects_operands_of_the_wrong_type() {
+        let cases: [(&[Instruction], usize); 4] = [
+            (&[Instruction::Fconst1, Instruction::Iconst1, Instruction::Iadd, Instruction::Ireturn], 2),
+            (&[Instruction::Fconst0, Instruction::Istore0, Instruction::Iconst0, Instruction::Ireturn], 1),
+            (&[Instruction::Iconst1, Instruction::Bipush(3), Instruction::Lshl, Instruction::L2i, Instruction::Ireturn], 3),
+            (&[Instruction::AconstNull, Instruction::Ireturn], 1),
+        ];
+        for (instructions, pc) in cases {
+            let mut code = CodeBuilder::new();
+            for instruction in instructions {
+                code.emit(instruction.clone());
+            }
+            let mut class = ClassBuilder::new("Mismatch", "java/lang/Object");
+            let result = class.add_method(ACC_STATIC, "f", "()I", code);
+            assert!(matches!(result, Err(AssembleError::TypeMismatch { pc: p }) if p == pc), "{:?}", result.map(|_| ()));
+        }
+    }
+
+    // static int classify(int n) {
+    //     try {
+    //         if (n < 0) return 3;
+    //         switch (n) { case 1: return 1; case 7: return 14 / n; default: return 100 / (n - 2); }
+    //     } catch (ArithmeticException e) {
+    //         return -1;
+    //     }
+    // }
+    fn classify_method() -> CodeBuilder {
+        let mut code = CodeBuilder::new();
+        let start = code.new_label();
+        let non_negative = code.new_label();
+        let one = code.new_label();
+        let seven = code.new_label();
+        let other = code.new_label();
+        let end = code.new_label();
+        let handler = code.new_label();
+        code.place(start);
+        code.emit(Instruction::Iload0);
+        code.branch(Instruction::Ifge, non_negative);
+        code.emit(Instruction::Iconst3);
+        code.emit(Instruction::Ireturn);
+        code.place(non_negative);
+        code.emit(Instruction::Iload0);
+        code.lookupswitch(vec![(7, seven), (1, one)], other);
+        code.place(one);
+        code.emit(Instruction::Iconst1);
+        code.emit(Instruction::Ireturn);
+        code.place(seven);
+        code.emit(Instruction::Bipush(14));
+        code.emit(Instruction::Iload0);
+        code.emit(Instruction::Idiv);
+        code.emit(Instruction::Ireturn);
+        code.place(other);
+        code.emit(Instruction::Bipush(100));
+        code.emit(Instruction::Iload0);
+        code.emit(Instruction::Iconst2);
+        code.emit(Instruction::Isub);
+        code.emit(Instruction::Idiv);
+        code.emit(Instruction::Ireturn);
+        code.place(end);
+        code.place(handler);
+        code.emit(Instruction::Pop);
+        code.emit(Instruction::IconstM1);
+        code.emit(Instruction::Ireturn);
+        code.exception_handler(start, end, handler, Some("java/lang/ArithmeticException"));
+        code
+    }
+
+    // Writes the class out and reads it back before running it, so the
+    // bytes are what the parser and interpreter see
+    #[test]
+    fn assembles_a_class_that_parses_and_runs() {
+        let mut class = ClassBuilder::new("Classify", "java/lang/Object");
+        class.add_method(ACC_STATIC, "classify", "(I)I", classify_method()).unwrap();
+        let classify = class.method_ref("Classify", "classify", "(I)I").unwrap();
+        let exit = class.method_ref("java/lang/System", "exit", "(I)V").unwrap();
+        let mut main = CodeBuilder::new();
+        // classify(1) + 10 * classify(7) + 100 * classify(-3) + 1000 * classify(52)
+        main.emit(Instruction::Iconst1);
+        main.emit(Instruction::Invokestatic(classify));
+        for (n, scale) in [(7, 10), (-3, 100), (52, 1000)] {
+            main.emit(Instruction::Bipush(n));
+            main.emit(Instruction::Invokestatic(classify));
+            main.emit(Instruction::Sipush(scale));
+            main.emit(Instruction::Imul);
+            main.emit(Instruction::Iadd);
+        }
+        main.emit(Instruction::Invokestatic(exit));
+        main.emit(Instruction::Return);
+        class.add_method(ACC_PUBLIC | ACC_STATIC, "main", "([Ljava/lang/String;)V", main).unwrap();
+
+        let bytes = crate::write_class::to_bytes(&class.build()).unwrap();
+        let class = crate::read_class::parse_bytes(&bytes).unwrap();
+        let code = class.get_method("classify", "(I)I").unwrap().get_code_attribute();
+        assert_eq!(code.exception_table.len(), 1);
+        assert_eq!(code.exception_table[0].catch_type_name.as_deref(), Some("java/lang/ArithmeticException"));
+        assert_eq!(crate::operation::execute(class), 2321);
+    }
+}
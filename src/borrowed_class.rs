@@ -0,0 +1,520 @@
+//! A class file parser that borrows from the input buffer instead of copying
+//! it, for scanning many classes quickly.
+//!
+//! Only the structure of the class is read up front: the constant pool is
+//! indexed, members and attributes are split into slices of the input, and
+//! the constant pool indexes they hold, like the name of each
+//! `CONSTANT_Class`, are checked to refer to entries of the right kind. `CONSTANT_Utf8` entries are decoded only when asked for, and
+//! without allocating unless they contain NUL or supplementary characters.
+//! Attributes stay raw, except that `Code` can be read on demand.
+//!
+//! Use `read_class` to get a fully decoded `BytecodeClass` instead.
+
+use std::borrow::Cow;
+
+use crate::mutf8;
+use crate::read_class::ClassFormatError;
+
+const CONSTANT_UTF8: u8 = 1;
+const CONSTANT_CLASS: u8 = 7;
+
+// Where lookups of an index that was not read from the class file are
+// reported, as the count is what the index is checked against
+const CONSTANT_POOL_COUNT_OFFSET: usize = 8;
+
+pub struct BorrowedClass<'a> {
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub constant_pool: BorrowedConstantPool<'a>,
+    pub access_flags: u16,
+    pub this_class: u16,
+    /// 0 for `java.lang.Object` and module descriptors
+    pub super_class: u16,
+    pub interfaces: Vec<u16>,
+    pub fields: Vec<BorrowedMember<'a>>,
+    pub methods: Vec<BorrowedMember<'a>>,
+    pub attributes: Vec<BorrowedAttribute<'a>>,
+    /// Offset of `this_class` in the class file, with `super_class` after it
+    this_class_offset: usize,
+}
+
+impl<'a> BorrowedClass<'a> {
+    pub fn this_class_name(&self) -> Result<Cow<'a, str>, ClassFormatError> {
+        self.constant_pool.class_name_at(self.this_class, self.this_class_offset)
+    }
+
+    pub fn super_class_name(&self) -> Result<Option<Cow<'a, str>>, ClassFormatError> {
+        match self.super_class {
+            0 => Ok(None),
+            index => self.constant_pool.class_name_at(index, self.this_class_offset + 2).map(Some),
+        }
+    }
+
+    pub fn get_method(&self, name: &str, descriptor: &str) -> Option<&BorrowedMember<'a>> {
+        self.methods.iter().find(|method| {
+            self.constant_pool.utf8_eq(method.name_index, name)
+                && self.constant_pool.utf8_eq(method.descriptor_index, descriptor)
+        })
+    }
+
+    pub fn get_attribute(&self, name: &str) -> Option<&BorrowedAttribute<'a>> {
+        find_attribute(&self.attributes, &self.constant_pool, name)
+    }
+}
+
+/// The constant pool, as the offsets of its entries in the class file.
+pub struct BorrowedConstantPool<'a> {
+    bytes: &'a [u8],
+    /// Offset of the tag of each entry, or `None` for the unusable entry
+    /// after a `Long` or `Double`
+    entries: Vec<Option<usize>>,
+}
+
+impl<'a> BorrowedConstantPool<'a> {
+    /// The number of entries, counting the unusable ones after `Long` and
+    /// `Double` constants. Valid indexes are `1..=len()`.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The tag of entry `index`, or `None` if there is no such entry.
+    pub fn tag(&self, index: u16) -> Option<u8> {
+        self.entry(index).map(|offset| self.bytes[offset])
+    }
+
+    /// The raw modified UTF-8 bytes of a `CONSTANT_Utf8` entry.
+    ///
+    /// The indexes in a `BorrowedClass` were checked when it was parsed, and
+    /// its methods report errors at the offset of the index they look up.
+    /// An index from elsewhere has no such offset, so an error is reported at
+    /// the `constant_pool_count`.
+    pub fn utf8_bytes(&self, index: u16) -> Result<&'a [u8], ClassFormatError> {
+        self.utf8_bytes_at(index, CONSTANT_POOL_COUNT_OFFSET)
+    }
+
+    pub fn utf8(&self, index: u16) -> Result<Cow<'a, str>, ClassFormatError> {
+        self.utf8_at(index, CONSTANT_POOL_COUNT_OFFSET)
+    }
+
+    /// Whether entry `index` is a `CONSTANT_Utf8` holding `s`. ASCII strings
+    /// are compared without decoding.
+    pub fn utf8_eq(&self, index: u16, s: &str) -> bool {
+        match self.utf8_bytes(index) {
+            Ok(bytes) if s.is_ascii() => bytes == s.as_bytes(),
+            Ok(_) => matches!(self.utf8(index), Ok(utf8) if utf8 == s),
+            Err(_) => false,
+        }
+    }
+
+    pub fn class_name(&self, index: u16) -> Result<Cow<'a, str>, ClassFormatError> {
+        self.class_name_at(index, CONSTANT_POOL_COUNT_OFFSET)
+    }
+
+    fn entry(&self, index: u16) -> Option<usize> {
+        (index as usize).checked_sub(1)
+            .and_then(|i| self.entries.get(i))
+            .copied()
+            .flatten()
+    }
+
+    // Checks the kind of entry `index`, which is found at `offset` in the
+    // class file, and returns the offset of the entry
+    fn expect(&self, index: u16, tag: u8, expected: &'static str, offset: usize) -> Result<usize, ClassFormatError> {
+        match self.entry(index) {
+            Some(entry) if self.bytes[entry] == tag => Ok(entry),
+            Some(_) => Err(ClassFormatError::WrongConstantKind { offset, index, expected }),
+            None => Err(ClassFormatError::BadConstantIndex { offset, index }),
+        }
+    }
+
+    fn utf8_bytes_at(&self, index: u16, offset: usize) -> Result<&'a [u8], ClassFormatError> {
+        let entry = self.expect(index, CONSTANT_UTF8, "Utf8", offset)?;
+        let length = u16::from_be_bytes([self.bytes[entry + 1], self.bytes[entry + 2]]) as usize;
+        Ok(&self.bytes[entry + 3..entry + 3 + length])
+    }
+
+    fn utf8_at(&self, index: u16, offset: usize) -> Result<Cow<'a, str>, ClassFormatError> {
+        let bytes = self.utf8_bytes_at(index, offset)?;
+        mutf8::decode_borrowed(bytes).map_err(|e| {
+            let start = self.entries[index as usize - 1].unwrap_or(0) + 3;
+            ClassFormatError::InvalidUtf8 { offset: start + e.position }
+        })
+    }
+
+    fn class_name_at(&self, index: u16, offset: usize) -> Result<Cow<'a, str>, ClassFormatError> {
+        let entry = self.expect(index, CONSTANT_CLASS, "Class", offset)?;
+        self.utf8_at(u16::from_be_bytes([self.bytes[entry + 1], self.bytes[entry + 2]]), entry + 1)
+    }
+}
+
+/// A `field_info` or `method_info`, which have the same layout.
+pub struct BorrowedMember<'a> {
+    /// Offset of `access_flags` in the class file
+    pub offset: usize,
+    pub access_flags: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes: Vec<BorrowedAttribute<'a>>,
+}
+
+impl<'a> BorrowedMember<'a> {
+    pub fn name(&self, constant_pool: &BorrowedConstantPool<'a>) -> Result<Cow<'a, str>, ClassFormatError> {
+        constant_pool.utf8_at(self.name_index, self.offset + 2)
+    }
+
+    pub fn descriptor(&self, constant_pool: &BorrowedConstantPool<'a>) -> Result<Cow<'a, str>, ClassFormatError> {
+        constant_pool.utf8_at(self.descriptor_index, self.offset + 4)
+    }
+
+    pub fn get_attribute(&self, constant_pool: &BorrowedConstantPool<'a>, name: &str)
+        -> Option<&BorrowedAttribute<'a>> {
+        find_attribute(&self.attributes, constant_pool, name)
+    }
+
+    /// Reads the `Code` attribute of a method, if it has one.
+    pub fn code(&self, constant_pool: &BorrowedConstantPool<'a>) -> Result<Option<BorrowedCode<'a>>, ClassFormatError> {
+        match self.get_attribute(constant_pool, "Code") {
+            Some(attribute) => read_code(attribute, constant_pool).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+pub struct BorrowedAttribute<'a> {
+    pub attribute_name_index: u16,
+    /// Offset of `info` in the class file
+    pub offset: usize,
+    pub info: &'a [u8],
+}
+
+impl<'a> BorrowedAttribute<'a> {
+    pub fn name(&self, constant_pool: &BorrowedConstantPool<'a>) -> Result<Cow<'a, str>, ClassFormatError> {
+        // The name index comes before the four byte length
+        constant_pool.utf8_at(self.attribute_name_index, self.offset - 6)
+    }
+}
+
+pub struct BorrowedCode<'a> {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: &'a [u8],
+    pub exception_table: Vec<ExceptionHandler>,
+    pub attributes: Vec<BorrowedAttribute<'a>>,
+}
+
+/// An entry of a `Code` attribute's exception table. `catch_type` is 0 for
+/// a handler that catches everything.
+#[derive(Copy, Clone)]
+pub struct ExceptionHandler {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    pub catch_type: u16,
+}
+
+// Reads big-endian values from a slice. Offsets are always relative to the
+// start of the class file, so errors inside an attribute point at the right
+// byte
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ClassFormatError> {
+        match self.bytes.get(self.offset..self.offset + length) {
+            Some(bytes) => {
+                self.offset += length;
+                Ok(bytes)
+            },
+            None => Err(ClassFormatError::Truncated { offset: self.offset }),
+        }
+    }
+
+    fn u1(&mut self) -> Result<u8, ClassFormatError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u2(&mut self) -> Result<u16, ClassFormatError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u4(&mut self) -> Result<u32, ClassFormatError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // Reads a constant pool index and checks the kind of entry it refers to
+    fn index(&mut self, constant_pool: &BorrowedConstantPool, tag: u8, expected: &'static str)
+        -> Result<u16, ClassFormatError> {
+        let offset = self.offset;
+        let index = self.u2()?;
+        constant_pool.expect(index, tag, expected, offset)?;
+        Ok(index)
+    }
+
+    // Reads an index which is either 0, for none, or checked like `index`
+    fn optional_index(&mut self, constant_pool: &BorrowedConstantPool, tag: u8, expected: &'static str)
+        -> Result<u16, ClassFormatError> {
+        let offset = self.offset;
+        match self.u2()? {
+            0 => Ok(0),
+            index => {
+                constant_pool.expect(index, tag, expected, offset)?;
+                Ok(index)
+            },
+        }
+    }
+}
+
+/// Parses the structure of the class file in `bytes`.
+pub fn parse(bytes: &[u8]) -> Result<BorrowedClass<'_>, ClassFormatError> {
+    let mut con = Cursor { bytes, offset: 0 };
+    let magic = con.u4()?;
+    if magic != 0xCAFEBABE {
+        return Err(ClassFormatError::BadMagic { offset: 0, magic });
+    }
+    let minor_version = con.u2()?;
+    let major_version = con.u2()?;
+    let constant_pool = read_constant_pool(&mut con)?;
+    let access_flags = con.u2()?;
+    let this_class_offset = con.offset;
+    let this_class = con.index(&constant_pool, CONSTANT_CLASS, "Class")?;
+    let super_class = con.optional_index(&constant_pool, CONSTANT_CLASS, "Class")?;
+    let interfaces_count = con.u2()?;
+    let interfaces: Vec<u16> = (0..interfaces_count)
+        .map(|_x| con.index(&constant_pool, CONSTANT_CLASS, "Class"))
+        .collect::<Result<Vec<u16>, ClassFormatError>>()?;
+    let fields_count = con.u2()?;
+    let fields: Vec<BorrowedMember> = (0..fields_count)
+        .map(|_x| read_member(&mut con, &constant_pool))
+        .collect::<Result<Vec<BorrowedMember>, ClassFormatError>>()?;
+    let methods_count = con.u2()?;
+    let methods: Vec<BorrowedMember> = (0..methods_count)
+        .map(|_x| read_member(&mut con, &constant_pool))
+        .collect::<Result<Vec<BorrowedMember>, ClassFormatError>>()?;
+    let attributes = read_attributes(&mut con, &constant_pool)?;
+    if con.offset != bytes.len() {
+        return Err(ClassFormatError::TrailingBytes { offset: con.offset });
+    }
+    Ok(BorrowedClass {
+        minor_version,
+        major_version,
+        constant_pool,
+        access_flags,
+        this_class,
+        super_class,
+        interfaces,
+        fields,
+        methods,
+        attributes,
+        this_class_offset,
+    })
+}
+
+fn read_constant_pool<'a>(con: &mut Cursor<'a>) -> Result<BorrowedConstantPool<'a>, ClassFormatError> {
+    let constant_pool_count = con.u2()?;
+    let mut entries: Vec<Option<usize>> = Vec::with_capacity(constant_pool_count as usize);
+    while entries.len() < constant_pool_count.saturating_sub(1) as usize {
+        let offset = con.offset;
+        let tag = con.u1()?;
+        let length = match tag {
+            1 => con.u2()? as usize,
+            7 | 8 | 16 | 19 | 20 => 2,
+            15 => 3,
+            3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => 4,
+            5 | 6 => 8,
+            _ => return Err(ClassFormatError::BadConstantTag { offset, tag }),
+        };
+        con.take(length)?;
+        entries.push(Some(offset));
+        if tag == 5 || tag == 6 {
+            entries.push(None);
+        }
+    }
+    let constant_pool = BorrowedConstantPool { bytes: con.bytes, entries };
+    // Class names are looked up lazily, so check them while the offsets of
+    // their indexes are known
+    for offset in constant_pool.entries.iter().flatten() {
+        if constant_pool.bytes[*offset] == CONSTANT_CLASS {
+            let mut name_index = Cursor { bytes: con.bytes, offset: offset + 1 };
+            name_index.index(&constant_pool, CONSTANT_UTF8, "Utf8")?;
+        }
+    }
+    Ok(constant_pool)
+}
+
+fn read_member<'a>(con: &mut Cursor<'a>, constant_pool: &BorrowedConstantPool)
+    -> Result<BorrowedMember<'a>, ClassFormatError> {
+    Ok(BorrowedMember {
+        offset: con.offset,
+        access_flags: con.u2()?,
+        name_index: con.index(constant_pool, CONSTANT_UTF8, "Utf8")?,
+        descriptor_index: con.index(constant_pool, CONSTANT_UTF8, "Utf8")?,
+        attributes: read_attributes(con, constant_pool)?,
+    })
+}
+
+fn read_attributes<'a>(con: &mut Cursor<'a>, constant_pool: &BorrowedConstantPool)
+    -> Result<Vec<BorrowedAttribute<'a>>, ClassFormatError> {
+    let attributes_count = con.u2()?;
+    let mut attributes: Vec<BorrowedAttribute> = Vec::with_capacity(attributes_count as usize);
+    for _ in 0..attributes_count {
+        let attribute_name_index = con.index(constant_pool, CONSTANT_UTF8, "Utf8")?;
+        let attribute_length = con.u4()?;
+        let offset = con.offset;
+        let info = con.take(attribute_length as usize)?;
+        attributes.push(BorrowedAttribute { attribute_name_index, offset, info });
+    }
+    Ok(attributes)
+}
+
+fn find_attribute<'a, 'b>(attributes: &'b [BorrowedAttribute<'a>], constant_pool: &BorrowedConstantPool, name: &str)
+    -> Option<&'b BorrowedAttribute<'a>> {
+    attributes.iter()
+        .find(|attribute| constant_pool.utf8_eq(attribute.attribute_name_index, name))
+}
+
+fn read_code<'a>(attribute: &BorrowedAttribute<'a>, constant_pool: &BorrowedConstantPool<'a>)
+    -> Result<BorrowedCode<'a>, ClassFormatError> {
+    // Cover the class file up to the end of the attribute so that offsets
+    // stay absolute
+    let end = attribute.offset + attribute.info.len();
+    let mut con = Cursor { bytes: &constant_pool.bytes[..end], offset: attribute.offset };
+    let max_stack = con.u2()?;
+    let max_locals = con.u2()?;
+    let code_length = con.u4()?;
+    let code = con.take(code_length as usize)?;
+    let exception_table_length = con.u2()?;
+    let mut exception_table: Vec<ExceptionHandler> = Vec::with_capacity(exception_table_length as usize);
+    for _ in 0..exception_table_length {
+        let start_pc = con.u2()?;
+        let end_pc = con.u2()?;
+        let handler_pc = con.u2()?;
+        let catch_type = con.optional_index(constant_pool, CONSTANT_CLASS, "Class")?;
+        exception_table.push(ExceptionHandler { start_pc, end_pc, handler_pc, catch_type });
+    }
+    let attributes = read_attributes(&mut con, constant_pool)?;
+    if con.offset != end {
+        return Err(ClassFormatError::BadAttributeLength {
+            offset: attribute.offset - 6,
+            name: String::from("Code"),
+            attribute_length: attribute.info.len() as u32,
+            actual_length: con.offset - attribute.offset,
+        });
+    }
+    Ok(BorrowedCode {
+        max_stack,
+        max_locals,
+        code,
+        exception_table,
+        attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+        use crate::read_class;
+
+    const HELLO_WORLD: &[u8] = include_bytes!("../HelloWorld.class");
+    // Where the constant pool of HELLO_WORLD ends and this_class starts
+    const THIS_CLASS_OFFSET: usize = 312;
+
+    fn attribute_names(attributes: &[BorrowedAttribute], constant_pool: &BorrowedConstantPool) -> Vec<String> {
+        attributes.iter().map(|attribute| attribute.name(constant_pool).unwrap().into_owned()).collect()
+    }
+
+    #[test]
+    fn matches_read_class() {
+        let borrowed = parse(HELLO_WORLD).unwrap();
+        let owned = read_class::parse_bytes(HELLO_WORLD).unwrap();
+        let constant_pool = &borrowed.constant_pool;
+        assert_eq!(constant_pool.len(), owned.constant_pool.len());
+        assert_eq!((borrowed.major_version, borrowed.minor_version), (owned.major_version, owned.minor_version));
+        assert_eq!(borrowed.access_flags, owned.access_flags);
+        assert_eq!(borrowed.this_class_name().unwrap(), owned.this_class_name);
+        assert_eq!(borrowed.super_class_name().unwrap().unwrap(), owned.super_class_name);
+        assert_eq!(borrowed.interfaces.len(), owned.interfaces.len());
+        assert_eq!(borrowed.fields.len(), owned.fields.len());
+        assert_eq!(attribute_names(&borrowed.attributes, constant_pool), vec!["SourceFile"]);
+        let source_file = borrowed.get_attribute("SourceFile").unwrap().info;
+        let sourcefile_index = u16::from_be_bytes([source_file[0], source_file[1]]);
+        assert_eq!(constant_pool.utf8(sourcefile_index).unwrap(), owned.get_source_file().unwrap());
+
+        assert_eq!(borrowed.methods.len(), owned.methods.len());
+        for (method, owned_method) in borrowed.methods.iter().zip(&owned.methods) {
+            assert_eq!(method.access_flags, owned_method.access_flags);
+            assert_eq!(method.name(constant_pool).unwrap(), owned_method.name);
+            assert_eq!(method.descriptor(constant_pool).unwrap(), owned_method.descriptor);
+            let code = method.code(constant_pool).unwrap().unwrap();
+            let owned_code = owned_method.get_code_attribute();
+            assert_eq!(code.code, &owned_code.code[..]);
+            assert_eq!((code.max_stack, code.max_locals), (owned_code.max_stack, owned_code.max_locals));
+            assert_eq!(code.exception_table.len(), owned_code.exception_table.len());
+            assert_eq!(attribute_names(&code.attributes, constant_pool), vec!["LineNumberTable"]);
+            // The first entry of the LineNumberTable is for pc 0
+            let line_numbers = code.attributes[0].info;
+            let line_number = u16::from_be_bytes([line_numbers[4], line_numbers[5]]);
+            assert_eq!(Some(line_number), owned_method.get_line_number(0));
+        }
+        assert!(borrowed.get_method("main", "([Ljava/lang/String;)V").is_some());
+    }
+
+    #[test]
+    fn reports_truncation() {
+        for length in 0..HELLO_WORLD.len() {
+            match parse(&HELLO_WORLD[..length]) {
+                Err(ClassFormatError::Truncated { offset }) => assert!(offset <= length),
+                Err(e) => panic!("{} for {} bytes", e, length),
+                Ok(_) => panic!("Parsed {} bytes", length),
+            }
+        }
+    }
+
+    #[test]
+    fn reports_wrong_kind_of_constant() {
+        let mut bytes = HELLO_WORLD.to_vec();
+        // Constant 1 is a Methodref
+        bytes[THIS_CLASS_OFFSET + 1] = 1;
+        assert!(matches!(parse(&bytes), Err(ClassFormatError::WrongConstantKind {
+            offset: THIS_CLASS_OFFSET, index: 1, expected: "Class" })));
+    }
+
+    // Constant 5, at 28, is the Class naming HelloWorld
+    #[test]
+    fn checks_class_names() {
+        let mut bytes = HELLO_WORLD.to_vec();
+        bytes[30] = 1;
+        assert!(matches!(parse(&bytes), Err(ClassFormatError::WrongConstantKind {
+            offset: 29, index: 1, expected: "Utf8" })));
+        bytes[30] = 0;
+        assert!(matches!(parse(&bytes), Err(ClassFormatError::BadConstantIndex { offset: 29, index: 0 })));
+    }
+
+    #[test]
+    fn reads_missing_super_class() {
+        let mut bytes = HELLO_WORLD.to_vec();
+        bytes[THIS_CLASS_OFFSET + 3] = 0;
+        let class = parse(&bytes).unwrap();
+        assert_eq!(class.super_class, 0);
+        assert_eq!(class.super_class_name().unwrap(), None);
+    }
+
+    #[test]
+    fn reports_lookups_outside_the_class() {
+        let class = parse(HELLO_WORLD).unwrap();
+        let constant_pool = &class.constant_pool;
+        assert!(matches!(constant_pool.utf8(0), Err(ClassFormatError::BadConstantIndex { offset: 8, index: 0 })));
+        assert!(matches!(constant_pool.class_name(1000),
+            Err(ClassFormatError::BadConstantIndex { offset: 8, index: 1000 })));
+        assert!(matches!(constant_pool.class_name(14),
+            Err(ClassFormatError::WrongConstantKind { offset: 8, index: 14, expected: "Class" })));
+        assert!(constant_pool.utf8_eq(14, "HelloWorld.java"));
+    }
+}
@@ -1,13 +1,29 @@
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::Utf8Index;
+use crate::constant_pool::ClassIndex;
+use crate::constant_pool::NameAndTypeIndex;
+use crate::constant_pool::MemberRefIndex;
+use crate::constant_pool::ModuleIndex;
+use crate::constant_pool::PackageIndex;
+use crate::signature::ClassSignature;
+use crate::signature::MethodSignature;
+use crate::signature::ReferenceTypeSignature;
+use crate::signature::SignatureError;
+use crate::signature::parse_field_signature;
+
 pub struct BytecodeClass {
     pub magic: u32,
     pub minor_version: u16,
     pub major_version: u16,
-    pub constant_pool: Vec<ConstantPoolEnum>,
+    pub constant_pool: ConstantPool,
     pub access_flags: u16,
-    pub this_class: u16,
+    pub this_class: ClassIndex,
     pub this_class_name: String,
-    pub super_class: u16,
+    pub super_class: ClassIndex,
     pub super_class_name: String,
+    pub interfaces: Vec<ClassIndex>,
+    pub interface_names: Vec<String>,
+    pub fields: Vec<FieldInfo>,
     pub methods: Vec<MethodInfo>,
     pub attributes: Vec<AttributeEnum>,
 }
@@ -23,6 +39,43 @@ impl BytecodeClass {
         }
         &self.methods[ii]
     }
+
+    pub fn get_source_file(&self) -> Option<&str> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeEnum::SourceFileItem(source_file) =>
+                self.constant_pool.utf8(source_file.sourcefile_index).ok(),
+            _ => None,
+        })
+    }
+
+    /// The visible and invisible annotations on the class.
+    pub fn get_annotations(&self) -> impl Iterator<Item = &Annotation> {
+        annotations(&self.attributes)
+    }
+
+    /// The generic signature, for a class that is generic or extends or
+    /// implements a parameterized type.
+    pub fn get_signature(&self) -> Option<Result<ClassSignature, SignatureError>> {
+        signature(&self.attributes).map(ClassSignature::parse)
+    }
+
+    /// Whether this is a `module-info.class` rather than a class or
+    /// interface.
+    pub fn is_module(&self) -> bool {
+        self.access_flags & 0x8000 != 0
+    }
+
+    pub fn get_module(&self) -> Option<&ModuleClass> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeEnum::ModuleItem(module) => Some(module),
+            _ => None,
+        })
+    }
+
+    pub fn get_method(&self, name: &str, descriptor: &str) -> Option<&MethodInfo> {
+        self.methods.iter()
+            .find(|method| method.name == name && method.descriptor == descriptor)
+    }
 }
 
 #[derive(Clone)]
@@ -33,84 +86,277 @@ pub enum ConstantPoolEnum {
     ConstantFieldrefItem(ConstantFieldrefClass),
     ConstantMethodrefItem(ConstantMethodrefClass),
     ConstantNameAndTypeItem(ConstantNameAndTypeClass),
+    ConstantIntegerItem(ConstantIntegerClass),
+    ConstantFloatItem(ConstantFloatClass),
+    ConstantLongItem(ConstantLongClass),
+    ConstantDoubleItem(ConstantDoubleClass),
+    ConstantInterfaceMethodrefItem(ConstantInterfaceMethodrefClass),
+    ConstantMethodHandleItem(ConstantMethodHandleClass),
+    ConstantMethodTypeItem(ConstantMethodTypeClass),
+    ConstantDynamicItem(ConstantDynamicClass),
+    ConstantInvokeDynamicItem(ConstantInvokeDynamicClass),
+    ConstantModuleItem(ConstantModuleClass),
+    ConstantPackageItem(ConstantPackageClass),
+    // Long and Double constants take up two entries; this fills the second
+    ConstantUnusableItem,
 }
 
 #[derive(Clone)]
 pub struct ConstantUtf8Class {
     pub length: u16,
     pub bytes: String, 
+    /// The modified UTF-8 from the class file, kept only when encoding
+    /// `bytes` would not reproduce it, e.g. because it held unpaired
+    /// surrogates
+    pub original: Option<Vec<u8>>,
 }
 
 #[derive(Copy, Clone)]
 pub struct ConstantClassClass {
-    pub name_index: u16,
+    pub name_index: Utf8Index,
 }
 
 #[derive(Copy, Clone)]
 pub struct ConstantStringClass {
-    pub string_index: u16,
+    pub string_index: Utf8Index,
 }
 
 #[derive(Copy, Clone)]
 pub struct ConstantFieldrefClass { 
-    pub class_index: u16,
-    pub name_and_type_index: u16,
+    pub class_index: ClassIndex,
+    pub name_and_type_index: NameAndTypeIndex,
 }
 
 #[derive(Copy, Clone)]
 pub struct ConstantMethodrefClass {
-    pub class_index: u16,
-    pub name_and_type_index: u16,
+    pub class_index: ClassIndex,
+    pub name_and_type_index: NameAndTypeIndex,
 }
 
 #[derive(Copy, Clone)]
 pub struct ConstantNameAndTypeClass {
-    pub name_index: u16,
-    pub descriptor_index: u16,
+    pub name_index: Utf8Index,
+    pub descriptor_index: Utf8Index,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantIntegerClass {
+    pub value: i32,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantFloatClass {
+    pub value: f32,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantLongClass {
+    pub value: i64,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantDoubleClass {
+    pub value: f64,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantInterfaceMethodrefClass {
+    pub class_index: ClassIndex,
+    pub name_and_type_index: NameAndTypeIndex,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantMethodHandleClass {
+    /// One of the `REF_` kinds, 1 (`REF_getField`) to 9 (`REF_invokeInterface`)
+    pub reference_kind: u8,
+    pub reference_index: MemberRefIndex,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantMethodTypeClass {
+    pub descriptor_index: Utf8Index,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantDynamicClass {
+    pub bootstrap_method_attr_index: u16,
+    pub name_and_type_index: NameAndTypeIndex,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantInvokeDynamicClass {
+    pub bootstrap_method_attr_index: u16,
+    pub name_and_type_index: NameAndTypeIndex,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantModuleClass {
+    pub name_index: Utf8Index,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConstantPackageClass {
+    pub name_index: Utf8Index,
+}
+
+pub struct FieldInfo {
+    pub access_flags: u16,
+    pub name_index: Utf8Index,
+    pub name: String,
+    pub descriptor_index: Utf8Index,
+    pub descriptor: String,
+    pub attributes_count: u16,
+    pub attributes: Vec<AttributeEnum>,
+}
+
+impl FieldInfo {
+    pub fn get_annotations(&self) -> impl Iterator<Item = &Annotation> {
+        annotations(&self.attributes)
+    }
+
+    /// The generic signature, for a field whose type uses type variables or
+    /// parameterized types.
+    pub fn get_signature(&self) -> Option<Result<ReferenceTypeSignature, SignatureError>> {
+        signature(&self.attributes).map(parse_field_signature)
+    }
+
+    /// The constant pool index of the initial value of a constant field.
+    pub fn get_constant_value(&self) -> Option<u16> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeEnum::ConstantValueItem(constant_value) => Some(constant_value.constantvalue_index),
+            _ => None,
+        })
+    }
 }
 
 pub struct MethodInfo {
     pub access_flags: u16,
-    pub name_index: u16,
+    pub name_index: Utf8Index,
     pub name: String,
-    pub descriptor_index: u16,
+    pub descriptor_index: Utf8Index,
     pub descriptor: String,
     pub attributes_count: u16,
     pub attributes: Vec<AttributeEnum>,
 }
 
 impl MethodInfo {
+    pub fn get_annotations(&self) -> impl Iterator<Item = &Annotation> {
+        annotations(&self.attributes)
+    }
+
+    /// The generic signature, for a method that is generic or whose
+    /// parameter, return or exception types use type variables or
+    /// parameterized types.
+    pub fn get_signature(&self) -> Option<Result<MethodSignature, SignatureError>> {
+        signature(&self.attributes).map(MethodSignature::parse)
+    }
+
     pub fn get_code(&self) -> &Vec<u8> {
+        &self.get_code_attribute().code
+    }
+
+    /// The source line of the instruction at `pc`, if the method has a
+    /// `LineNumberTable`.
+    pub fn get_line_number(&self, pc: usize) -> Option<u16> {
+        self.get_code_attribute().attributes.iter()
+            .filter_map(|attribute| match attribute {
+                AttributeEnum::LineNumberTableItem(table) => Some(&table.line_number_table),
+                _ => None,
+            })
+            .flatten()
+            .filter(|entry| entry.start_pc as usize <= pc)
+            .max_by_key(|entry| entry.start_pc)
+            .map(|entry| entry.line_number)
+    }
+
+    pub fn get_code_attribute(&self) -> &CodeClass {
         let mut ii = 0;
         for i in 0..self.attributes.len() {
-            match &self.attributes[i] {
-                AttributeEnum::CodeItem(_code_class) => {
-                    ii = i;
-                    break;
-                }
-                _ => {}, 
-            };
+            if let AttributeEnum::CodeItem(_code_class) = &self.attributes[i] {
+                ii = i;
+                break;
+            }
         }
         match &self.attributes[ii] {
-            AttributeEnum::CodeItem(code_class) => {
-                &code_class.code
-            },
+            AttributeEnum::CodeItem(code_class) => code_class,
             _ => panic!("Something unexpected happened"), 
         }
     }
 }
 
+fn signature(attributes: &[AttributeEnum]) -> Option<&str> {
+    attributes.iter().find_map(|attribute| match attribute {
+        AttributeEnum::SignatureItem(x) => Some(x.signature.as_ref()),
+        _ => None,
+    })
+}
+
+fn annotations(attributes: &[AttributeEnum]) -> impl Iterator<Item = &Annotation> {
+    attributes.iter()
+        .filter_map(|attribute| match attribute {
+            AttributeEnum::RuntimeVisibleAnnotationsItem(x)
+            | AttributeEnum::RuntimeInvisibleAnnotationsItem(x) => Some(&x.annotations),
+            _ => None,
+        })
+        .flatten()
+}
+
 pub enum AttributeEnum {
     CodeItem(CodeClass),
     LineNumberTableItem(LineNumberTableClass),
     StackMapTableItem(StackMapTableClass),
     SourceFileItem(SourceFileClass),
+    ConstantValueItem(ConstantValueClass),
+    ExceptionsItem(ExceptionsClass),
+    InnerClassesItem(InnerClassesClass),
+    EnclosingMethodItem(EnclosingMethodClass),
+    SignatureItem(SignatureClass),
+    LocalVariableTableItem(LocalVariableTableClass),
+    LocalVariableTypeTableItem(LocalVariableTypeTableClass),
+    BootstrapMethodsItem(BootstrapMethodsClass),
+    MethodParametersItem(MethodParametersClass),
+    NestHostItem(NestHostClass),
+    NestMembersItem(NestMembersClass),
+    RecordItem(RecordClass),
+    PermittedSubclassesItem(PermittedSubclassesClass),
+    DeprecatedItem(DeprecatedClass),
+    SyntheticItem(SyntheticClass),
+    SourceDebugExtensionItem(SourceDebugExtensionClass),
+    RuntimeVisibleAnnotationsItem(AnnotationsClass),
+    RuntimeInvisibleAnnotationsItem(AnnotationsClass),
+    RuntimeVisibleParameterAnnotationsItem(ParameterAnnotationsClass),
+    RuntimeInvisibleParameterAnnotationsItem(ParameterAnnotationsClass),
+    RuntimeVisibleTypeAnnotationsItem(TypeAnnotationsClass),
+    RuntimeInvisibleTypeAnnotationsItem(TypeAnnotationsClass),
+    AnnotationDefaultItem(AnnotationDefaultClass),
+    ModuleItem(ModuleClass),
+    ModulePackagesItem(ModulePackagesClass),
+    ModuleMainClassItem(ModuleMainClassClass),
+    /// An attribute this parser does not know, such as a vendor-specific
+    /// one, kept as its raw bytes
+    UnknownItem(UnknownClass),
+}
+
+pub struct ExceptionTableEntry {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    pub catch_type: ClassIndex,
+    /// The class of exceptions handled, or `None` for a handler that
+    /// catches everything (`finally`)
+    pub catch_type_name: Option<String>,
 }
 
-pub struct ExceptionTable {}
+impl ExceptionTableEntry {
+    /// Whether the handler covers the instruction at `pc`. `end_pc` is
+    /// exclusive.
+    pub fn covers(&self, pc: usize) -> bool {
+        self.start_pc as usize <= pc && pc < self.end_pc as usize
+    }
+}
 
 pub struct CodeClass {
-    pub attribute_name_index: u16,
+    pub attribute_name_index: Utf8Index,
     pub attribute_name: String,
     pub attribute_length: u32,
     pub max_stack: u16,
@@ -118,7 +364,7 @@ pub struct CodeClass {
     pub code_length: u32,
     pub code: Vec<u8>,
     pub exception_table_length: u16,
-    pub exception_table: ExceptionTable,
+    pub exception_table: Vec<ExceptionTableEntry>,
     pub attributes_count: u16,
     pub attributes: Vec<AttributeEnum>,
 }
@@ -129,7 +375,7 @@ pub struct LineNumberTableElem {
 }
 
 pub struct LineNumberTableClass {
-    pub attribute_name_index: u16,
+    pub attribute_name_index: Utf8Index,
     pub attribute_name: String,
     pub attribute_length: u32,
     pub line_number_table_length: u16,
@@ -137,13 +383,538 @@ pub struct LineNumberTableClass {
 }
 
 pub struct StackMapTableClass {
-    pub attribute_name_index: u16,
+    pub attribute_name_index: Utf8Index,
     pub attribute_name: String,
     pub attribute_length: u32,
+    pub number_of_entries: u16,
+    pub entries: Vec<StackMapFrame>,
+}
+
+/// A `stack_map_frame` (JVMS 4.7.4). Each frame gives the types of the
+/// locals and operand stack at one address, relative to the previous frame.
+///
+/// `frame_type` is kept in every variant because several values encode the
+/// same frame: it carries the offset delta of the short forms and the number
+/// of locals chopped or appended.
+pub enum StackMapFrame {
+    SameFrame { frame_type: u8 },
+    SameLocals1StackItemFrame { frame_type: u8, stack: VerificationTypeInfo },
+    SameLocals1StackItemFrameExtended { frame_type: u8, offset_delta: u16, stack: VerificationTypeInfo },
+    ChopFrame { frame_type: u8, offset_delta: u16 },
+    SameFrameExtended { frame_type: u8, offset_delta: u16 },
+    AppendFrame { frame_type: u8, offset_delta: u16, locals: Vec<VerificationTypeInfo> },
+    FullFrame {
+        frame_type: u8,
+        offset_delta: u16,
+        number_of_locals: u16,
+        locals: Vec<VerificationTypeInfo>,
+        number_of_stack_items: u16,
+        stack: Vec<VerificationTypeInfo>,
+    },
+}
+
+impl StackMapFrame {
+    pub fn frame_type(&self) -> u8 {
+        match self {
+            StackMapFrame::SameFrame { frame_type }
+            | StackMapFrame::SameLocals1StackItemFrame { frame_type, .. }
+            | StackMapFrame::SameLocals1StackItemFrameExtended { frame_type, .. }
+            | StackMapFrame::ChopFrame { frame_type, .. }
+            | StackMapFrame::SameFrameExtended { frame_type, .. }
+            | StackMapFrame::AppendFrame { frame_type, .. }
+            | StackMapFrame::FullFrame { frame_type, .. } => *frame_type,
+        }
+    }
+
+    /// The distance from the previous frame's address. The address of the
+    /// first frame is `offset_delta`, and of each later one the previous
+    /// address plus `offset_delta + 1`.
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            StackMapFrame::SameFrame { frame_type } => *frame_type as u16,
+            StackMapFrame::SameLocals1StackItemFrame { frame_type, .. } => (*frame_type - 64) as u16,
+            StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, .. }
+            | StackMapFrame::ChopFrame { offset_delta, .. }
+            | StackMapFrame::SameFrameExtended { offset_delta, .. }
+            | StackMapFrame::AppendFrame { offset_delta, .. }
+            | StackMapFrame::FullFrame { offset_delta, .. } => *offset_delta,
+        }
+    }
+}
+
+/// The type of one local variable or operand stack entry in a stack map
+/// frame. `Long` and `Double` each stand for two slots.
+pub enum VerificationTypeInfo {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    Object { cpool_index: ClassIndex, class_name: String },
+    /// An object created by the `new` instruction at `offset` whose
+    /// constructor has not run yet
+    Uninitialized { offset: u16 },
+}
+
+impl VerificationTypeInfo {
+    pub fn tag(&self) -> u8 {
+        match self {
+            VerificationTypeInfo::Top => 0,
+            VerificationTypeInfo::Integer => 1,
+            VerificationTypeInfo::Float => 2,
+            VerificationTypeInfo::Double => 3,
+            VerificationTypeInfo::Long => 4,
+            VerificationTypeInfo::Null => 5,
+            VerificationTypeInfo::UninitializedThis => 6,
+            VerificationTypeInfo::Object { .. } => 7,
+            VerificationTypeInfo::Uninitialized { .. } => 8,
+        }
+    }
 }
 
 pub struct SourceFileClass {
-    pub attribute_name_index: u16,
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub sourcefile_index: Utf8Index,
+}
+
+pub struct ConstantValueClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub constantvalue_index: u16,
+}
+
+pub struct ExceptionsClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub number_of_exceptions: u16,
+    pub exception_index_table: Vec<ClassIndex>,
+    pub exception_names: Vec<String>,
+}
+
+pub struct InnerClassesElem {
+    pub inner_class_info_index: ClassIndex,
+    pub inner_class_name: String,
+    /// 0 for local and anonymous classes
+    pub outer_class_info_index: ClassIndex,
+    pub outer_class_name: Option<String>,
+    /// 0 for anonymous classes
+    pub inner_name_index: Utf8Index,
+    pub inner_name: Option<String>,
+    pub inner_class_access_flags: u16,
+}
+
+pub struct InnerClassesClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub number_of_classes: u16,
+    pub classes: Vec<InnerClassesElem>,
+}
+
+pub struct EnclosingMethodClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub class_index: ClassIndex,
+    pub class_name: String,
+    /// 0 when the class is not enclosed by a method, e.g. in an initializer
+    pub method_index: NameAndTypeIndex,
+    pub method_name: Option<String>,
+    pub method_descriptor: Option<String>,
+}
+
+pub struct SignatureClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub signature_index: Utf8Index,
+    pub signature: String,
+}
+
+pub struct LocalVariableTableElem {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: Utf8Index,
+    pub name: String,
+    pub descriptor_index: Utf8Index,
+    pub descriptor: String,
+    pub index: u16,
+}
+
+pub struct LocalVariableTableClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub local_variable_table_length: u16,
+    pub local_variable_table: Vec<LocalVariableTableElem>,
+}
+
+pub struct LocalVariableTypeTableElem {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: Utf8Index,
+    pub name: String,
+    pub signature_index: Utf8Index,
+    pub signature: String,
+    pub index: u16,
+}
+
+pub struct LocalVariableTypeTableClass {
+    pub attribute_name_index: Utf8Index,
     pub attribute_name: String,
-    pub sourcefile_index: u16,
+    pub attribute_length: u32,
+    pub local_variable_type_table_length: u16,
+    pub local_variable_type_table: Vec<LocalVariableTypeTableElem>,
+}
+
+pub struct BootstrapMethodElem {
+    pub bootstrap_method_ref: u16,
+    pub num_bootstrap_arguments: u16,
+    pub bootstrap_arguments: Vec<u16>,
+}
+
+pub struct BootstrapMethodsClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub num_bootstrap_methods: u16,
+    pub bootstrap_methods: Vec<BootstrapMethodElem>,
+}
+
+pub struct MethodParameterElem {
+    /// 0 for a parameter without a name
+    pub name_index: Utf8Index,
+    pub name: Option<String>,
+    pub access_flags: u16,
+}
+
+pub struct MethodParametersClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub parameters_count: u8,
+    pub parameters: Vec<MethodParameterElem>,
+}
+
+pub struct NestHostClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub host_class_index: ClassIndex,
+    pub host_class_name: String,
+}
+
+pub struct NestMembersClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub number_of_classes: u16,
+    pub classes: Vec<ClassIndex>,
+    pub class_names: Vec<String>,
+}
+
+pub struct RecordComponentInfo {
+    pub name_index: Utf8Index,
+    pub name: String,
+    pub descriptor_index: Utf8Index,
+    pub descriptor: String,
+    pub attributes_count: u16,
+    pub attributes: Vec<AttributeEnum>,
+}
+
+pub struct RecordClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub components_count: u16,
+    pub components: Vec<RecordComponentInfo>,
+}
+
+pub struct PermittedSubclassesClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub number_of_classes: u16,
+    pub classes: Vec<ClassIndex>,
+    pub class_names: Vec<String>,
+}
+
+pub struct DeprecatedClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+}
+
+pub struct SyntheticClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+}
+
+pub struct SourceDebugExtensionClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    /// Usually modified UTF-8, but not required to be
+    pub debug_extension: Vec<u8>,
+}
+
+pub struct UnknownClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// The attribute of both `RuntimeVisibleAnnotations` and
+/// `RuntimeInvisibleAnnotations`, which differ only in retention.
+pub struct AnnotationsClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub num_annotations: u16,
+    pub annotations: Vec<Annotation>,
+}
+
+pub struct ParameterAnnotations {
+    pub num_annotations: u16,
+    pub annotations: Vec<Annotation>,
+}
+
+/// The attribute of both `RuntimeVisibleParameterAnnotations` and
+/// `RuntimeInvisibleParameterAnnotations`.
+pub struct ParameterAnnotationsClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub num_parameters: u8,
+    pub parameter_annotations: Vec<ParameterAnnotations>,
+}
+
+/// The attribute of both `RuntimeVisibleTypeAnnotations` and
+/// `RuntimeInvisibleTypeAnnotations`.
+pub struct TypeAnnotationsClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub num_annotations: u16,
+    pub annotations: Vec<TypeAnnotation>,
+}
+
+pub struct AnnotationDefaultClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub default_value: ElementValue,
+}
+
+pub struct Annotation {
+    pub type_index: Utf8Index,
+    /// The field descriptor of the annotation interface, e.g.
+    /// `Ljava/lang/Deprecated;`
+    pub type_name: String,
+    pub num_element_value_pairs: u16,
+    pub element_value_pairs: Vec<ElementValuePair>,
+}
+
+pub struct ElementValuePair {
+    pub element_name_index: Utf8Index,
+    pub element_name: String,
+    pub value: ElementValue,
+}
+
+/// The value of an annotation element (JVMS 4.7.16.1).
+pub enum ElementValue {
+    /// A primitive or `String` constant. `tag` is one of `BCDFIJSZs`
+    Const { tag: u8, const_value_index: u16 },
+    Enum { type_name_index: Utf8Index, type_name: String, const_name_index: Utf8Index, const_name: String },
+    /// A class literal, given as a return descriptor such as `V` or
+    /// `Ljava/lang/Object;`
+    Class { class_info_index: Utf8Index, class_info: String },
+    Annotation(Annotation),
+    Array { num_values: u16, values: Vec<ElementValue> },
+}
+
+impl ElementValue {
+    pub fn tag(&self) -> u8 {
+        match self {
+            ElementValue::Const { tag, .. } => *tag,
+            ElementValue::Enum { .. } => b'e',
+            ElementValue::Class { .. } => b'c',
+            ElementValue::Annotation(_) => b'@',
+            ElementValue::Array { .. } => b'[',
+        }
+    }
+}
+
+/// An annotation on a use of a type (JVMS 4.7.20).
+pub struct TypeAnnotation {
+    /// The kind of target, which decides the `target_info` variant
+    pub target_type: u8,
+    pub target_info: TargetInfo,
+    pub target_path: TypePath,
+    pub annotation: Annotation,
+}
+
+pub enum TargetInfo {
+    TypeParameter { type_parameter_index: u8 },
+    Supertype { supertype_index: u16 },
+    TypeParameterBound { type_parameter_index: u8, bound_index: u8 },
+    /// The type of a field, the return type or the receiver type
+    Empty,
+    FormalParameter { formal_parameter_index: u8 },
+    Throws { throws_type_index: u16 },
+    Localvar { table_length: u16, table: Vec<LocalvarTargetElem> },
+    Catch { exception_table_index: u16 },
+    Offset { offset: u16 },
+    TypeArgument { offset: u16, type_argument_index: u8 },
+}
+
+pub struct LocalvarTargetElem {
+    pub start_pc: u16,
+    pub length: u16,
+    pub index: u16,
+}
+
+/// The location of the annotated type within the type of the target, e.g.
+/// the `String` in `List<String>`.
+pub struct TypePath {
+    pub path_length: u8,
+    pub path: Vec<TypePathElem>,
+}
+
+pub struct TypePathElem {
+    pub type_path_kind: u8,
+    pub type_argument_index: u8,
+}
+
+/// The `Module` attribute of a module descriptor (JVMS 4.7.25).
+pub struct ModuleClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub module_name_index: ModuleIndex,
+    pub module_name: String,
+    pub module_flags: u16,
+    pub module_version_index: Utf8Index,
+    pub module_version: Option<String>,
+    pub requires_count: u16,
+    pub requires: Vec<ModuleRequires>,
+    pub exports_count: u16,
+    pub exports: Vec<ModuleExports>,
+    pub opens_count: u16,
+    pub opens: Vec<ModuleOpens>,
+    pub uses_count: u16,
+    pub uses_index: Vec<ClassIndex>,
+    /// The service interfaces the module uses
+    pub uses_names: Vec<String>,
+    pub provides_count: u16,
+    pub provides: Vec<ModuleProvides>,
+}
+
+pub struct ModuleRequires {
+    pub requires_index: ModuleIndex,
+    pub requires_name: String,
+    pub requires_flags: u16,
+    pub requires_version_index: Utf8Index,
+    pub requires_version: Option<String>,
+}
+
+pub struct ModuleExports {
+    pub exports_index: PackageIndex,
+    pub exports_name: String,
+    pub exports_flags: u16,
+    /// 0 for an unqualified export to every module
+    pub exports_to_count: u16,
+    pub exports_to_index: Vec<ModuleIndex>,
+    pub exports_to_names: Vec<String>,
+}
+
+pub struct ModuleOpens {
+    pub opens_index: PackageIndex,
+    pub opens_name: String,
+    pub opens_flags: u16,
+    /// 0 for an unqualified open to every module
+    pub opens_to_count: u16,
+    pub opens_to_index: Vec<ModuleIndex>,
+    pub opens_to_names: Vec<String>,
+}
+
+pub struct ModuleProvides {
+    pub provides_index: ClassIndex,
+    /// The service interface
+    pub provides_name: String,
+    pub provides_with_count: u16,
+    pub provides_with_index: Vec<ClassIndex>,
+    /// The implementations of the service
+    pub provides_with_names: Vec<String>,
+}
+
+pub struct ModulePackagesClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub package_count: u16,
+    pub package_index: Vec<PackageIndex>,
+    pub package_names: Vec<String>,
+}
+
+pub struct ModuleMainClassClass {
+    pub attribute_name_index: Utf8Index,
+    pub attribute_name: String,
+    pub attribute_length: u32,
+    pub main_class_index: ClassIndex,
+    pub main_class_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::read_class::parse_bytes;
+
+    const HELLO_WORLD: &[u8] = include_bytes!("../HelloWorld.class");
+    const FIXTURE: &[u8] = include_bytes!("../fixtures/Fixture.class");
+
+    #[test]
+    fn finds_methods() {
+        let class = parse_bytes(HELLO_WORLD).unwrap();
+        assert_eq!(class.get_main_method().descriptor, "([Ljava/lang/String;)V");
+        assert_eq!(class.get_method("<init>", "()V").unwrap().get_code(), &[0x2a, 0xb7, 0, 1, 0xb1]);
+        assert!(class.get_method("main", "()V").is_none());
+        assert_eq!(class.get_source_file(), Some("HelloWorld.java"));
+    }
+
+    // javac marks the finally block copied after each path with the line of
+    // the finally, and the return after it with the line of the return
+    #[test]
+    fn finds_line_numbers() {
+        let class = parse_bytes(FIXTURE).unwrap();
+        let parse = class.get_method("parse", "(Ljava/lang/String;)I").unwrap();
+        let lines: Vec<Option<u16>> = [0, 4, 5, 12, 14, 16, 35].iter().map(|pc| parse.get_line_number(*pc)).collect();
+        assert_eq!(lines, [Some(108), Some(108), Some(112), Some(108), Some(109), Some(110), Some(113)]);
+
+        let class = parse_bytes(HELLO_WORLD).unwrap();
+        let main = class.get_main_method();
+        assert_eq!(main.get_line_number(7), Some(5));
+        assert_eq!(main.get_line_number(8), Some(6));
+    }
+
+    #[test]
+    fn parses_signatures() {
+        let class = parse_bytes(FIXTURE).unwrap();
+        let signature = class.get_signature().unwrap().unwrap();
+        assert_eq!(signature.to_java("Fixture"), "Fixture<T extends java.lang.Comparable<T>> extends java.lang.Object");
+        assert_eq!(class.fields[0].get_signature().unwrap().unwrap().to_string(), "java.util.List<T>");
+        assert!(class.fields[1].get_signature().is_none());
+        let max = class.get_method("max", "(Ljava/util/Comparator;)Ljava/lang/Comparable;").unwrap();
+        assert_eq!(max.get_signature().unwrap().unwrap().to_java("max"), "T max(java.util.Comparator<? super T>)");
+        assert!(class.get_method("dense", "(I)I").unwrap().get_signature().is_none());
+
+        let class = parse_bytes(HELLO_WORLD).unwrap();
+        assert!(class.get_signature().is_none());
+    }
 }
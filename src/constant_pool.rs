@@ -0,0 +1,287 @@
+//! The constant pool of a class, and typed indexes into it.
+//!
+//! Indexes are 1-based as in the class file, and each newtype names the
+//! kind of entry it must refer to, so that `ConstantClassClass::name_index`
+//! cannot be looked up as anything but a `CONSTANT_Utf8`.
+
+use std::error;
+use std::fmt;
+use std::slice;
+
+use crate::class::ConstantPoolEnum;
+
+/// An index of a `CONSTANT_Utf8` entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Utf8Index(pub u16);
+
+/// An index of a `CONSTANT_Class` entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ClassIndex(pub u16);
+
+/// An index of a `CONSTANT_NameAndType` entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NameAndTypeIndex(pub u16);
+
+/// An index of a `CONSTANT_Fieldref`, `CONSTANT_Methodref` or
+/// `CONSTANT_InterfaceMethodref` entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemberRefIndex(pub u16);
+
+/// An index of a `CONSTANT_Module` entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ModuleIndex(pub u16);
+
+/// An index of a `CONSTANT_Package` entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PackageIndex(pub u16);
+
+// Each index converts back to the `u2` written in the class file
+impl From<Utf8Index> for u16 {
+    fn from(index: Utf8Index) -> u16 {
+        index.0
+    }
+}
+
+impl From<ClassIndex> for u16 {
+    fn from(index: ClassIndex) -> u16 {
+        index.0
+    }
+}
+
+impl From<NameAndTypeIndex> for u16 {
+    fn from(index: NameAndTypeIndex) -> u16 {
+        index.0
+    }
+}
+
+impl From<MemberRefIndex> for u16 {
+    fn from(index: MemberRefIndex) -> u16 {
+        index.0
+    }
+}
+
+impl From<ModuleIndex> for u16 {
+    fn from(index: ModuleIndex) -> u16 {
+        index.0
+    }
+}
+
+impl From<PackageIndex> for u16 {
+    fn from(index: PackageIndex) -> u16 {
+        index.0
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConstantPoolError {
+    BadIndex { index: u16 },
+    WrongKind { index: u16, expected: &'static str },
+    /// No more entries fit: `constant_pool_count` is a `u2`, so the last
+    /// usable index is 65534
+    Full,
+}
+
+impl fmt::Display for ConstantPoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConstantPoolError::BadIndex { index } =>
+                write!(f, "Constant pool index {} out of range", index),
+            ConstantPoolError::WrongKind { index, expected } =>
+                write!(f, "Constant pool entry {} is not a {}", index, expected),
+            ConstantPoolError::Full => write!(f, "Constant pool has more than 65534 entries"),
+        }
+    }
+}
+
+impl error::Error for ConstantPoolError {}
+
+// One less than the largest `constant_pool_count`
+const MAX_ENTRIES: usize = 65534;
+
+#[derive(Clone, Default)]
+pub struct ConstantPool {
+    entries: Vec<ConstantPoolEnum>,
+}
+
+impl ConstantPool {
+    pub fn new() -> ConstantPool {
+        ConstantPool::default()
+    }
+
+    /// The number of entries, counting the unusable entry after each
+    /// `CONSTANT_Long` and `CONSTANT_Double`. This is one less than the
+    /// `constant_pool_count` of the class file.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, ConstantPoolEnum> {
+        self.entries.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, ConstantPoolEnum> {
+        self.entries.iter_mut()
+    }
+
+    /// Appends `constant`, followed by an unusable entry if it is a long or
+    /// double, and returns its index.
+    pub fn push(&mut self, constant: ConstantPoolEnum) -> Result<u16, ConstantPoolError> {
+        let takes_two_entries = matches!(constant,
+            ConstantPoolEnum::ConstantLongItem(_) | ConstantPoolEnum::ConstantDoubleItem(_));
+        let length = self.entries.len() + if takes_two_entries { 2 } else { 1 };
+        if length > MAX_ENTRIES {
+            return Err(ConstantPoolError::Full);
+        }
+        self.entries.push(constant);
+        let index = self.entries.len() as u16;
+        if takes_two_entries {
+            self.entries.push(ConstantPoolEnum::ConstantUnusableItem);
+        }
+        Ok(index)
+    }
+
+    /// The entry at `index`, of whatever kind.
+    pub fn get(&self, index: u16) -> Result<&ConstantPoolEnum, ConstantPoolError> {
+        (index as usize).checked_sub(1)
+            .and_then(|i| self.entries.get(i))
+            .ok_or(ConstantPoolError::BadIndex { index })
+    }
+
+    pub fn utf8(&self, index: Utf8Index) -> Result<&str, ConstantPoolError> {
+        match self.get(index.0)? {
+            ConstantPoolEnum::ConstantUtf8Item(x) => Ok(&x.bytes),
+            _ => Err(ConstantPoolError::WrongKind { index: index.0, expected: "Utf8" }),
+        }
+    }
+
+    pub fn class_name(&self, index: ClassIndex) -> Result<&str, ConstantPoolError> {
+        match self.get(index.0)? {
+            ConstantPoolEnum::ConstantClassItem(x) => self.utf8(x.name_index),
+            _ => Err(ConstantPoolError::WrongKind { index: index.0, expected: "Class" }),
+        }
+    }
+
+    /// The name and descriptor.
+    pub fn name_and_type(&self, index: NameAndTypeIndex) -> Result<(&str, &str), ConstantPoolError> {
+        match self.get(index.0)? {
+            ConstantPoolEnum::ConstantNameAndTypeItem(x) =>
+                Ok((self.utf8(x.name_index)?, self.utf8(x.descriptor_index)?)),
+            _ => Err(ConstantPoolError::WrongKind { index: index.0, expected: "NameAndType" }),
+        }
+    }
+
+    /// The class name, member name and descriptor of a field or method
+    /// reference.
+    pub fn member_ref(&self, index: MemberRefIndex) -> Result<(&str, &str, &str), ConstantPoolError> {
+        let (class_index, name_and_type_index) = match self.get(index.0)? {
+            ConstantPoolEnum::ConstantFieldrefItem(x) => (x.class_index, x.name_and_type_index),
+            ConstantPoolEnum::ConstantMethodrefItem(x) => (x.class_index, x.name_and_type_index),
+            ConstantPoolEnum::ConstantInterfaceMethodrefItem(x) => (x.class_index, x.name_and_type_index),
+            _ => return Err(ConstantPoolError::WrongKind { index: index.0, expected: "Fieldref or Methodref" }),
+        };
+        let (name, descriptor) = self.name_and_type(name_and_type_index)?;
+        Ok((self.class_name(class_index)?, name, descriptor))
+    }
+
+    pub fn module_name(&self, index: ModuleIndex) -> Result<&str, ConstantPoolError> {
+        match self.get(index.0)? {
+            ConstantPoolEnum::ConstantModuleItem(x) => self.utf8(x.name_index),
+            _ => Err(ConstantPoolError::WrongKind { index: index.0, expected: "Module" }),
+        }
+    }
+
+    pub fn package_name(&self, index: PackageIndex) -> Result<&str, ConstantPoolError> {
+        match self.get(index.0)? {
+            ConstantPoolEnum::ConstantPackageItem(x) => self.utf8(x.name_index),
+            _ => Err(ConstantPoolError::WrongKind { index: index.0, expected: "Package" }),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a ConstantPool {
+    type Item = &'a ConstantPoolEnum;
+    type IntoIter = slice::Iter<'a, ConstantPoolEnum>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::ConstantUtf8Class;
+    use crate::class::ConstantClassClass;
+    use crate::class::ConstantIntegerClass;
+    use crate::class::ConstantLongClass;
+
+    fn utf8_constant(s: &str) -> ConstantPoolEnum {
+        ConstantPoolEnum::ConstantUtf8Item(ConstantUtf8Class { length: s.len() as u16, bytes: s.to_string(), original: None })
+    }
+
+    // 1: Utf8 "Hello", 2: Class "Hello", 3-4: Long, 5: Integer
+    fn constant_pool() -> ConstantPool {
+        let mut constant_pool = ConstantPool::new();
+        assert_eq!(constant_pool.push(utf8_constant("Hello")), Ok(1));
+        assert_eq!(constant_pool.push(ConstantPoolEnum::ConstantClassItem(
+            ConstantClassClass { name_index: Utf8Index(1) })), Ok(2));
+        assert_eq!(constant_pool.push(ConstantPoolEnum::ConstantLongItem(ConstantLongClass { value: 7 })), Ok(3));
+        assert_eq!(constant_pool.push(ConstantPoolEnum::ConstantIntegerItem(ConstantIntegerClass { value: 7 })), Ok(5));
+        constant_pool
+    }
+
+    #[test]
+    fn resolves_indexes() {
+        let constant_pool = constant_pool();
+        assert_eq!(constant_pool.len(), 5);
+        assert_eq!(constant_pool.utf8(Utf8Index(1)), Ok("Hello"));
+        assert_eq!(constant_pool.class_name(ClassIndex(2)), Ok("Hello"));
+        assert!(matches!(constant_pool.get(3), Ok(ConstantPoolEnum::ConstantLongItem(_))));
+    }
+
+    #[test]
+    fn rejects_index_zero_and_out_of_range() {
+        let constant_pool = constant_pool();
+        assert_eq!(constant_pool.get(0).err(), Some(ConstantPoolError::BadIndex { index: 0 }));
+        assert_eq!(constant_pool.utf8(Utf8Index(6)), Err(ConstantPoolError::BadIndex { index: 6 }));
+        assert_eq!(constant_pool.class_name(ClassIndex(u16::MAX)),
+            Err(ConstantPoolError::BadIndex { index: u16::MAX }));
+    }
+
+    #[test]
+    fn rejects_wrong_kind() {
+        let constant_pool = constant_pool();
+        assert_eq!(constant_pool.utf8(Utf8Index(2)), Err(ConstantPoolError::WrongKind { index: 2, expected: "Utf8" }));
+        assert_eq!(constant_pool.class_name(ClassIndex(1)),
+            Err(ConstantPoolError::WrongKind { index: 1, expected: "Class" }));
+        assert_eq!(constant_pool.member_ref(MemberRefIndex(5)),
+            Err(ConstantPoolError::WrongKind { index: 5, expected: "Fieldref or Methodref" }));
+    }
+
+    #[test]
+    fn rejects_entry_after_long() {
+        let constant_pool = constant_pool();
+        assert!(matches!(constant_pool.get(4), Ok(ConstantPoolEnum::ConstantUnusableItem)));
+        assert_eq!(constant_pool.utf8(Utf8Index(4)), Err(ConstantPoolError::WrongKind { index: 4, expected: "Utf8" }));
+        assert_eq!(constant_pool.class_name(ClassIndex(4)),
+            Err(ConstantPoolError::WrongKind { index: 4, expected: "Class" }));
+    }
+
+    #[test]
+    fn stops_at_largest_index() {
+        let mut constant_pool = ConstantPool::new();
+        for _ in 0..65533 {
+            constant_pool.push(utf8_constant("")).unwrap();
+        }
+        // A long needs two entries, but only 65534 is left
+        assert_eq!(constant_pool.push(ConstantPoolEnum::ConstantLongItem(ConstantLongClass { value: 0 })),
+            Err(ConstantPoolError::Full));
+        assert_eq!(constant_pool.push(utf8_constant("")), Ok(65534));
+        assert_eq!(constant_pool.push(utf8_constant("")), Err(ConstantPoolError::Full));
+        assert_eq!(constant_pool.len(), 65534);
+    }
+}
@@ -0,0 +1,342 @@
+//! Field and method descriptors (JVMS 4.3), such as `[Ljava/lang/String;`
+//! and `(IJ)V`.
+
+use std::error;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BaseType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+}
+
+impl BaseType {
+    pub(crate) fn from_char(c: u8) -> Option<BaseType> {
+        match c {
+            b'B' => Some(BaseType::Byte),
+            b'C' => Some(BaseType::Char),
+            b'D' => Some(BaseType::Double),
+            b'F' => Some(BaseType::Float),
+            b'I' => Some(BaseType::Int),
+            b'J' => Some(BaseType::Long),
+            b'S' => Some(BaseType::Short),
+            b'Z' => Some(BaseType::Boolean),
+            _ => None,
+        }
+    }
+
+    /// The character that stands for this type in descriptors.
+    pub fn descriptor_char(&self) -> char {
+        match self {
+            BaseType::Byte => 'B',
+            BaseType::Char => 'C',
+            BaseType::Double => 'D',
+            BaseType::Float => 'F',
+            BaseType::Int => 'I',
+            BaseType::Long => 'J',
+            BaseType::Short => 'S',
+            BaseType::Boolean => 'Z',
+        }
+    }
+
+    /// The Java keyword for this type.
+    pub fn java_name(&self) -> &'static str {
+        match self {
+            BaseType::Byte => "byte",
+            BaseType::Char => "char",
+            BaseType::Double => "double",
+            BaseType::Float => "float",
+            BaseType::Int => "int",
+            BaseType::Long => "long",
+            BaseType::Short => "short",
+            BaseType::Boolean => "boolean",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Base(BaseType),
+    /// A class or interface, by internal name such as `java/lang/String`
+    Object(String),
+    /// An array with `dimensions` dimensions of `element`, which is not
+    /// itself an array
+    Array { dimensions: u8, element: Box<FieldType> },
+}
+
+impl FieldType {
+    pub fn parse(descriptor: &str) -> Result<FieldType, DescriptorError> {
+        let mut parser = Parser { descriptor, position: 0 };
+        let field_type = parser.field_type()?;
+        parser.end()?;
+        Ok(field_type)
+    }
+
+    /// The number of local variables or operand stack slots a value of this
+    /// type takes up.
+    pub fn slots(&self) -> usize {
+        match self {
+            FieldType::Base(BaseType::Long) | FieldType::Base(BaseType::Double) => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        !matches!(self, FieldType::Base(_))
+    }
+
+    /// The type of the elements of an array type, with one less dimension.
+    pub fn component_type(&self) -> Option<FieldType> {
+        match self {
+            FieldType::Array { dimensions: 1, element } => Some((**element).clone()),
+            FieldType::Array { dimensions, element } =>
+                Some(FieldType::Array { dimensions: dimensions - 1, element: element.clone() }),
+            _ => None,
+        }
+    }
+
+    /// The type as a descriptor, the inverse of `parse`.
+    pub fn descriptor(&self) -> String {
+        match self {
+            FieldType::Base(base_type) => base_type.descriptor_char().to_string(),
+            FieldType::Object(class_name) => format!("L{};", class_name),
+            FieldType::Array { dimensions, element } =>
+                format!("{}{}", "[".repeat(*dimensions as usize), element.descriptor()),
+        }
+    }
+}
+
+/// Formats the type as in Java source, e.g. `java.lang.String[]`.
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldType::Base(base_type) => write!(f, "{}", base_type.java_name()),
+            FieldType::Object(class_name) => write!(f, "{}", class_name.replace('/', ".")),
+            FieldType::Array { dimensions, element } => write!(f, "{}{}", element, "[]".repeat(*dimensions as usize)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    /// `None` for `void`
+    pub return_type: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    /// Parses a method descriptor, which may have parameters taking up at
+    /// most 255 local variables (JVMS 4.3.3). Instance methods also pass
+    /// `this`, which the descriptor cannot tell about, so callers that know
+    /// the method is not static should check `argument_slots() < 255`.
+    pub fn parse(descriptor: &str) -> Result<MethodDescriptor, DescriptorError> {
+        let mut parser = Parser { descriptor, position: 0 };
+        parser.expect(b'(')?;
+        let mut parameters = vec![];
+        let mut slots = 0;
+        while parser.peek() != Some(b')') {
+            let start = parser.position;
+            let parameter = parser.field_type()?;
+            slots += parameter.slots();
+            if slots > 255 {
+                return Err(DescriptorError { descriptor: descriptor.to_string(), position: start });
+            }
+            parameters.push(parameter);
+        }
+        parser.expect(b')')?;
+        let return_type = if parser.peek() == Some(b'V') {
+            parser.position += 1;
+            None
+        } else {
+            Some(parser.field_type()?)
+        };
+        parser.end()?;
+        Ok(MethodDescriptor { parameters, return_type })
+    }
+
+    /// The number of local variables the arguments take up, not counting
+    /// `this`.
+    pub fn argument_slots(&self) -> usize {
+        self.parameters.iter().map(FieldType::slots).sum()
+    }
+
+    pub fn descriptor(&self) -> String {
+        let parameters: String = self.parameters.iter().map(FieldType::descriptor).collect();
+        match &self.return_type {
+            Some(return_type) => format!("({}){}", parameters, return_type.descriptor()),
+            None => format!("({})V", parameters),
+        }
+    }
+
+    /// Formats a method called `name` with this descriptor as in Java
+    /// source, e.g. `void main(java.lang.String[])`.
+    pub fn to_java(&self, name: &str) -> String {
+        let parameters: Vec<String> = self.parameters.iter().map(FieldType::to_string).collect();
+        match &self.return_type {
+            Some(return_type) => format!("{} {}({})", return_type, name, parameters.join(", ")),
+            None => format!("void {}({})", name, parameters.join(", ")),
+        }
+    }
+}
+
+/// A descriptor that does not follow the grammar, with the byte offset at
+/// which parsing failed.
+#[derive(Debug, PartialEq)]
+pub struct DescriptorError {
+    pub descriptor: String,
+    pub position: usize,
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bad descriptor {} at position {}", self.descriptor, self.position)
+    }
+}
+
+impl error::Error for DescriptorError {}
+
+struct Parser<'a> {
+    descriptor: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self) -> DescriptorError {
+        DescriptorError { descriptor: self.descriptor.to_string(), position: self.position }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.descriptor.as_bytes().get(self.position).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), DescriptorError> {
+        if self.peek() != Some(c) {
+            return Err(self.error());
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn end(&self) -> Result<(), DescriptorError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error()),
+        }
+    }
+
+    fn field_type(&mut self) -> Result<FieldType, DescriptorError> {
+        let mut dimensions: usize = 0;
+        while self.peek() == Some(b'[') {
+            dimensions += 1;
+            self.position += 1;
+        }
+        // JVMS 4.3.2: an array type has at most 255 dimensions
+        if dimensions > 255 {
+            return Err(self.error());
+        }
+        let c = self.peek().ok_or_else(|| self.error())?;
+        let element = if c == b'L' {
+            let start = self.position + 1;
+            let length = self.descriptor[start..].find(';').ok_or_else(|| self.error())?;
+            let class_name = &self.descriptor[start..start + length];
+            if class_name.is_empty() || class_name.contains(['.', '[']) {
+                return Err(self.error());
+            }
+            self.position = start + length + 1;
+            FieldType::Object(class_name.to_string())
+        } else {
+            let base_type = BaseType::from_char(c).ok_or_else(|| self.error())?;
+            self.position += 1;
+            FieldType::Base(base_type)
+        };
+        Ok(match dimensions {
+            0 => element,
+            _ => FieldType::Array { dimensions: dimensions as u8, element: Box::new(element) },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_position(descriptor: &str) -> usize {
+        MethodDescriptor::parse(descriptor).unwrap_err().position
+    }
+
+    #[test]
+    fn parses_field_types() {
+        assert_eq!(FieldType::parse("J"), Ok(FieldType::Base(BaseType::Long)));
+        assert_eq!(FieldType::parse("Ljava/lang/String;"), Ok(FieldType::Object(String::from("java/lang/String"))));
+        assert_eq!(FieldType::parse("[[Z"), Ok(FieldType::Array {
+            dimensions: 2,
+            element: Box::new(FieldType::Base(BaseType::Boolean)),
+        }));
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(FieldType::parse("").unwrap_err().position, 0);
+        assert_eq!(FieldType::parse("V").unwrap_err().position, 0);
+        assert_eq!(FieldType::parse("II").unwrap_err().position, 1);
+        assert_eq!(FieldType::parse("[L;").unwrap_err().position, 1);
+        assert_eq!(FieldType::parse("Ljava/lang/String").unwrap_err().position, 0);
+        assert_eq!(FieldType::parse("Ljava.lang.String;").unwrap_err().position, 0);
+        assert_eq!(error_position("I)V"), 0);
+        assert_eq!(error_position("(IQ)V"), 2);
+        assert_eq!(error_position("(I"), 2);
+        assert_eq!(error_position("()"), 2);
+        assert_eq!(error_position("()VV"), 3);
+    }
+
+    #[test]
+    fn limits_array_dimensions() {
+        let array = format!("{}I", "[".repeat(255));
+        assert_eq!(FieldType::parse(&array).unwrap().descriptor(), array);
+        assert_eq!(FieldType::parse(&format!("{}I", "[".repeat(256))).unwrap_err().position, 256);
+    }
+
+    #[test]
+    fn limits_parameter_slots() {
+        let descriptor = format!("({})V", "J".repeat(127));
+        assert_eq!(MethodDescriptor::parse(&descriptor).unwrap().argument_slots(), 254);
+        assert!(MethodDescriptor::parse(&format!("({}I)V", "J".repeat(127))).is_ok());
+        // The 128th long takes the count from 254 to 256
+        assert_eq!(error_position(&format!("({})V", "J".repeat(128))), 128);
+    }
+
+    #[test]
+    fn counts_slots() {
+        assert_eq!(FieldType::Base(BaseType::Double).slots(), 2);
+        assert_eq!(FieldType::parse("[J").unwrap().slots(), 1);
+        let descriptor = MethodDescriptor::parse("(IJLjava/lang/Object;[DF)V").unwrap();
+        assert_eq!(descriptor.argument_slots(), 6);
+        assert!(descriptor.parameters[2].is_reference());
+        assert!(!descriptor.parameters[0].is_reference());
+    }
+
+    #[test]
+    fn takes_component_types() {
+        let array = FieldType::parse("[[Ljava/lang/String;").unwrap();
+        let component = array.component_type().unwrap();
+        assert_eq!(component.descriptor(), "[Ljava/lang/String;");
+        assert_eq!(component.component_type().unwrap().descriptor(), "Ljava/lang/String;");
+        assert_eq!(FieldType::parse("I").unwrap().component_type(), None);
+    }
+
+    #[test]
+    fn formats_as_java() {
+        assert_eq!(FieldType::parse("[[Ljava/lang/String;").unwrap().to_string(), "java.lang.String[][]");
+        let descriptor = MethodDescriptor::parse("([Ljava/lang/String;J)V").unwrap();
+        assert_eq!(descriptor.to_java("main"), "void main(java.lang.String[], long)");
+        assert_eq!(descriptor.descriptor(), "([Ljava/lang/String;J)V");
+        let descriptor = MethodDescriptor::parse("()Ljava/util/List;").unwrap();
+        assert_eq!(descriptor.to_java("list"), "java.util.List list()");
+    }
+}
@@ -0,0 +1,133 @@
+use crate::class::BytecodeClass;
+use crate::class::MethodInfo;
+use crate::value::Value;
+
+/// The activation record for a single method invocation.
+///
+/// The local variable array and operand stack are sized from the `max_locals`
+/// and `max_stack` values of the method's `Code` attribute. A category 2 value
+/// stored at local `n` also claims local `n + 1`, which is left empty, and
+/// one on the operand stack is a single entry that counts twice towards
+/// `max_stack`.
+pub struct Frame<'a> {
+    pub class: &'a BytecodeClass,
+    pub method: &'a MethodInfo,
+    pub locals: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    max_stack: usize,
+    /// Operand stack depth in units, counting category 2 values as two
+    depth: usize,
+    /// Address of the next instruction to execute
+    pub pc: usize,
+    /// Address of the instruction being executed, which is still the
+    /// invoking instruction while a callee runs
+    pub instruction_pc: usize,
+}
+
+impl<'a> Frame<'a> {
+    pub fn new(class: &'a BytecodeClass, method: &'a MethodInfo) -> Frame<'a> {
+        let code = method.get_code_attribute();
+        Frame {
+            class,
+            method,
+            locals: vec![None; code.max_locals as usize],
+            stack: Vec::with_capacity(code.max_stack as usize),
+            max_stack: code.max_stack as usize,
+            depth: 0,
+            pc: 0,
+            instruction_pc: 0,
+        }
+    }
+
+    pub fn code(&self) -> &'a [u8] {
+        self.method.get_code()
+    }
+
+    pub fn push(&mut self, value: Value) {
+        self.depth += value.category();
+        if self.depth > self.max_stack {
+            panic!("Operand stack overflow in {}", self.method.name);
+        }
+        self.stack.push(value);
+    }
+
+    /// The value on top of the operand stack, without popping it.
+    pub fn peek(&self) -> &Value {
+        match self.stack.last() {
+            Some(value) => value,
+            None => panic!("Operand stack underflow in {}", self.method.name),
+        }
+    }
+
+    pub fn pop(&mut self) -> Value {
+        match self.stack.pop() {
+            Some(value) => {
+                self.depth -= value.category();
+                value
+            },
+            None => panic!("Operand stack underflow in {}", self.method.name),
+        }
+    }
+
+    pub fn load(&self, index: usize) -> Value {
+        match &self.locals[index] {
+            Some(value) => value.clone(),
+            None => panic!("Local variable {} is not set in {}", index, self.method.name),
+        }
+    }
+
+    pub fn store(&mut self, index: usize, value: Value) {
+        if value.category() == 2 {
+            self.locals[index + 1] = None;
+        }
+        // Overwriting the second half of a category 2 value invalidates it
+        if index > 0 {
+            if let Some(previous) = &self.locals[index - 1] {
+                if previous.category() == 2 {
+                    self.locals[index - 1] = None;
+                }
+            }
+        }
+        self.locals[index] = Some(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::ClassBuilder;
+    use crate::assembler::CodeBuilder;
+    use crate::assembler::ACC_STATIC;
+    use crate::instruction::Instruction;
+
+    // A class whose only method has a `max_stack` of 2
+    fn class() -> BytecodeClass {
+        let mut class = ClassBuilder::new("Test", "java/lang/Object");
+        let mut code = CodeBuilder::new();
+        code.emit(Instruction::Lconst1);
+        code.emit(Instruction::Lreturn);
+        class.add_method(ACC_STATIC, "f", "()J", code).unwrap();
+        class.build()
+    }
+
+    #[test]
+    fn counts_stack_depth_in_units() {
+        let class = class();
+        let mut frame = Frame::new(&class, &class.methods[0]);
+        frame.push(Value::Int(1));
+        frame.push(Value::Int(2));
+        frame.pop();
+        frame.pop();
+        frame.push(Value::Long(3));
+        assert_eq!(frame.peek().as_long(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Operand stack overflow in f")]
+    fn checks_max_stack() {
+        let class = class();
+        let mut frame = Frame::new(&class, &class.methods[0]);
+        frame.push(Value::Long(3));
+        frame.push(Value::Int(1));
+    }
+}
@@ -0,0 +1,1394 @@
+use std::fmt;
+
+/// A single decoded JVM instruction.
+///
+/// There is one variant per opcode. Local variable indexes are always `u16`:
+/// an instruction whose operands do not fit in a byte is encoded with the
+/// `wide` prefix, and `Wide` keeps the prefix of an instruction that was
+/// decoded with it, so `wide iload 3` decodes to `Wide(Iload(3))` and
+/// re-encodes to the same four bytes. Branch offsets are relative to the
+/// address of the instruction's opcode.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    Nop,
+    AconstNull,
+    IconstM1,
+    Iconst0,
+    Iconst1,
+    Iconst2,
+    Iconst3,
+    Iconst4,
+    Iconst5,
+    Lconst0,
+    Lconst1,
+    Fconst0,
+    Fconst1,
+    Fconst2,
+    Dconst0,
+    Dconst1,
+    Bipush(i8),
+    Sipush(i16),
+    Ldc(u8),
+    LdcW(u16),
+    Ldc2W(u16),
+    Iload(u16),
+    Lload(u16),
+    Fload(u16),
+    Dload(u16),
+    Aload(u16),
+    Iload0,
+    Iload1,
+    Iload2,
+    Iload3,
+    Lload0,
+    Lload1,
+    Lload2,
+    Lload3,
+    Fload0,
+    Fload1,
+    Fload2,
+    Fload3,
+    Dload0,
+    Dload1,
+    Dload2,
+    Dload3,
+    Aload0,
+    Aload1,
+    Aload2,
+    Aload3,
+    Iaload,
+    Laload,
+    Faload,
+    Daload,
+    Aaload,
+    Baload,
+    Caload,
+    Saload,
+    Istore(u16),
+    Lstore(u16),
+    Fstore(u16),
+    Dstore(u16),
+    Astore(u16),
+    Istore0,
+    Istore1,
+    Istore2,
+    Istore3,
+    Lstore0,
+    Lstore1,
+    Lstore2,
+    Lstore3,
+    Fstore0,
+    Fstore1,
+    Fstore2,
+    Fstore3,
+    Dstore0,
+    Dstore1,
+    Dstore2,
+    Dstore3,
+    Astore0,
+    Astore1,
+    Astore2,
+    Astore3,
+    Iastore,
+    Lastore,
+    Fastore,
+    Dastore,
+    Aastore,
+    Bastore,
+    Castore,
+    Sastore,
+    Pop,
+    Pop2,
+    Dup,
+    DupX1,
+    DupX2,
+    Dup2,
+    Dup2X1,
+    Dup2X2,
+    Swap,
+    Iadd,
+    Ladd,
+    Fadd,
+    Dadd,
+    Isub,
+    Lsub,
+    Fsub,
+    Dsub,
+    Imul,
+    Lmul,
+    Fmul,
+    Dmul,
+    Idiv,
+    Ldiv,
+    Fdiv,
+    Ddiv,
+    Irem,
+    Lrem,
+    Frem,
+    Drem,
+    Ineg,
+    Lneg,
+    Fneg,
+    Dneg,
+    Ishl,
+    Lshl,
+    Ishr,
+    Lshr,
+    Iushr,
+    Lushr,
+    Iand,
+    Land,
+    Ior,
+    Lor,
+    Ixor,
+    Lxor,
+    Iinc(u16, i16),
+    I2l,
+    I2f,
+    I2d,
+    L2i,
+    L2f,
+    L2d,
+    F2i,
+    F2l,
+    F2d,
+    D2i,
+    D2l,
+    D2f,
+    I2b,
+    I2c,
+    I2s,
+    Lcmp,
+    Fcmpl,
+    Fcmpg,
+    Dcmpl,
+    Dcmpg,
+    Ifeq(i16),
+    Ifne(i16),
+    Iflt(i16),
+    Ifge(i16),
+    Ifgt(i16),
+    Ifle(i16),
+    IfIcmpeq(i16),
+    IfIcmpne(i16),
+    IfIcmplt(i16),
+    IfIcmpge(i16),
+    IfIcmpgt(i16),
+    IfIcmple(i16),
+    IfAcmpeq(i16),
+    IfAcmpne(i16),
+    Goto(i16),
+    Jsr(i16),
+    Ret(u16),
+    Tableswitch { default: i32, low: i32, high: i32, offsets: Vec<i32> },
+    Lookupswitch { default: i32, pairs: Vec<(i32, i32)> },
+    Ireturn,
+    Lreturn,
+    Freturn,
+    Dreturn,
+    Areturn,
+    Return,
+    Getstatic(u16),
+    Putstatic(u16),
+    Getfield(u16),
+    Putfield(u16),
+    Invokevirtual(u16),
+    Invokespecial(u16),
+    Invokestatic(u16),
+    Invokeinterface(u16, u8),
+    Invokedynamic(u16),
+    New(u16),
+    Newarray(u8),
+    Anewarray(u16),
+    Arraylength,
+    Athrow,
+    Checkcast(u16),
+    Instanceof(u16),
+    Monitorenter,
+    Monitorexit,
+    /// A load, store, `iinc` or `ret` written with the `wide` prefix
+    Wide(Box<Instruction>),
+    Multianewarray(u16, u8),
+    Ifnull(i16),
+    Ifnonnull(i16),
+    GotoW(i32),
+    JsrW(i32),
+    Breakpoint,
+    Impdep1,
+    Impdep2,
+}
+
+/// The meaning of an operand, for disassembly and verification.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OperandType {
+    /// Index into the local variable array
+    LocalIndex,
+    /// Index into the constant pool
+    ConstantPoolIndex,
+    /// Signed byte pushed by `bipush`
+    Byte,
+    /// Signed short pushed by `sipush`
+    Short,
+    /// Signed amount added by `iinc`
+    Increment,
+    /// Signed offset from the address of the opcode
+    BranchOffset,
+    /// Primitive element type code used by `newarray`
+    ArrayType,
+    /// Argument slot count of `invokeinterface`
+    Count,
+    /// Number of dimensions created by `multianewarray`
+    Dimensions,
+    /// Reserved operand byte that must be zero
+    Zero,
+    /// Padding, default offset, bounds and jump table of `tableswitch`
+    TableSwitch,
+    /// Padding, default offset and match-offset pairs of `lookupswitch`
+    LookupSwitch,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    UnknownOpcode { pc: usize, opcode: u8 },
+    InvalidWide { pc: usize, opcode: u8 },
+    UnexpectedEnd { pc: usize },
+    /// A `tableswitch` whose `high` is less than its `low`
+    BadSwitchBounds { pc: usize, low: i32, high: i32 },
+    /// A `lookupswitch` with a negative `npairs`, or with keys that are not
+    /// in increasing order
+    BadSwitchPairs { pc: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnknownOpcode { pc, opcode } =>
+                write!(f, "Unknown opcode {} at {}", opcode, pc),
+            DecodeError::InvalidWide { pc, opcode } =>
+                write!(f, "wide cannot modify opcode {} at {}", opcode, pc),
+            DecodeError::UnexpectedEnd { pc } =>
+                write!(f, "Code ends in the middle of the instruction at {}", pc),
+            DecodeError::BadSwitchBounds { pc, low, high } =>
+                write!(f, "tableswitch at {} has high {} less than low {}", pc, high, low),
+            DecodeError::BadSwitchPairs { pc } =>
+                write!(f, "lookupswitch at {} has a negative count or unsorted keys", pc),
+        }
+    }
+}
+
+struct Cursor<'a> {
+    code: &'a [u8],
+    pc: usize,
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        match self.code.get(self.position..self.position + n) {
+            Some(bytes) => {
+                self.position += n;
+                Ok(bytes)
+            },
+            None => Err(DecodeError::UnexpectedEnd { pc: self.pc }),
+        }
+    }
+
+    fn u1(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u2(&mut self) -> Result<u16, DecodeError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i2(&mut self) -> Result<i16, DecodeError> {
+        Ok(self.u2()? as i16)
+    }
+
+    fn i4(&mut self) -> Result<i32, DecodeError> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+// tableswitch and lookupswitch operands start on a 4-byte boundary
+fn switch_padding(pc: usize) -> usize {
+    (4 - (pc + 1) % 4) % 4
+}
+
+/// Decodes the instruction starting at `pc`, returning it along with its
+/// length in bytes.
+pub fn decode(code: &[u8], pc: usize) -> Result<(Instruction, usize), DecodeError> {
+    let mut cursor = Cursor { code, pc, position: pc };
+    let opcode = cursor.u1()?;
+    let instruction = match opcode {
+        0 => Instruction::Nop,
+        1 => Instruction::AconstNull,
+        2 => Instruction::IconstM1,
+        3 => Instruction::Iconst0,
+        4 => Instruction::Iconst1,
+        5 => Instruction::Iconst2,
+        6 => Instruction::Iconst3,
+        7 => Instruction::Iconst4,
+        8 => Instruction::Iconst5,
+        9 => Instruction::Lconst0,
+        10 => Instruction::Lconst1,
+        11 => Instruction::Fconst0,
+        12 => Instruction::Fconst1,
+        13 => Instruction::Fconst2,
+        14 => Instruction::Dconst0,
+        15 => Instruction::Dconst1,
+        16 => Instruction::Bipush(cursor.u1()? as i8),
+        17 => Instruction::Sipush(cursor.i2()?),
+        18 => Instruction::Ldc(cursor.u1()?),
+        19 => Instruction::LdcW(cursor.u2()?),
+        20 => Instruction::Ldc2W(cursor.u2()?),
+        21 => Instruction::Iload(cursor.u1()? as u16),
+        22 => Instruction::Lload(cursor.u1()? as u16),
+        23 => Instruction::Fload(cursor.u1()? as u16),
+        24 => Instruction::Dload(cursor.u1()? as u16),
+        25 => Instruction::Aload(cursor.u1()? as u16),
+        26 => Instruction::Iload0,
+        27 => Instruction::Iload1,
+        28 => Instruction::Iload2,
+        29 => Instruction::Iload3,
+        30 => Instruction::Lload0,
+        31 => Instruction::Lload1,
+        32 => Instruction::Lload2,
+        33 => Instruction::Lload3,
+        34 => Instruction::Fload0,
+        35 => Instruction::Fload1,
+        36 => Instruction::Fload2,
+        37 => Instruction::Fload3,
+        38 => Instruction::Dload0,
+        39 => Instruction::Dload1,
+        40 => Instruction::Dload2,
+        41 => Instruction::Dload3,
+        42 => Instruction::Aload0,
+        43 => Instruction::Aload1,
+        44 => Instruction::Aload2,
+        45 => Instruction::Aload3,
+        46 => Instruction::Iaload,
+        47 => Instruction::Laload,
+        48 => Instruction::Faload,
+        49 => Instruction::Daload,
+        50 => Instruction::Aaload,
+        51 => Instruction::Baload,
+        52 => Instruction::Caload,
+        53 => Instruction::Saload,
+        54 => Instruction::Istore(cursor.u1()? as u16),
+        55 => Instruction::Lstore(cursor.u1()? as u16),
+        56 => Instruction::Fstore(cursor.u1()? as u16),
+        57 => Instruction::Dstore(cursor.u1()? as u16),
+        58 => Instruction::Astore(cursor.u1()? as u16),
+        59 => Instruction::Istore0,
+        60 => Instruction::Istore1,
+        61 => Instruction::Istore2,
+        62 => Instruction::Istore3,
+        63 => Instruction::Lstore0,
+        64 => Instruction::Lstore1,
+        65 => Instruction::Lstore2,
+        66 => Instruction::Lstore3,
+        67 => Instruction::Fstore0,
+        68 => Instruction::Fstore1,
+        69 => Instruction::Fstore2,
+        70 => Instruction::Fstore3,
+        71 => Instruction::Dstore0,
+        72 => Instruction::Dstore1,
+        73 => Instruction::Dstore2,
+        74 => Instruction::Dstore3,
+        75 => Instruction::Astore0,
+        76 => Instruction::Astore1,
+        77 => Instruction::Astore2,
+        78 => Instruction::Astore3,
+        79 => Instruction::Iastore,
+        80 => Instruction::Lastore,
+        81 => Instruction::Fastore,
+        82 => Instruction::Dastore,
+        83 => Instruction::Aastore,
+        84 => Instruction::Bastore,
+        85 => Instruction::Castore,
+        86 => Instruction::Sastore,
+        87 => Instruction::Pop,
+        88 => Instruction::Pop2,
+        89 => Instruction::Dup,
+        90 => Instruction::DupX1,
+        91 => Instruction::DupX2,
+        92 => Instruction::Dup2,
+        93 => Instruction::Dup2X1,
+        94 => Instruction::Dup2X2,
+        95 => Instruction::Swap,
+        96 => Instruction::Iadd,
+        97 => Instruction::Ladd,
+        98 => Instruction::Fadd,
+        99 => Instruction::Dadd,
+        100 => Instruction::Isub,
+        101 => Instruction::Lsub,
+        102 => Instruction::Fsub,
+        103 => Instruction::Dsub,
+        104 => Instruction::Imul,
+        105 => Instruction::Lmul,
+        106 => Instruction::Fmul,
+        107 => Instruction::Dmul,
+        108 => Instruction::Idiv,
+        109 => Instruction::Ldiv,
+        110 => Instruction::Fdiv,
+        111 => Instruction::Ddiv,
+        112 => Instruction::Irem,
+        113 => Instruction::Lrem,
+        114 => Instruction::Frem,
+        115 => Instruction::Drem,
+        116 => Instruction::Ineg,
+        117 => Instruction::Lneg,
+        118 => Instruction::Fneg,
+        119 => Instruction::Dneg,
+        120 => Instruction::Ishl,
+        121 => Instruction::Lshl,
+        122 => Instruction::Ishr,
+        123 => Instruction::Lshr,
+        124 => Instruction::Iushr,
+        125 => Instruction::Lushr,
+        126 => Instruction::Iand,
+        127 => Instruction::Land,
+        128 => Instruction::Ior,
+        129 => Instruction::Lor,
+        130 => Instruction::Ixor,
+        131 => Instruction::Lxor,
+        132 => Instruction::Iinc(cursor.u1()? as u16, cursor.u1()? as i8 as i16),
+        133 => Instruction::I2l,
+        134 => Instruction::I2f,
+        135 => Instruction::I2d,
+        136 => Instruction::L2i,
+        137 => Instruction::L2f,
+        138 => Instruction::L2d,
+        139 => Instruction::F2i,
+        140 => Instruction::F2l,
+        141 => Instruction::F2d,
+        142 => Instruction::D2i,
+        143 => Instruction::D2l,
+        144 => Instruction::D2f,
+        145 => Instruction::I2b,
+        146 => Instruction::I2c,
+        147 => Instruction::I2s,
+        148 => Instruction::Lcmp,
+        149 => Instruction::Fcmpl,
+        150 => Instruction::Fcmpg,
+        151 => Instruction::Dcmpl,
+        152 => Instruction::Dcmpg,
+        153 => Instruction::Ifeq(cursor.i2()?),
+        154 => Instruction::Ifne(cursor.i2()?),
+        155 => Instruction::Iflt(cursor.i2()?),
+        156 => Instruction::Ifge(cursor.i2()?),
+        157 => Instruction::Ifgt(cursor.i2()?),
+        158 => Instruction::Ifle(cursor.i2()?),
+        159 => Instruction::IfIcmpeq(cursor.i2()?),
+        160 => Instruction::IfIcmpne(cursor.i2()?),
+        161 => Instruction::IfIcmplt(cursor.i2()?),
+        162 => Instruction::IfIcmpge(cursor.i2()?),
+        163 => Instruction::IfIcmpgt(cursor.i2()?),
+        164 => Instruction::IfIcmple(cursor.i2()?),
+        165 => Instruction::IfAcmpeq(cursor.i2()?),
+        166 => Instruction::IfAcmpne(cursor.i2()?),
+        167 => Instruction::Goto(cursor.i2()?),
+        168 => Instruction::Jsr(cursor.i2()?),
+        169 => Instruction::Ret(cursor.u1()? as u16),
+        172 => Instruction::Ireturn,
+        173 => Instruction::Lreturn,
+        174 => Instruction::Freturn,
+        175 => Instruction::Dreturn,
+        176 => Instruction::Areturn,
+        177 => Instruction::Return,
+        178 => Instruction::Getstatic(cursor.u2()?),
+        179 => Instruction::Putstatic(cursor.u2()?),
+        180 => Instruction::Getfield(cursor.u2()?),
+        181 => Instruction::Putfield(cursor.u2()?),
+        182 => Instruction::Invokevirtual(cursor.u2()?),
+        183 => Instruction::Invokespecial(cursor.u2()?),
+        184 => Instruction::Invokestatic(cursor.u2()?),
+        187 => Instruction::New(cursor.u2()?),
+        188 => Instruction::Newarray(cursor.u1()?),
+        189 => Instruction::Anewarray(cursor.u2()?),
+        190 => Instruction::Arraylength,
+        191 => Instruction::Athrow,
+        192 => Instruction::Checkcast(cursor.u2()?),
+        193 => Instruction::Instanceof(cursor.u2()?),
+        194 => Instruction::Monitorenter,
+        195 => Instruction::Monitorexit,
+        197 => Instruction::Multianewarray(cursor.u2()?, cursor.u1()?),
+        198 => Instruction::Ifnull(cursor.i2()?),
+        199 => Instruction::Ifnonnull(cursor.i2()?),
+        200 => Instruction::GotoW(cursor.i4()?),
+        201 => Instruction::JsrW(cursor.i4()?),
+        202 => Instruction::Breakpoint,
+        254 => Instruction::Impdep1,
+        255 => Instruction::Impdep2,
+        170 => {
+            cursor.bytes(switch_padding(pc))?;
+            let default = cursor.i4()?;
+            let low = cursor.i4()?;
+            let high = cursor.i4()?;
+            if high < low {
+                return Err(DecodeError::BadSwitchBounds { pc, low, high });
+            }
+            let offsets = (low..=high)
+                .map(|_| cursor.i4())
+                .collect::<Result<Vec<i32>, DecodeError>>()?;
+            Instruction::Tableswitch { default, low, high, offsets }
+        },
+        171 => {
+            cursor.bytes(switch_padding(pc))?;
+            let default = cursor.i4()?;
+            let npairs = cursor.i4()?;
+            if npairs < 0 {
+                return Err(DecodeError::BadSwitchPairs { pc });
+            }
+            let pairs = (0..npairs)
+                .map(|_| Ok((cursor.i4()?, cursor.i4()?)))
+                .collect::<Result<Vec<(i32, i32)>, DecodeError>>()?;
+            if pairs.windows(2).any(|w| w[0].0 >= w[1].0) {
+                return Err(DecodeError::BadSwitchPairs { pc });
+            }
+            Instruction::Lookupswitch { default, pairs }
+        },
+        185 => {
+            let index = cursor.u2()?;
+            let count = cursor.u1()?;
+            cursor.u1()?;
+            Instruction::Invokeinterface(index, count)
+        },
+        186 => {
+            let index = cursor.u2()?;
+            cursor.u2()?;
+            Instruction::Invokedynamic(index)
+        },
+        196 => {
+            let modified = cursor.u1()?;
+            Instruction::Wide(Box::new(match modified {
+                21 => Instruction::Iload(cursor.u2()?),
+                22 => Instruction::Lload(cursor.u2()?),
+                23 => Instruction::Fload(cursor.u2()?),
+                24 => Instruction::Dload(cursor.u2()?),
+                25 => Instruction::Aload(cursor.u2()?),
+                54 => Instruction::Istore(cursor.u2()?),
+                55 => Instruction::Lstore(cursor.u2()?),
+                56 => Instruction::Fstore(cursor.u2()?),
+                57 => Instruction::Dstore(cursor.u2()?),
+                58 => Instruction::Astore(cursor.u2()?),
+                169 => Instruction::Ret(cursor.u2()?),
+                132 => Instruction::Iinc(cursor.u2()?, cursor.i2()?),
+                _ => return Err(DecodeError::InvalidWide { pc, opcode: modified }),
+            }))
+        },
+        _ => return Err(DecodeError::UnknownOpcode { pc, opcode }),
+    };
+    Ok((instruction, cursor.position - pc))
+}
+
+/// Decodes a whole code array into instructions paired with their addresses.
+pub fn decode_all(code: &[u8]) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+    let mut instructions = vec![];
+    let mut pc = 0;
+    while pc < code.len() {
+        let (instruction, length) = decode(code, pc)?;
+        instructions.push((pc, instruction));
+        pc += length;
+    }
+    Ok(instructions)
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Instruction::Nop => 0,
+            Instruction::AconstNull => 1,
+            Instruction::IconstM1 => 2,
+            Instruction::Iconst0 => 3,
+            Instruction::Iconst1 => 4,
+            Instruction::Iconst2 => 5,
+            Instruction::Iconst3 => 6,
+            Instruction::Iconst4 => 7,
+            Instruction::Iconst5 => 8,
+            Instruction::Lconst0 => 9,
+            Instruction::Lconst1 => 10,
+            Instruction::Fconst0 => 11,
+            Instruction::Fconst1 => 12,
+            Instruction::Fconst2 => 13,
+            Instruction::Dconst0 => 14,
+            Instruction::Dconst1 => 15,
+            Instruction::Bipush(_) => 16,
+            Instruction::Sipush(_) => 17,
+            Instruction::Ldc(_) => 18,
+            Instruction::LdcW(_) => 19,
+            Instruction::Ldc2W(_) => 20,
+            Instruction::Iload(_) => 21,
+            Instruction::Lload(_) => 22,
+            Instruction::Fload(_) => 23,
+            Instruction::Dload(_) => 24,
+            Instruction::Aload(_) => 25,
+            Instruction::Iload0 => 26,
+            Instruction::Iload1 => 27,
+            Instruction::Iload2 => 28,
+            Instruction::Iload3 => 29,
+            Instruction::Lload0 => 30,
+            Instruction::Lload1 => 31,
+            Instruction::Lload2 => 32,
+            Instruction::Lload3 => 33,
+            Instruction::Fload0 => 34,
+            Instruction::Fload1 => 35,
+            Instruction::Fload2 => 36,
+            Instruction::Fload3 => 37,
+            Instruction::Dload0 => 38,
+            Instruction::Dload1 => 39,
+            Instruction::Dload2 => 40,
+            Instruction::Dload3 => 41,
+            Instruction::Aload0 => 42,
+            Instruction::Aload1 => 43,
+            Instruction::Aload2 => 44,
+            Instruction::Aload3 => 45,
+            Instruction::Iaload => 46,
+            Instruction::Laload => 47,
+            Instruction::Faload => 48,
+            Instruction::Daload => 49,
+            Instruction::Aaload => 50,
+            Instruction::Baload => 51,
+            Instruction::Caload => 52,
+            Instruction::Saload => 53,
+            Instruction::Istore(_) => 54,
+            Instruction::Lstore(_) => 55,
+            Instruction::Fstore(_) => 56,
+            Instruction::Dstore(_) => 57,
+            Instruction::Astore(_) => 58,
+            Instruction::Istore0 => 59,
+            Instruction::Istore1 => 60,
+            Instruction::Istore2 => 61,
+            Instruction::Istore3 => 62,
+            Instruction::Lstore0 => 63,
+            Instruction::Lstore1 => 64,
+            Instruction::Lstore2 => 65,
+            Instruction::Lstore3 => 66,
+            Instruction::Fstore0 => 67,
+            Instruction::Fstore1 => 68,
+            Instruction::Fstore2 => 69,
+            Instruction::Fstore3 => 70,
+            Instruction::Dstore0 => 71,
+            Instruction::Dstore1 => 72,
+            Instruction::Dstore2 => 73,
+            Instruction::Dstore3 => 74,
+            Instruction::Astore0 => 75,
+            Instruction::Astore1 => 76,
+            Instruction::Astore2 => 77,
+            Instruction::Astore3 => 78,
+            Instruction::Iastore => 79,
+            Instruction::Lastore => 80,
+            Instruction::Fastore => 81,
+            Instruction::Dastore => 82,
+            Instruction::Aastore => 83,
+            Instruction::Bastore => 84,
+            Instruction::Castore => 85,
+            Instruction::Sastore => 86,
+            Instruction::Pop => 87,
+            Instruction::Pop2 => 88,
+            Instruction::Dup => 89,
+            Instruction::DupX1 => 90,
+            Instruction::DupX2 => 91,
+            Instruction::Dup2 => 92,
+            Instruction::Dup2X1 => 93,
+            Instruction::Dup2X2 => 94,
+            Instruction::Swap => 95,
+            Instruction::Iadd => 96,
+            Instruction::Ladd => 97,
+            Instruction::Fadd => 98,
+            Instruction::Dadd => 99,
+            Instruction::Isub => 100,
+            Instruction::Lsub => 101,
+            Instruction::Fsub => 102,
+            Instruction::Dsub => 103,
+            Instruction::Imul => 104,
+            Instruction::Lmul => 105,
+            Instruction::Fmul => 106,
+            Instruction::Dmul => 107,
+            Instruction::Idiv => 108,
+            Instruction::Ldiv => 109,
+            Instruction::Fdiv => 110,
+            Instruction::Ddiv => 111,
+            Instruction::Irem => 112,
+            Instruction::Lrem => 113,
+            Instruction::Frem => 114,
+            Instruction::Drem => 115,
+            Instruction::Ineg => 116,
+            Instruction::Lneg => 117,
+            Instruction::Fneg => 118,
+            Instruction::Dneg => 119,
+            Instruction::Ishl => 120,
+            Instruction::Lshl => 121,
+            Instruction::Ishr => 122,
+            Instruction::Lshr => 123,
+            Instruction::Iushr => 124,
+            Instruction::Lushr => 125,
+            Instruction::Iand => 126,
+            Instruction::Land => 127,
+            Instruction::Ior => 128,
+            Instruction::Lor => 129,
+            Instruction::Ixor => 130,
+            Instruction::Lxor => 131,
+            Instruction::Iinc(_, _) => 132,
+            Instruction::I2l => 133,
+            Instruction::I2f => 134,
+            Instruction::I2d => 135,
+            Instruction::L2i => 136,
+            Instruction::L2f => 137,
+            Instruction::L2d => 138,
+            Instruction::F2i => 139,
+            Instruction::F2l => 140,
+            Instruction::F2d => 141,
+            Instruction::D2i => 142,
+            Instruction::D2l => 143,
+            Instruction::D2f => 144,
+            Instruction::I2b => 145,
+            Instruction::I2c => 146,
+            Instruction::I2s => 147,
+            Instruction::Lcmp => 148,
+            Instruction::Fcmpl => 149,
+            Instruction::Fcmpg => 150,
+            Instruction::Dcmpl => 151,
+            Instruction::Dcmpg => 152,
+            Instruction::Ifeq(_) => 153,
+            Instruction::Ifne(_) => 154,
+            Instruction::Iflt(_) => 155,
+            Instruction::Ifge(_) => 156,
+            Instruction::Ifgt(_) => 157,
+            Instruction::Ifle(_) => 158,
+            Instruction::IfIcmpeq(_) => 159,
+            Instruction::IfIcmpne(_) => 160,
+            Instruction::IfIcmplt(_) => 161,
+            Instruction::IfIcmpge(_) => 162,
+            Instruction::IfIcmpgt(_) => 163,
+            Instruction::IfIcmple(_) => 164,
+            Instruction::IfAcmpeq(_) => 165,
+            Instruction::IfAcmpne(_) => 166,
+            Instruction::Goto(_) => 167,
+            Instruction::Jsr(_) => 168,
+            Instruction::Ret(_) => 169,
+            Instruction::Tableswitch { .. } => 170,
+            Instruction::Lookupswitch { .. } => 171,
+            Instruction::Ireturn => 172,
+            Instruction::Lreturn => 173,
+            Instruction::Freturn => 174,
+            Instruction::Dreturn => 175,
+            Instruction::Areturn => 176,
+            Instruction::Return => 177,
+            Instruction::Getstatic(_) => 178,
+            Instruction::Putstatic(_) => 179,
+            Instruction::Getfield(_) => 180,
+            Instruction::Putfield(_) => 181,
+            Instruction::Invokevirtual(_) => 182,
+            Instruction::Invokespecial(_) => 183,
+            Instruction::Invokestatic(_) => 184,
+            Instruction::Invokeinterface(_, _) => 185,
+            Instruction::Invokedynamic(_) => 186,
+            Instruction::New(_) => 187,
+            Instruction::Newarray(_) => 188,
+            Instruction::Anewarray(_) => 189,
+            Instruction::Arraylength => 190,
+            Instruction::Athrow => 191,
+            Instruction::Checkcast(_) => 192,
+            Instruction::Instanceof(_) => 193,
+            Instruction::Monitorenter => 194,
+            Instruction::Monitorexit => 195,
+            Instruction::Wide(_) => 196,
+            Instruction::Multianewarray(_, _) => 197,
+            Instruction::Ifnull(_) => 198,
+            Instruction::Ifnonnull(_) => 199,
+            Instruction::GotoW(_) => 200,
+            Instruction::JsrW(_) => 201,
+            Instruction::Breakpoint => 202,
+            Instruction::Impdep1 => 254,
+            Instruction::Impdep2 => 255,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Nop => "nop",
+            Instruction::AconstNull => "aconst_null",
+            Instruction::IconstM1 => "iconst_m1",
+            Instruction::Iconst0 => "iconst_0",
+            Instruction::Iconst1 => "iconst_1",
+            Instruction::Iconst2 => "iconst_2",
+            Instruction::Iconst3 => "iconst_3",
+            Instruction::Iconst4 => "iconst_4",
+            Instruction::Iconst5 => "iconst_5",
+            Instruction::Lconst0 => "lconst_0",
+            Instruction::Lconst1 => "lconst_1",
+            Instruction::Fconst0 => "fconst_0",
+            Instruction::Fconst1 => "fconst_1",
+            Instruction::Fconst2 => "fconst_2",
+            Instruction::Dconst0 => "dconst_0",
+            Instruction::Dconst1 => "dconst_1",
+            Instruction::Bipush(_) => "bipush",
+            Instruction::Sipush(_) => "sipush",
+            Instruction::Ldc(_) => "ldc",
+            Instruction::LdcW(_) => "ldc_w",
+            Instruction::Ldc2W(_) => "ldc2_w",
+            Instruction::Iload(_) => "iload",
+            Instruction::Lload(_) => "lload",
+            Instruction::Fload(_) => "fload",
+            Instruction::Dload(_) => "dload",
+            Instruction::Aload(_) => "aload",
+            Instruction::Iload0 => "iload_0",
+            Instruction::Iload1 => "iload_1",
+            Instruction::Iload2 => "iload_2",
+            Instruction::Iload3 => "iload_3",
+            Instruction::Lload0 => "lload_0",
+            Instruction::Lload1 => "lload_1",
+            Instruction::Lload2 => "lload_2",
+            Instruction::Lload3 => "lload_3",
+            Instruction::Fload0 => "fload_0",
+            Instruction::Fload1 => "fload_1",
+            Instruction::Fload2 => "fload_2",
+            Instruction::Fload3 => "fload_3",
+            Instruction::Dload0 => "dload_0",
+            Instruction::Dload1 => "dload_1",
+            Instruction::Dload2 => "dload_2",
+            Instruction::Dload3 => "dload_3",
+            Instruction::Aload0 => "aload_0",
+            Instruction::Aload1 => "aload_1",
+            Instruction::Aload2 => "aload_2",
+            Instruction::Aload3 => "aload_3",
+            Instruction::Iaload => "iaload",
+            Instruction::Laload => "laload",
+            Instruction::Faload => "faload",
+            Instruction::Daload => "daload",
+            Instruction::Aaload => "aaload",
+            Instruction::Baload => "baload",
+            Instruction::Caload => "caload",
+            Instruction::Saload => "saload",
+            Instruction::Istore(_) => "istore",
+            Instruction::Lstore(_) => "lstore",
+            Instruction::Fstore(_) => "fstore",
+            Instruction::Dstore(_) => "dstore",
+            Instruction::Astore(_) => "astore",
+            Instruction::Istore0 => "istore_0",
+            Instruction::Istore1 => "istore_1",
+            Instruction::Istore2 => "istore_2",
+            Instruction::Istore3 => "istore_3",
+            Instruction::Lstore0 => "lstore_0",
+            Instruction::Lstore1 => "lstore_1",
+            Instruction::Lstore2 => "lstore_2",
+            Instruction::Lstore3 => "lstore_3",
+            Instruction::Fstore0 => "fstore_0",
+            Instruction::Fstore1 => "fstore_1",
+            Instruction::Fstore2 => "fstore_2",
+            Instruction::Fstore3 => "fstore_3",
+            Instruction::Dstore0 => "dstore_0",
+            Instruction::Dstore1 => "dstore_1",
+            Instruction::Dstore2 => "dstore_2",
+            Instruction::Dstore3 => "dstore_3",
+            Instruction::Astore0 => "astore_0",
+            Instruction::Astore1 => "astore_1",
+            Instruction::Astore2 => "astore_2",
+            Instruction::Astore3 => "astore_3",
+            Instruction::Iastore => "iastore",
+            Instruction::Lastore => "lastore",
+            Instruction::Fastore => "fastore",
+            Instruction::Dastore => "dastore",
+            Instruction::Aastore => "aastore",
+            Instruction::Bastore => "bastore",
+            Instruction::Castore => "castore",
+            Instruction::Sastore => "sastore",
+            Instruction::Pop => "pop",
+            Instruction::Pop2 => "pop2",
+            Instruction::Dup => "dup",
+            Instruction::DupX1 => "dup_x1",
+            Instruction::DupX2 => "dup_x2",
+            Instruction::Dup2 => "dup2",
+            Instruction::Dup2X1 => "dup2_x1",
+            Instruction::Dup2X2 => "dup2_x2",
+            Instruction::Swap => "swap",
+            Instruction::Iadd => "iadd",
+            Instruction::Ladd => "ladd",
+            Instruction::Fadd => "fadd",
+            Instruction::Dadd => "dadd",
+            Instruction::Isub => "isub",
+            Instruction::Lsub => "lsub",
+            Instruction::Fsub => "fsub",
+            Instruction::Dsub => "dsub",
+            Instruction::Imul => "imul",
+            Instruction::Lmul => "lmul",
+            Instruction::Fmul => "fmul",
+            Instruction::Dmul => "dmul",
+            Instruction::Idiv => "idiv",
+            Instruction::Ldiv => "ldiv",
+            Instruction::Fdiv => "fdiv",
+            Instruction::Ddiv => "ddiv",
+            Instruction::Irem => "irem",
+            Instruction::Lrem => "lrem",
+            Instruction::Frem => "frem",
+            Instruction::Drem => "drem",
+            Instruction::Ineg => "ineg",
+            Instruction::Lneg => "lneg",
+            Instruction::Fneg => "fneg",
+            Instruction::Dneg => "dneg",
+            Instruction::Ishl => "ishl",
+            Instruction::Lshl => "lshl",
+            Instruction::Ishr => "ishr",
+            Instruction::Lshr => "lshr",
+            Instruction::Iushr => "iushr",
+            Instruction::Lushr => "lushr",
+            Instruction::Iand => "iand",
+            Instruction::Land => "land",
+            Instruction::Ior => "ior",
+            Instruction::Lor => "lor",
+            Instruction::Ixor => "ixor",
+            Instruction::Lxor => "lxor",
+            Instruction::Iinc(_, _) => "iinc",
+            Instruction::I2l => "i2l",
+            Instruction::I2f => "i2f",
+            Instruction::I2d => "i2d",
+            Instruction::L2i => "l2i",
+            Instruction::L2f => "l2f",
+            Instruction::L2d => "l2d",
+            Instruction::F2i => "f2i",
+            Instruction::F2l => "f2l",
+            Instruction::F2d => "f2d",
+            Instruction::D2i => "d2i",
+            Instruction::D2l => "d2l",
+            Instruction::D2f => "d2f",
+            Instruction::I2b => "i2b",
+            Instruction::I2c => "i2c",
+            Instruction::I2s => "i2s",
+            Instruction::Lcmp => "lcmp",
+            Instruction::Fcmpl => "fcmpl",
+            Instruction::Fcmpg => "fcmpg",
+            Instruction::Dcmpl => "dcmpl",
+            Instruction::Dcmpg => "dcmpg",
+            Instruction::Ifeq(_) => "ifeq",
+            Instruction::Ifne(_) => "ifne",
+            Instruction::Iflt(_) => "iflt",
+            Instruction::Ifge(_) => "ifge",
+            Instruction::Ifgt(_) => "ifgt",
+            Instruction::Ifle(_) => "ifle",
+            Instruction::IfIcmpeq(_) => "if_icmpeq",
+            Instruction::IfIcmpne(_) => "if_icmpne",
+            Instruction::IfIcmplt(_) => "if_icmplt",
+            Instruction::IfIcmpge(_) => "if_icmpge",
+            Instruction::IfIcmpgt(_) => "if_icmpgt",
+            Instruction::IfIcmple(_) => "if_icmple",
+            Instruction::IfAcmpeq(_) => "if_acmpeq",
+            Instruction::IfAcmpne(_) => "if_acmpne",
+            Instruction::Goto(_) => "goto",
+            Instruction::Jsr(_) => "jsr",
+            Instruction::Ret(_) => "ret",
+            Instruction::Tableswitch { .. } => "tableswitch",
+            Instruction::Lookupswitch { .. } => "lookupswitch",
+            Instruction::Ireturn => "ireturn",
+            Instruction::Lreturn => "lreturn",
+            Instruction::Freturn => "freturn",
+            Instruction::Dreturn => "dreturn",
+            Instruction::Areturn => "areturn",
+            Instruction::Return => "return",
+            Instruction::Getstatic(_) => "getstatic",
+            Instruction::Putstatic(_) => "putstatic",
+            Instruction::Getfield(_) => "getfield",
+            Instruction::Putfield(_) => "putfield",
+            Instruction::Invokevirtual(_) => "invokevirtual",
+            Instruction::Invokespecial(_) => "invokespecial",
+            Instruction::Invokestatic(_) => "invokestatic",
+            Instruction::Invokeinterface(_, _) => "invokeinterface",
+            Instruction::Invokedynamic(_) => "invokedynamic",
+            Instruction::New(_) => "new",
+            Instruction::Newarray(_) => "newarray",
+            Instruction::Anewarray(_) => "anewarray",
+            Instruction::Arraylength => "arraylength",
+            Instruction::Athrow => "athrow",
+            Instruction::Checkcast(_) => "checkcast",
+            Instruction::Instanceof(_) => "instanceof",
+            Instruction::Monitorenter => "monitorenter",
+            Instruction::Monitorexit => "monitorexit",
+            Instruction::Wide(_) => "wide",
+            Instruction::Multianewarray(_, _) => "multianewarray",
+            Instruction::Ifnull(_) => "ifnull",
+            Instruction::Ifnonnull(_) => "ifnonnull",
+            Instruction::GotoW(_) => "goto_w",
+            Instruction::JsrW(_) => "jsr_w",
+            Instruction::Breakpoint => "breakpoint",
+            Instruction::Impdep1 => "impdep1",
+            Instruction::Impdep2 => "impdep2",
+        }
+    }
+
+    pub fn operand_types(&self) -> &'static [OperandType] {
+        match self {
+            Instruction::Bipush(_) => &[OperandType::Byte],
+            Instruction::Sipush(_) => &[OperandType::Short],
+            Instruction::Ldc(_) => &[OperandType::ConstantPoolIndex],
+            Instruction::LdcW(_)
+            | Instruction::Ldc2W(_)
+            | Instruction::Getstatic(_)
+            | Instruction::Putstatic(_)
+            | Instruction::Getfield(_)
+            | Instruction::Putfield(_)
+            | Instruction::Invokevirtual(_)
+            | Instruction::Invokespecial(_)
+            | Instruction::Invokestatic(_)
+            | Instruction::New(_)
+            | Instruction::Anewarray(_)
+            | Instruction::Checkcast(_)
+            | Instruction::Instanceof(_) => &[OperandType::ConstantPoolIndex],
+            Instruction::Iload(_)
+            | Instruction::Lload(_)
+            | Instruction::Fload(_)
+            | Instruction::Dload(_)
+            | Instruction::Aload(_)
+            | Instruction::Istore(_)
+            | Instruction::Lstore(_)
+            | Instruction::Fstore(_)
+            | Instruction::Dstore(_)
+            | Instruction::Astore(_)
+            | Instruction::Ret(_) => &[OperandType::LocalIndex],
+            Instruction::Iinc(_, _) => &[OperandType::LocalIndex, OperandType::Increment],
+            Instruction::Ifeq(_)
+            | Instruction::Ifne(_)
+            | Instruction::Iflt(_)
+            | Instruction::Ifge(_)
+            | Instruction::Ifgt(_)
+            | Instruction::Ifle(_)
+            | Instruction::IfIcmpeq(_)
+            | Instruction::IfIcmpne(_)
+            | Instruction::IfIcmplt(_)
+            | Instruction::IfIcmpge(_)
+            | Instruction::IfIcmpgt(_)
+            | Instruction::IfIcmple(_)
+            | Instruction::IfAcmpeq(_)
+            | Instruction::IfAcmpne(_)
+            | Instruction::Goto(_)
+            | Instruction::Jsr(_)
+            | Instruction::Ifnull(_)
+            | Instruction::Ifnonnull(_) => &[OperandType::BranchOffset],
+            Instruction::Tableswitch { .. } => &[OperandType::TableSwitch],
+            Instruction::Lookupswitch { .. } => &[OperandType::LookupSwitch],
+            Instruction::Invokeinterface(_, _) => &[OperandType::ConstantPoolIndex, OperandType::Count, OperandType::Zero],
+            Instruction::Invokedynamic(_) => &[OperandType::ConstantPoolIndex, OperandType::Zero, OperandType::Zero],
+            Instruction::Newarray(_) => &[OperandType::ArrayType],
+            Instruction::Multianewarray(_, _) => &[OperandType::ConstantPoolIndex, OperandType::Dimensions],
+            Instruction::GotoW(_)
+            | Instruction::JsrW(_) => &[OperandType::BranchOffset],
+            Instruction::Wide(instruction) => instruction.operand_types(),
+            _ => &[],
+        }
+    }
+
+    /// The offset of the jump target of a branch instruction, other than
+    /// the switches, from the address of its opcode.
+    pub fn branch_offset(&self) -> Option<i32> {
+        match self {
+            Instruction::Ifeq(offset) => Some(*offset as i32),
+            Instruction::Ifne(offset) => Some(*offset as i32),
+            Instruction::Iflt(offset) => Some(*offset as i32),
+            Instruction::Ifge(offset) => Some(*offset as i32),
+            Instruction::Ifgt(offset) => Some(*offset as i32),
+            Instruction::Ifle(offset) => Some(*offset as i32),
+            Instruction::IfIcmpeq(offset) => Some(*offset as i32),
+            Instruction::IfIcmpne(offset) => Some(*offset as i32),
+            Instruction::IfIcmplt(offset) => Some(*offset as i32),
+            Instruction::IfIcmpge(offset) => Some(*offset as i32),
+            Instruction::IfIcmpgt(offset) => Some(*offset as i32),
+            Instruction::IfIcmple(offset) => Some(*offset as i32),
+            Instruction::IfAcmpeq(offset) => Some(*offset as i32),
+            Instruction::IfAcmpne(offset) => Some(*offset as i32),
+            Instruction::Goto(offset) => Some(*offset as i32),
+            Instruction::Jsr(offset) => Some(*offset as i32),
+            Instruction::Ifnull(offset) => Some(*offset as i32),
+            Instruction::Ifnonnull(offset) => Some(*offset as i32),
+            Instruction::GotoW(offset) | Instruction::JsrW(offset) => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// The length in bytes of the instruction when it starts at `pc`. Other
+    /// than in `Wide`, the `wide` prefix is used only where an operand does
+    /// not fit without it.
+    pub fn length(&self, pc: usize) -> usize {
+        match self {
+            Instruction::Wide(instruction) => match **instruction {
+                Instruction::Iinc(_, _) => 6,
+                _ => 4,
+            },
+            Instruction::Iload(index)
+            | Instruction::Lload(index)
+            | Instruction::Fload(index)
+            | Instruction::Dload(index)
+            | Instruction::Aload(index)
+            | Instruction::Istore(index)
+            | Instruction::Lstore(index)
+            | Instruction::Fstore(index)
+            | Instruction::Dstore(index)
+            | Instruction::Astore(index)
+            | Instruction::Ret(index) => if *index > 0xff { 4 } else { 2 },
+            Instruction::Iinc(index, increment) =>
+                if *index > 0xff || *increment < i8::MIN as i16 || *increment > i8::MAX as i16 {
+                    6
+                } else {
+                    3
+                },
+            Instruction::Tableswitch { offsets, .. } =>
+                1 + switch_padding(pc) + 12 + 4 * offsets.len(),
+            Instruction::Lookupswitch { pairs, .. } =>
+                1 + switch_padding(pc) + 8 + 8 * pairs.len(),
+            Instruction::Bipush(_) => 2,
+            Instruction::Sipush(_) => 3,
+            Instruction::Ldc(_) => 2,
+            Instruction::LdcW(_)
+            | Instruction::Ldc2W(_)
+            | Instruction::Getstatic(_)
+            | Instruction::Putstatic(_)
+            | Instruction::Getfield(_)
+            | Instruction::Putfield(_)
+            | Instruction::Invokevirtual(_)
+            | Instruction::Invokespecial(_)
+            | Instruction::Invokestatic(_)
+            | Instruction::New(_)
+            | Instruction::Anewarray(_)
+            | Instruction::Checkcast(_)
+            | Instruction::Instanceof(_) => 3,
+            Instruction::Ifeq(_)
+            | Instruction::Ifne(_)
+            | Instruction::Iflt(_)
+            | Instruction::Ifge(_)
+            | Instruction::Ifgt(_)
+            | Instruction::Ifle(_)
+            | Instruction::IfIcmpeq(_)
+            | Instruction::IfIcmpne(_)
+            | Instruction::IfIcmplt(_)
+            | Instruction::IfIcmpge(_)
+            | Instruction::IfIcmpgt(_)
+            | Instruction::IfIcmple(_)
+            | Instruction::IfAcmpeq(_)
+            | Instruction::IfAcmpne(_)
+            | Instruction::Goto(_)
+            | Instruction::Jsr(_)
+            | Instruction::Ifnull(_)
+            | Instruction::Ifnonnull(_) => 3,
+            Instruction::Invokeinterface(_, _) => 5,
+            Instruction::Invokedynamic(_) => 5,
+            Instruction::Newarray(_) => 2,
+            Instruction::Multianewarray(_, _) => 4,
+            Instruction::GotoW(_)
+            | Instruction::JsrW(_) => 5,
+            _ => 1,
+        }
+    }
+
+    /// Appends the encoding of the instruction when it starts at `pc` to
+    /// `code`, writing `length(pc)` bytes.
+    pub fn encode(&self, pc: usize, code: &mut Vec<u8>) {
+        match self {
+            Instruction::Wide(instruction) => {
+                code.extend_from_slice(&[196, instruction.opcode()]);
+                match **instruction {
+                    Instruction::Iinc(index, increment) => {
+                        code.extend_from_slice(&index.to_be_bytes());
+                        code.extend_from_slice(&increment.to_be_bytes());
+                    },
+                    Instruction::Iload(index)
+                    | Instruction::Lload(index)
+                    | Instruction::Fload(index)
+                    | Instruction::Dload(index)
+                    | Instruction::Aload(index)
+                    | Instruction::Istore(index)
+                    | Instruction::Lstore(index)
+                    | Instruction::Fstore(index)
+                    | Instruction::Dstore(index)
+                    | Instruction::Astore(index)
+                    | Instruction::Ret(index) => code.extend_from_slice(&index.to_be_bytes()),
+                    _ => panic!("wide cannot modify {}", instruction.mnemonic()),
+                }
+            },
+            Instruction::Iload(index)
+            | Instruction::Lload(index)
+            | Instruction::Fload(index)
+            | Instruction::Dload(index)
+            | Instruction::Aload(index)
+            | Instruction::Istore(index)
+            | Instruction::Lstore(index)
+            | Instruction::Fstore(index)
+            | Instruction::Dstore(index)
+            | Instruction::Astore(index)
+            | Instruction::Ret(index) =>
+                if *index > 0xff {
+                    code.extend_from_slice(&[196, self.opcode()]);
+                    code.extend_from_slice(&index.to_be_bytes());
+                } else {
+                    code.extend_from_slice(&[self.opcode(), *index as u8]);
+                },
+            Instruction::Iinc(index, increment) =>
+                if self.length(pc) == 6 {
+                    code.extend_from_slice(&[196, 132]);
+                    code.extend_from_slice(&index.to_be_bytes());
+                    code.extend_from_slice(&increment.to_be_bytes());
+                } else {
+                    code.extend_from_slice(&[132, *index as u8, *increment as i8 as u8]);
+                },
+            Instruction::Tableswitch { default, low, high, offsets } => {
+                code.push(170);
+                code.resize(code.len() + switch_padding(pc), 0);
+                for value in [*default, *low, *high].iter().chain(offsets) {
+                    code.extend_from_slice(&value.to_be_bytes());
+                }
+            },
+            Instruction::Lookupswitch { default, pairs } => {
+                code.push(171);
+                code.resize(code.len() + switch_padding(pc), 0);
+                code.extend_from_slice(&default.to_be_bytes());
+                code.extend_from_slice(&(pairs.len() as i32).to_be_bytes());
+                for (key, offset) in pairs {
+                    code.extend_from_slice(&key.to_be_bytes());
+                    code.extend_from_slice(&offset.to_be_bytes());
+                }
+            },
+            Instruction::Bipush(x) => code.extend_from_slice(&[16, *x as u8]),
+            Instruction::Sipush(x) => {
+                code.push(17);
+                code.extend_from_slice(&x.to_be_bytes());
+            },
+            Instruction::Ldc(x) => code.extend_from_slice(&[18, *x]),
+            Instruction::Newarray(x) => code.extend_from_slice(&[188, *x]),
+            Instruction::LdcW(x)
+            | Instruction::Ldc2W(x)
+            | Instruction::Getstatic(x)
+            | Instruction::Putstatic(x)
+            | Instruction::Getfield(x)
+            | Instruction::Putfield(x)
+            | Instruction::Invokevirtual(x)
+            | Instruction::Invokespecial(x)
+            | Instruction::Invokestatic(x)
+            | Instruction::New(x)
+            | Instruction::Anewarray(x)
+            | Instruction::Checkcast(x)
+            | Instruction::Instanceof(x) => {
+                code.push(self.opcode());
+                code.extend_from_slice(&x.to_be_bytes());
+            },
+            Instruction::Invokeinterface(index, count) => {
+                code.push(185);
+                code.extend_from_slice(&index.to_be_bytes());
+                code.extend_from_slice(&[*count, 0]);
+            },
+            Instruction::Invokedynamic(index) => {
+                code.push(186);
+                code.extend_from_slice(&index.to_be_bytes());
+                code.extend_from_slice(&[0, 0]);
+            },
+            Instruction::Multianewarray(index, dimensions) => {
+                code.push(197);
+                code.extend_from_slice(&index.to_be_bytes());
+                code.push(*dimensions);
+            },
+            Instruction::GotoW(offset) | Instruction::JsrW(offset) => {
+                code.push(self.opcode());
+                code.extend_from_slice(&offset.to_be_bytes());
+            },
+            _ => match self.branch_offset() {
+                Some(offset) => {
+                    code.push(self.opcode());
+                    code.extend_from_slice(&(offset as i16).to_be_bytes());
+                },
+                None => code.push(self.opcode()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        match self {
+            Instruction::Iload(x)
+            | Instruction::Lload(x)
+            | Instruction::Fload(x)
+            | Instruction::Dload(x)
+            | Instruction::Aload(x)
+            | Instruction::Istore(x)
+            | Instruction::Lstore(x)
+            | Instruction::Fstore(x)
+            | Instruction::Dstore(x)
+            | Instruction::Astore(x)
+            | Instruction::Ret(x)
+            | Instruction::LdcW(x)
+            | Instruction::Ldc2W(x)
+            | Instruction::Getstatic(x)
+            | Instruction::Putstatic(x)
+            | Instruction::Getfield(x)
+            | Instruction::Putfield(x)
+            | Instruction::Invokevirtual(x)
+            | Instruction::Invokespecial(x)
+            | Instruction::Invokestatic(x)
+            | Instruction::Invokedynamic(x)
+            | Instruction::New(x)
+            | Instruction::Anewarray(x)
+            | Instruction::Checkcast(x)
+            | Instruction::Instanceof(x) => write!(f, " {}", x),
+            Instruction::Ldc(x) | Instruction::Newarray(x) => write!(f, " {}", x),
+            Instruction::Bipush(x) => write!(f, " {}", x),
+            Instruction::Sipush(x) => write!(f, " {}", x),
+            Instruction::Iinc(index, increment) => write!(f, " {}, {}", index, increment),
+            Instruction::Invokeinterface(index, count) => write!(f, " {}, {}", index, count),
+            Instruction::Multianewarray(index, dimensions) => write!(f, " {}, {}", index, dimensions),
+            Instruction::Wide(instruction) => write!(f, " {}", instruction),
+            Instruction::Tableswitch { default, low, offsets, .. } => {
+                for (i, offset) in offsets.iter().enumerate() {
+                    write!(f, " {}: {:+}", *low as i64 + i as i64, offset)?;
+                }
+                write!(f, " default: {:+}", default)
+            },
+            Instruction::Lookupswitch { default, pairs } => {
+                for (key, offset) in pairs {
+                    write!(f, " {}: {:+}", key, offset)?;
+                }
+                write!(f, " default: {:+}", default)
+            },
+            _ => match self.branch_offset() {
+                Some(offset) => write!(f, " {:+}", offset),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // lookupswitch at 1, after two bytes of padding
+    fn lookupswitch(npairs: i32, keys: &[i32]) -> Vec<u8> {
+        let mut code = vec![0x00, 171, 0, 0];
+        code.extend_from_slice(&20i32.to_be_bytes());
+        code.extend_from_slice(&npairs.to_be_bytes());
+        for key in keys {
+            code.extend_from_slice(&key.to_be_bytes());
+            code.extend_from_slice(&30i32.to_be_bytes());
+        }
+        code
+    }
+
+    #[test]
+    fn decodes_lookupswitch() {
+        let code = lookupswitch(2, &[-1, 5]);
+        let (instruction, length) = decode(&code, 1).unwrap();
+        assert_eq!(1 + length, code.len());
+        assert!(matches!(&instruction,
+            Instruction::Lookupswitch { default: 20, pairs } if pairs == &[(-1, 30), (5, 30)]));
+        let mut encoded = vec![0x00];
+        instruction.encode(1, &mut encoded);
+        assert_eq!(encoded, code);
+    }
+
+    #[test]
+    fn rejects_bad_lookupswitch() {
+        assert!(matches!(decode(&lookupswitch(-1, &[]), 1), Err(DecodeError::BadSwitchPairs { pc: 1 })));
+        assert!(matches!(decode(&lookupswitch(2, &[5, -1]), 1), Err(DecodeError::BadSwitchPairs { pc: 1 })));
+        assert!(matches!(decode(&lookupswitch(2, &[5, 5]), 1), Err(DecodeError::BadSwitchPairs { pc: 1 })));
+        assert!(matches!(decode(&lookupswitch(3, &[1, 2]), 1), Err(DecodeError::UnexpectedEnd { pc: 1 })));
+    }
+}
@@ -0,0 +1,14 @@
+pub mod assembler;
+pub mod borrowed_class;
+pub mod class;
+pub mod constant_pool;
+pub mod descriptor;
+pub mod frame;
+pub mod instruction;
+pub mod mutf8;
+pub mod operation;
+pub mod read_class;
+pub mod signature;
+pub mod value;
+pub mod write_class;
+mod utils;
@@ -1,13 +1,18 @@
-mod read_class;
-mod class;
-mod utils;
-mod operation;
+use vm::operation;
+use vm::read_class;
 
 use std::env;
+use std::process;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
-    let bytecode = read_class::parse(filename.to_string());
-    operation::execute(bytecode);
+    let bytecode = match read_class::parse(filename.to_string()) {
+        Ok(bytecode) => bytecode,
+        Err(e) => {
+            eprintln!("Error: could not load {}: {}", filename, e);
+            process::exit(1);
+        },
+    };
+    process::exit(operation::execute(bytecode));
 }
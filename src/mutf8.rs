@@ -0,0 +1,136 @@
+//! The modified UTF-8 encoding used by `CONSTANT_Utf8` entries (JVMS 4.4.7).
+//!
+//! It differs from standard UTF-8 in two ways: the NUL character is encoded
+//! as the two bytes `C0 80`, so no encoded string contains a zero byte, and
+//! characters outside the Basic Multilingual Plane are encoded as their
+//! UTF-16 surrogate pair, each surrogate taking three bytes (CESU-8).
+
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub struct Mutf8Error {
+    /// Position of the first byte of the malformed sequence
+    pub position: usize,
+}
+
+impl fmt::Display for Mutf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid modified UTF-8 at byte {}", self.position)
+    }
+}
+
+/// Decodes modified UTF-8 into a `String`.
+///
+/// Java strings may contain unpaired surrogates, which a `String` cannot.
+/// Those are decoded as U+FFFD REPLACEMENT CHARACTER.
+pub fn decode(bytes: &[u8]) -> Result<String, Mutf8Error> {
+    let mut units: Vec<u16> = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+    while position < bytes.len() {
+        let continuation = |offset: usize| match bytes.get(position + offset) {
+            Some(byte) if byte & 0xc0 == 0x80 => Ok((byte & 0x3f) as u16),
+            _ => Err(Mutf8Error { position }),
+        };
+        let byte = bytes[position];
+        match byte {
+            0x01..=0x7f => {
+                units.push(byte as u16);
+                position += 1;
+            },
+            0xc0..=0xdf => {
+                units.push(((byte & 0x1f) as u16) << 6 | continuation(1)?);
+                position += 2;
+            },
+            0xe0..=0xef => {
+                units.push(((byte & 0x0f) as u16) << 12 | continuation(1)? << 6 | continuation(2)?);
+                position += 3;
+            },
+            // Zero bytes and the lead bytes of four-byte UTF-8 never appear
+            _ => return Err(Mutf8Error { position }),
+        }
+    }
+    Ok(std::char::decode_utf16(units)
+        .map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER))
+        .collect())
+}
+
+/// Decodes modified UTF-8, borrowing `bytes` when they mean the same thing
+/// as standard UTF-8, which is the case for any string without NUL or
+/// supplementary characters.
+pub fn decode_borrowed(bytes: &[u8]) -> Result<Cow<'_, str>, Mutf8Error> {
+    // Standard UTF-8 allows these bytes, modified UTF-8 does not
+    if !bytes.iter().any(|&byte| byte == 0 || byte >= 0xf0) {
+        if let Ok(s) = std::str::from_utf8(bytes) {
+            return Ok(Cow::Borrowed(s));
+        }
+    }
+    decode(bytes).map(Cow::Owned)
+}
+
+/// Encodes a `str` as modified UTF-8.
+pub fn encode(s: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(s.len());
+    for unit in s.encode_utf16() {
+        match unit {
+            0x0001..=0x007f => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07ff => {
+                bytes.push(0xc0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            },
+            _ => {
+                bytes.push(0xe0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            },
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_nul_as_two_bytes() {
+        assert_eq!(encode("a\0b"), vec![b'a', 0xc0, 0x80, b'b']);
+        assert_eq!(decode(&[b'a', 0xc0, 0x80, b'b']).unwrap(), "a\0b");
+        assert!(matches!(decode_borrowed(&[0xc0, 0x80]), Ok(Cow::Owned(s)) if s == "\0"));
+    }
+
+    #[test]
+    fn encodes_supplementary_characters_as_surrogate_pairs() {
+        let bytes = vec![0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80];
+        assert_eq!(encode("\u{1f600}"), bytes);
+        assert_eq!(decode(&bytes).unwrap(), "\u{1f600}");
+        assert!(matches!(decode_borrowed(&bytes), Ok(Cow::Owned(s)) if s == "\u{1f600}"));
+    }
+
+    #[test]
+    fn replaces_unpaired_surrogates() {
+        assert_eq!(decode(&[0xed, 0xa0, 0x80]).unwrap(), "\u{fffd}");
+        assert_eq!(decode(&[b'a', 0xed, 0xb0, 0x80, b'b']).unwrap(), "a\u{fffd}b");
+        // A low surrogate before a high one is not a pair
+        assert_eq!(decode(&[0xed, 0xb8, 0x80, 0xed, 0xa0, 0xbd]).unwrap(), "\u{fffd}\u{fffd}");
+    }
+
+    #[test]
+    fn borrows_standard_utf8() {
+        let s = "h\u{e9}llo \u{4e16}\u{754c}";
+        assert_eq!(encode(s), s.as_bytes());
+        assert!(matches!(decode_borrowed(s.as_bytes()), Ok(Cow::Borrowed(borrowed)) if borrowed == s));
+        assert_eq!(decode(s.as_bytes()).unwrap(), s);
+    }
+
+    #[test]
+    fn reports_malformed_bytes() {
+        assert_eq!(decode(&[b'a', 0]), Err(Mutf8Error { position: 1 }));
+        assert_eq!(decode(&[b'a', 0xc3]), Err(Mutf8Error { position: 1 }));
+        assert_eq!(decode(&[0xe4, 0xb8, b'a']), Err(Mutf8Error { position: 0 }));
+        assert_eq!(decode(&[b'a', b'b', 0x80]), Err(Mutf8Error { position: 2 }));
+        // Standard UTF-8 for U+1F600, which modified UTF-8 spells differently
+        assert_eq!(decode_borrowed("\u{1f600}".as_bytes()), Err(Mutf8Error { position: 0 }));
+        assert_eq!(decode_borrowed(&[b'a', 0]), Err(Mutf8Error { position: 1 }));
+    }
+}
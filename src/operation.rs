@@ -1,175 +1,1037 @@
 use crate::class::BytecodeClass;
 use crate::class::ConstantPoolEnum;
-use std::process::exit;
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::MemberRefIndex;
+use crate::descriptor::MethodDescriptor;
+use crate::frame::Frame;
+use crate::instruction;
+use crate::instruction::Instruction;
+use crate::utils::format_double;
+use crate::utils::format_float;
+use crate::value::Object;
+use crate::value::Stream;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::rc::Rc;
 use log::info;
 
-struct Envt {
-  pc: usize,
-  stack: Vec<String>,
-  // frame
+struct Envt<'a> {
+  frames: Vec<Frame<'a>>,
+  /// Set by `System.exit`, which also discards every frame
+  exit_status: Option<i32>,
 }
 
-struct Op {
-  pub opcode: u8,
-  pub operands: Vec<u8>,
+/// Runs the `main` method of `class`, returning the process exit status.
+pub fn execute(class: BytecodeClass) -> i32 {
+  let mut main_frame = Frame::new(&class, class.get_main_method());
+  // String[] args
+  main_frame.store(0, Value::null());
+  let mut envt = Envt {
+    frames: vec![main_frame],
+    exit_status: None,
+  };
+  execute_code(&mut envt)
 }
 
-pub fn execute(class: BytecodeClass) {
-  execute_code(class.get_main_method().get_code(), &class.constant_pool);
+fn execute_code(envt: &mut Envt) -> i32 {
+  while let Some(frame) = envt.frames.last_mut() {
+    let code = frame.code();
+    let pc = frame.pc;
+    let (instruction, length) = match instruction::decode(code, pc) {
+      Ok(decoded) => decoded,
+      Err(e) => panic!("{} in {}", e, frame.method.name),
+    };
+    frame.instruction_pc = pc;
+    frame.pc = pc + length;
+    info!("pc: {}", pc);
+    info!("instruction: {}", instruction);
+    if let Err(exception) = execute_instruction(pc, instruction, envt) {
+      eprintln!("Exception in thread \"main\" {}", exception);
+      print_stack_trace(&envt.frames);
+      return 1;
+    }
+  }
+  envt.exit_status.unwrap_or(0)
 }
 
-fn execute_code(code: &Vec<u8>, constant_pool: &Vec<ConstantPoolEnum>) {
-  let mut envt = Envt {
-    pc: 0,
-    stack: vec![],
-  };
-  info!("At start, pc: {}", envt.pc);
-  while envt.pc < code.len() {
-    let op: Op = read_operation(code, &mut envt);
-    info!("pc: {}", envt.pc);
-    info!("opcode: {}", op.opcode);
-    info!("Number of operands: {}", op.operands.len());
-    execute_operation(op, constant_pool, &mut envt);
-  }
-}
-
-fn execute_operation(op: Op, constant_pool: &Vec<ConstantPoolEnum>, envt: &mut Envt) {
-  match op.opcode {
-    // ldc, 1 operand
-    18 => {
-      info!("ldc");
-      let index: u8 = op.operands[0];
-      let constant_pool_index: &ConstantPoolEnum = &constant_pool[(index - 1) as usize];
-      let constant_pool_index_string_index = match constant_pool_index {
-        ConstantPoolEnum::ConstantStringItem(x) => x.string_index,
-        _ => panic!("Something went wrong!"),
-      };
-      let constant_pool_index_utf8: &ConstantPoolEnum = 
-        &constant_pool[(constant_pool_index_string_index - 1) as usize];
-      // TODO envt.stack is not a Vec of Strings
-      let name: String = match constant_pool_index_utf8 {
-          ConstantPoolEnum::ConstantUtf8Item(x) => x.bytes.clone(),
-          _ => panic!("Something went wrong!"),
-      };
-      info!("name: {}", name);
-      envt.stack.push(name);
-    },
-    // getstatic, 2 operands
-    178 => {
+fn print_stack_trace(frames: &[Frame]) {
+  for frame in frames.iter().rev() {
+    let location = match (frame.class.get_source_file(), frame.method.get_line_number(frame.instruction_pc)) {
+      (Some(source_file), Some(line_number)) => format!("{}:{}", source_file, line_number),
+      (Some(source_file), None) => source_file.to_string(),
+      (None, _) => String::from("Unknown Source"),
+    };
+    eprintln!("\tat {}.{}({})", frame.class.this_class_name.replace('/', "."), frame.method.name, location);
+  }
+}
+
+// `pc` is the address of the instruction, which branch offsets are relative to
+fn execute_instruction(pc: usize, instruction: Instruction, envt: &mut Envt) -> Result<(), Rc<Object>> {
+  let frame = envt.frames.last_mut().unwrap();
+  let class = frame.class;
+  let constant_pool = &class.constant_pool;
+  match instruction {
+    Instruction::Ldc(index) => frame.push(load_constant(constant_pool, index as u16)),
+    Instruction::LdcW(index) | Instruction::Ldc2W(index) =>
+      frame.push(load_constant(constant_pool, index)),
+    Instruction::Getstatic(index) => {
       info!("getstatic");
-      let cp_index = as_u2(op.operands[0], op.operands[1]);
-      info!("cp_index: {}", cp_index);
-      let symbol_name_index: &ConstantPoolEnum = &constant_pool[(cp_index - 1) as usize];
-      let symbol_name_index_class_index = match symbol_name_index {
-        ConstantPoolEnum::ConstantFieldrefItem(x) => x.class_index,
-        ConstantPoolEnum::ConstantMethodrefItem(x) => x.class_index,
-        _ => panic!("Something went wrong!"),
-      };
-      let constant_pool_symbol_name_index_class_index: &ConstantPoolEnum = 
-        &constant_pool[(symbol_name_index_class_index - 1) as usize];
-      let cls_name_index = match constant_pool_symbol_name_index_class_index {
-        ConstantPoolEnum::ConstantClassItem(x) => x.name_index,
-        ConstantPoolEnum::ConstantNameAndTypeItem(x) => x.name_index,
-        _ => panic!("Something went wrong!"),
-      };
-      let cls_name_index_utf8: &ConstantPoolEnum = &constant_pool[(cls_name_index - 1) as usize];
-      let cls: String = match cls_name_index_utf8 {
-          ConstantPoolEnum::ConstantUtf8Item(x) => x.bytes.clone(),
-          _ => panic!("Something went wrong!"),
-      };
+      info!("cp_index: {}", index);
+      let (cls, field, _) = member_ref(constant_pool, index);
       info!("cls: {}", cls);
-      // TODO Second time this check has been performed
-      let symbol_name_index_name_and_type_index = match symbol_name_index {
-        ConstantPoolEnum::ConstantFieldrefItem(x) => x.name_and_type_index,
-        _ => panic!("Something went wrong!"),
-      };
-      let constant_pool_symbol_name_index_name_and_type_index: &ConstantPoolEnum =
-        &constant_pool[(symbol_name_index_name_and_type_index - 1) as usize];
-      let field_name_index = match constant_pool_symbol_name_index_name_and_type_index {
-        ConstantPoolEnum::ConstantClassItem(x) => x.name_index,
-        ConstantPoolEnum::ConstantNameAndTypeItem(x) => x.name_index,
-        _ => panic!("Something went wrong!"),
-      };
-      let field_name_index_utf8: &ConstantPoolEnum = &constant_pool[(field_name_index - 1) as usize];
-      let field: String = match field_name_index_utf8 {
-          ConstantPoolEnum::ConstantUtf8Item(x) => x.bytes.clone(),
-          _ => panic!("Something went wrong!"),
-      };
       info!("field: {}", field);
-      let name = format!("{}.{}", cls, field);
-      info!("name: {}", name);
-      envt.stack.push(name);
+      let value = match (cls, field) {
+        ("java/lang/System", "out") => Value::object(Object::PrintStream(Stream::Out)),
+        ("java/lang/System", "err") => Value::object(Object::PrintStream(Stream::Err)),
+        _ => panic!("Not implemented: getstatic {}.{}", cls, field),
+      };
+      frame.push(value);
       },
-    // invokevirtual, 2 operands
-    182 => {
+    Instruction::Invokevirtual(index) => {
       info!("invokevirtual");
-      let index = as_u2(op.operands[0], op.operands[1]);
-      let constant_pool_index: &ConstantPoolEnum = &constant_pool[(index - 1) as usize];
-      let constant_pool_index_name_and_type_index: u16 = match constant_pool_index {
-        ConstantPoolEnum::ConstantFieldrefItem(x) => x.name_and_type_index,
-        ConstantPoolEnum::ConstantMethodrefItem(x) => x.name_and_type_index,
-        _ => panic!("Something went wrong!"),
-      }; 
-      let callee: &ConstantPoolEnum =
-        &constant_pool[(constant_pool_index_name_and_type_index - 1) as usize];
-      let callee_name_index = match callee {
-        ConstantPoolEnum::ConstantClassItem(x) => x.name_index,
-        ConstantPoolEnum::ConstantNameAndTypeItem(x) => x.name_index,
-        _ => panic!("Something went wrong!"),
-      }; 
-      let method_name_utf8: &ConstantPoolEnum =
-        &constant_pool[(callee_name_index - 1) as usize];
-      let method_name: String = match method_name_utf8 {
-          ConstantPoolEnum::ConstantUtf8Item(x) => x.bytes.clone(),
-          _ => panic!("Something went wrong!"),
-      };
-      if method_name != "println" {
-        panic!("Not implemented: {}", method_name);
+      let (class_name, method_name, descriptor) = member_ref(constant_pool, index);
+      info!("method: {}.{}{}", class_name, method_name, descriptor);
+      match (class_name, method_name) {
+        ("java/io/PrintStream", "println") | ("java/io/PrintStream", "print") =>
+          print(frame, method_name, descriptor)?,
+        ("java/lang/String", "hashCode") => {
+          let hash = match receiver_object(frame.pop())?.as_ref() {
+            Object::String(s) => s.encode_utf16()
+              .fold(0i32, |hash, c| hash.wrapping_mul(31).wrapping_add(c as i32)),
+            other => panic!("hashCode called on {}", other),
+          };
+          frame.push(Value::Int(hash));
+        },
+        ("java/lang/String", "equals") => {
+          let other = frame.pop();
+          let equal = match (receiver_object(frame.pop())?.as_ref(), other.as_reference().as_deref()) {
+            (Object::String(s1), Some(Object::String(s2))) => s1 == s2,
+            _ => false,
+          };
+          frame.push(Value::Int(equal as i32));
+        },
+        _ => panic!("Not implemented: {}.{}{}", class_name, method_name, descriptor),
       }
-      let args = envt.stack.pop();
-      let object_name = envt.stack.pop();
-      println!("{:?}", args.unwrap());
-      info!("object_name: {:?}", object_name);
       },
-    // return, 0 operands
-    // TODO Replace with return implementation
-    177 => exit(1),
-    // iconst_3, 0 operands
-    6 => panic!("iconst_3 not implemented"),
-    // istore, 1 operand
-    54 => panic!("istore not implemented"),
-    // iload, 1 operand
-    21 =>  panic!("iload not implemented"),
-    // ireturn, 0 operands
-    172 =>  panic!("ireturn not implemented"),
-    _ => panic!("Operation not implemented"),
-  }
-}
-
-fn read_operation(code: &Vec<u8>, envt: &mut Envt) -> Op {
-  let pc = envt.pc;
-  let opcode = code[pc];
-  let arity = match opcode {
-    18 => 1,
-    178 => 2,
-    182 => 2,
-    177 => 0,
-    _ => panic!("Operation not implemented"),
+    Instruction::Invokestatic(index) => {
+      info!("invokestatic");
+      let (class_name, method_name, descriptor) = member_ref(constant_pool, index);
+      info!("method: {}.{}{}", class_name, method_name, descriptor);
+      if class_name == "java/lang/System" && method_name == "exit" {
+        envt.exit_status = Some(frame.pop().as_int());
+        envt.frames.clear();
+        return Ok(());
+      }
+      if class_name != class.this_class_name {
+        panic!("Not implemented: {}.{}{}", class_name, method_name, descriptor);
+      }
+      let method = match class.get_method(method_name, descriptor) {
+        Some(method) => method,
+        None => panic!("No method {}{} in {}", method_name, descriptor, class_name),
+      };
+      let parameters = match MethodDescriptor::parse(descriptor) {
+        Ok(method_descriptor) => method_descriptor.parameters,
+        Err(e) => panic!("{}", e),
+      };
+      // Each argument is one value on the operand stack, whatever its category
+      let mut args: Vec<Value> = parameters.iter()
+        .map(|_| frame.pop())
+        .collect();
+      args.reverse();
+      let mut callee = Frame::new(class, method);
+      let mut local = 0;
+      for arg in args {
+        let category = arg.category();
+        callee.store(local, arg);
+        local += category;
+      }
+      envt.frames.push(callee);
+    },
+    Instruction::Ireturn
+    | Instruction::Lreturn
+    | Instruction::Freturn
+    | Instruction::Dreturn
+    | Instruction::Areturn => {
+      let value = frame.pop();
+      envt.frames.pop();
+      if let Some(caller) = envt.frames.last_mut() {
+        caller.push(value);
+      }
+    },
+    Instruction::Return => {
+      envt.frames.pop();
+    },
+    Instruction::Nop => {},
+    Instruction::AconstNull => frame.push(Value::null()),
+    Instruction::IconstM1 => frame.push(Value::Int(-1)),
+    Instruction::Iconst0 => frame.push(Value::Int(0)),
+    Instruction::Iconst1 => frame.push(Value::Int(1)),
+    Instruction::Iconst2 => frame.push(Value::Int(2)),
+    Instruction::Iconst3 => frame.push(Value::Int(3)),
+    Instruction::Iconst4 => frame.push(Value::Int(4)),
+    Instruction::Iconst5 => frame.push(Value::Int(5)),
+    Instruction::Lconst0 => frame.push(Value::Long(0)),
+    Instruction::Lconst1 => frame.push(Value::Long(1)),
+    Instruction::Fconst0 => frame.push(Value::Float(0.0)),
+    Instruction::Fconst1 => frame.push(Value::Float(1.0)),
+    Instruction::Fconst2 => frame.push(Value::Float(2.0)),
+    Instruction::Dconst0 => frame.push(Value::Double(0.0)),
+    Instruction::Dconst1 => frame.push(Value::Double(1.0)),
+    Instruction::Bipush(value) => frame.push(Value::Int(value as i32)),
+    Instruction::Sipush(value) => frame.push(Value::Int(value as i32)),
+    Instruction::Iload(index)
+    | Instruction::Lload(index)
+    | Instruction::Fload(index)
+    | Instruction::Dload(index)
+    | Instruction::Aload(index) => {
+      let value = frame.load(index as usize);
+      frame.push(value);
+    },
+    Instruction::Iload0 | Instruction::Lload0 | Instruction::Fload0 | Instruction::Dload0 | Instruction::Aload0 => {
+      let value = frame.load(0);
+      frame.push(value);
+    },
+    Instruction::Iload1 | Instruction::Lload1 | Instruction::Fload1 | Instruction::Dload1 | Instruction::Aload1 => {
+      let value = frame.load(1);
+      frame.push(value);
+    },
+    Instruction::Iload2 | Instruction::Lload2 | Instruction::Fload2 | Instruction::Dload2 | Instruction::Aload2 => {
+      let value = frame.load(2);
+      frame.push(value);
+    },
+    Instruction::Iload3 | Instruction::Lload3 | Instruction::Fload3 | Instruction::Dload3 | Instruction::Aload3 => {
+      let value = frame.load(3);
+      frame.push(value);
+    },
+    Instruction::Istore(index)
+    | Instruction::Lstore(index)
+    | Instruction::Fstore(index)
+    | Instruction::Dstore(index)
+    | Instruction::Astore(index) => {
+      let value = frame.pop();
+      frame.store(index as usize, value);
+    },
+    Instruction::Istore0 | Instruction::Lstore0 | Instruction::Fstore0 | Instruction::Dstore0 | Instruction::Astore0 => {
+      let value = frame.pop();
+      frame.store(0, value);
+    },
+    Instruction::Istore1 | Instruction::Lstore1 | Instruction::Fstore1 | Instruction::Dstore1 | Instruction::Astore1 => {
+      let value = frame.pop();
+      frame.store(1, value);
+    },
+    Instruction::Istore2 | Instruction::Lstore2 | Instruction::Fstore2 | Instruction::Dstore2 | Instruction::Astore2 => {
+      let value = frame.pop();
+      frame.store(2, value);
+    },
+    Instruction::Istore3 | Instruction::Lstore3 | Instruction::Fstore3 | Instruction::Dstore3 | Instruction::Astore3 => {
+      let value = frame.pop();
+      frame.store(3, value);
+    },
+    Instruction::Pop => {
+      frame.pop();
+    },
+    Instruction::Pop2 => {
+      pop_units(frame, 2);
+    },
+    Instruction::Dup => duplicate(frame, 1, 0),
+    Instruction::DupX1 => duplicate(frame, 1, 1),
+    Instruction::DupX2 => duplicate(frame, 1, 2),
+    Instruction::Dup2 => duplicate(frame, 2, 0),
+    Instruction::Dup2X1 => duplicate(frame, 2, 1),
+    Instruction::Dup2X2 => duplicate(frame, 2, 2),
+    Instruction::Swap => {
+      let value1 = frame.pop();
+      let value2 = frame.pop();
+      frame.push(value1);
+      frame.push(value2);
+    },
+    Instruction::Iadd => int_binary_operation(frame, |a, b| a.wrapping_add(b)),
+    Instruction::Ladd => long_binary_operation(frame, |a, b| a.wrapping_add(b)),
+    Instruction::Fadd => float_binary_operation(frame, |a, b| a + b),
+    Instruction::Dadd => double_binary_operation(frame, |a, b| a + b),
+    Instruction::Isub => int_binary_operation(frame, |a, b| a.wrapping_sub(b)),
+    Instruction::Lsub => long_binary_operation(frame, |a, b| a.wrapping_sub(b)),
+    Instruction::Fsub => float_binary_operation(frame, |a, b| a - b),
+    Instruction::Dsub => double_binary_operation(frame, |a, b| a - b),
+    Instruction::Imul => int_binary_operation(frame, |a, b| a.wrapping_mul(b)),
+    Instruction::Lmul => long_binary_operation(frame, |a, b| a.wrapping_mul(b)),
+    Instruction::Fmul => float_binary_operation(frame, |a, b| a * b),
+    Instruction::Dmul => double_binary_operation(frame, |a, b| a * b),
+    Instruction::Idiv => {
+      if frame.peek().as_int() == 0 {
+        return Err(exception("java/lang/ArithmeticException", "/ by zero"));
+      }
+      int_binary_operation(frame, |a, b| a.wrapping_div(b));
+    },
+    Instruction::Ldiv => {
+      if frame.peek().as_long() == 0 {
+        return Err(exception("java/lang/ArithmeticException", "/ by zero"));
+      }
+      long_binary_operation(frame, |a, b| a.wrapping_div(b));
+    },
+    Instruction::Fdiv => float_binary_operation(frame, |a, b| a / b),
+    Instruction::Ddiv => double_binary_operation(frame, |a, b| a / b),
+    Instruction::Irem => {
+      if frame.peek().as_int() == 0 {
+        return Err(exception("java/lang/ArithmeticException", "/ by zero"));
+      }
+      int_binary_operation(frame, |a, b| a.wrapping_rem(b));
+    },
+    Instruction::Lrem => {
+      if frame.peek().as_long() == 0 {
+        return Err(exception("java/lang/ArithmeticException", "/ by zero"));
+      }
+      long_binary_operation(frame, |a, b| a.wrapping_rem(b));
+    },
+    Instruction::Frem => float_binary_operation(frame, |a, b| a % b),
+    Instruction::Drem => double_binary_operation(frame, |a, b| a % b),
+    Instruction::Ineg => {
+      let value = frame.pop().as_int();
+      frame.push(Value::Int(value.wrapping_neg()));
+    },
+    Instruction::Lneg => {
+      let value = frame.pop().as_long();
+      frame.push(Value::Long(value.wrapping_neg()));
+    },
+    Instruction::Fneg => {
+      let value = frame.pop().as_float();
+      frame.push(Value::Float(-value));
+    },
+    Instruction::Dneg => {
+      let value = frame.pop().as_double();
+      frame.push(Value::Double(-value));
+    },
+    Instruction::Ishl => int_binary_operation(frame, |a, b| a.wrapping_shl(b as u32)),
+    Instruction::Lshl => long_shift_operation(frame, |a, b| a.wrapping_shl(b as u32)),
+    Instruction::Ishr => int_binary_operation(frame, |a, b| a.wrapping_shr(b as u32)),
+    Instruction::Lshr => long_shift_operation(frame, |a, b| a.wrapping_shr(b as u32)),
+    Instruction::Iushr => int_binary_operation(frame, |a, b| (a as u32).wrapping_shr(b as u32) as i32),
+    Instruction::Lushr => long_shift_operation(frame, |a, b| (a as u64).wrapping_shr(b as u32) as i64),
+    Instruction::Iand => int_binary_operation(frame, |a, b| a & b),
+    Instruction::Land => long_binary_operation(frame, |a, b| a & b),
+    Instruction::Ior => int_binary_operation(frame, |a, b| a | b),
+    Instruction::Lor => long_binary_operation(frame, |a, b| a | b),
+    Instruction::Ixor => int_binary_operation(frame, |a, b| a ^ b),
+    Instruction::Lxor => long_binary_operation(frame, |a, b| a ^ b),
+    Instruction::Iinc(index, increment) => {
+      let value = frame.load(index as usize).as_int();
+      frame.store(index as usize, Value::Int(value.wrapping_add(increment as i32)));
+    },
+    // The prefix only widens the operands, which are already decoded
+    Instruction::Wide(instruction) => return execute_instruction(pc, *instruction, envt),
+    Instruction::I2l => {
+      let value = frame.pop().as_int();
+      frame.push(Value::Long(value as i64));
+    },
+    Instruction::I2f => {
+      let value = frame.pop().as_int();
+      frame.push(Value::Float(value as f32));
+    },
+    Instruction::I2d => {
+      let value = frame.pop().as_int();
+      frame.push(Value::Double(value as f64));
+    },
+    Instruction::L2i => {
+      let value = frame.pop().as_long();
+      frame.push(Value::Int(value as i32));
+    },
+    Instruction::L2f => {
+      let value = frame.pop().as_long();
+      frame.push(Value::Float(value as f32));
+    },
+    Instruction::L2d => {
+      let value = frame.pop().as_long();
+      frame.push(Value::Double(value as f64));
+    },
+    // Rust's float to integer casts saturate and send NaN to 0, as Java's do
+    Instruction::F2i => {
+      let value = frame.pop().as_float();
+      frame.push(Value::Int(value as i32));
+    },
+    Instruction::F2l => {
+      let value = frame.pop().as_float();
+      frame.push(Value::Long(value as i64));
+    },
+    Instruction::F2d => {
+      let value = frame.pop().as_float();
+      frame.push(Value::Double(value as f64));
+    },
+    Instruction::D2i => {
+      let value = frame.pop().as_double();
+      frame.push(Value::Int(value as i32));
+    },
+    Instruction::D2l => {
+      let value = frame.pop().as_double();
+      frame.push(Value::Long(value as i64));
+    },
+    Instruction::D2f => {
+      let value = frame.pop().as_double();
+      frame.push(Value::Float(value as f32));
+    },
+    Instruction::I2b => {
+      let value = frame.pop().as_int();
+      frame.push(Value::Int(value as i8 as i32));
+    },
+    Instruction::I2c => {
+      let value = frame.pop().as_int();
+      frame.push(Value::Int(value as u16 as i32));
+    },
+    Instruction::I2s => {
+      let value = frame.pop().as_int();
+      frame.push(Value::Int(value as i16 as i32));
+    },
+    Instruction::Lcmp => {
+      let value2 = frame.pop().as_long();
+      let value1 = frame.pop().as_long();
+      frame.push(Value::Int(value1.cmp(&value2) as i32));
+    },
+    Instruction::Fcmpl | Instruction::Fcmpg => {
+      let value2 = frame.pop().as_float();
+      let value1 = frame.pop().as_float();
+      let nan_greater = instruction == Instruction::Fcmpg;
+      frame.push(Value::Int(compare_floating(value1.partial_cmp(&value2), nan_greater)));
+    },
+    Instruction::Dcmpl | Instruction::Dcmpg => {
+      let value2 = frame.pop().as_double();
+      let value1 = frame.pop().as_double();
+      let nan_greater = instruction == Instruction::Dcmpg;
+      frame.push(Value::Int(compare_floating(value1.partial_cmp(&value2), nan_greater)));
+    },
+    Instruction::Ifeq(offset) => int_branch(frame, pc, offset, |value| value == 0),
+    Instruction::Ifne(offset) => int_branch(frame, pc, offset, |value| value != 0),
+    Instruction::Iflt(offset) => int_branch(frame, pc, offset, |value| value < 0),
+    Instruction::Ifge(offset) => int_branch(frame, pc, offset, |value| value >= 0),
+    Instruction::Ifgt(offset) => int_branch(frame, pc, offset, |value| value > 0),
+    Instruction::Ifle(offset) => int_branch(frame, pc, offset, |value| value <= 0),
+    Instruction::IfIcmpeq(offset) => int_compare_branch(frame, pc, offset, |ordering| ordering == Ordering::Equal),
+    Instruction::IfIcmpne(offset) => int_compare_branch(frame, pc, offset, |ordering| ordering != Ordering::Equal),
+    Instruction::IfIcmplt(offset) => int_compare_branch(frame, pc, offset, |ordering| ordering == Ordering::Less),
+    Instruction::IfIcmpge(offset) => int_compare_branch(frame, pc, offset, |ordering| ordering != Ordering::Less),
+    Instruction::IfIcmpgt(offset) => int_compare_branch(frame, pc, offset, |ordering| ordering == Ordering::Greater),
+    Instruction::IfIcmple(offset) => int_compare_branch(frame, pc, offset, |ordering| ordering != Ordering::Greater),
+    Instruction::IfAcmpeq(offset) | Instruction::IfAcmpne(offset) => {
+      let value2 = frame.pop();
+      let value1 = frame.pop();
+      let same = same_reference(value1.as_reference(), value2.as_reference());
+      if same == (instruction == Instruction::IfAcmpeq(offset)) {
+        branch(frame, pc, offset as i32);
+      }
+    },
+    Instruction::Goto(offset) => branch(frame, pc, offset as i32),
+    Instruction::GotoW(offset) => branch(frame, pc, offset),
+    Instruction::Ifnull(offset) | Instruction::Ifnonnull(offset) => {
+      let value = frame.pop();
+      if value.as_reference().is_none() == (instruction == Instruction::Ifnull(offset)) {
+        branch(frame, pc, offset as i32);
+      }
+    },
+    Instruction::Tableswitch { default, low, high, offsets } => {
+      let index = frame.pop().as_int();
+      let offset = if index < low || index > high {
+        default
+      } else {
+        offsets[(index - low) as usize]
+      };
+      branch(frame, pc, offset);
+    },
+    Instruction::Lookupswitch { default, pairs } => {
+      let key = frame.pop().as_int();
+      let offset = pairs.iter()
+        .find(|(value, _)| *value == key)
+        .map_or(default, |(_, offset)| *offset);
+      branch(frame, pc, offset);
+    },
+    _ => panic!("Instruction not implemented: {}", instruction),
+  }
+  Ok(())
+}
+
+// The class name, member name and descriptor of a field or method reference
+fn member_ref(constant_pool: &ConstantPool, index: u16) -> (&str, &str, &str) {
+  match constant_pool.member_ref(MemberRefIndex(index)) {
+    Ok(member) => member,
+    Err(e) => panic!("{}", e),
+  }
+}
+
+// Pops the values that make up the top `units` of operand stack depth,
+// where a long or double counts as two, and returns them bottom first
+fn pop_units(frame: &mut Frame, units: usize) -> Vec<Value> {
+  let mut values = vec![];
+  let mut popped = 0;
+  while popped < units {
+    let value = frame.pop();
+    popped += value.category();
+    values.push(value);
+  }
+  values.reverse();
+  values
+}
+
+// Copies the top `units` of the stack to below the `depth` units under them,
+// which covers every form of dup, dup_x1, dup_x2, dup2, dup2_x1 and dup2_x2
+fn duplicate(frame: &mut Frame, units: usize, depth: usize) {
+  let top = pop_units(frame, units);
+  let below = pop_units(frame, depth);
+  for value in top.iter().chain(&below).chain(&top) {
+    frame.push(value.clone());
+  }
+}
+
+fn int_binary_operation(frame: &mut Frame, operation: fn(i32, i32) -> i32) {
+  let value2 = frame.pop().as_int();
+  let value1 = frame.pop().as_int();
+  frame.push(Value::Int(operation(value1, value2)));
+}
+
+fn int_branch(frame: &mut Frame, pc: usize, offset: i16, condition: fn(i32) -> bool) {
+  let value = frame.pop().as_int();
+  if condition(value) {
+    branch(frame, pc, offset as i32);
+  }
+}
+
+fn int_compare_branch(frame: &mut Frame, pc: usize, offset: i16, condition: fn(Ordering) -> bool) {
+  let value2 = frame.pop().as_int();
+  let value1 = frame.pop().as_int();
+  if condition(value1.cmp(&value2)) {
+    branch(frame, pc, offset as i32);
+  }
+}
+
+fn same_reference(reference1: &Option<Rc<Object>>, reference2: &Option<Rc<Object>>) -> bool {
+  match (reference1, reference2) {
+    (Some(object1), Some(object2)) => Rc::ptr_eq(object1, object2),
+    (None, None) => true,
+    _ => false,
+  }
+}
+
+fn branch(frame: &mut Frame, pc: usize, offset: i32) {
+  frame.pc = (pc as isize + offset as isize) as usize;
+}
+
+fn long_binary_operation(frame: &mut Frame, operation: fn(i64, i64) -> i64) {
+  let value2 = frame.pop().as_long();
+  let value1 = frame.pop().as_long();
+  frame.push(Value::Long(operation(value1, value2)));
+}
+
+// The shift distance of lshl, lshr and lushr is an int
+fn long_shift_operation(frame: &mut Frame, operation: fn(i64, i32) -> i64) {
+  let value2 = frame.pop().as_int();
+  let value1 = frame.pop().as_long();
+  frame.push(Value::Long(operation(value1, value2)));
+}
+
+fn float_binary_operation(frame: &mut Frame, operation: fn(f32, f32) -> f32) {
+  let value2 = frame.pop().as_float();
+  let value1 = frame.pop().as_float();
+  frame.push(Value::Float(operation(value1, value2)));
+}
+
+fn double_binary_operation(frame: &mut Frame, operation: fn(f64, f64) -> f64) {
+  let value2 = frame.pop().as_double();
+  let value1 = frame.pop().as_double();
+  frame.push(Value::Double(operation(value1, value2)));
+}
+
+// fcmpg and dcmpg push 1 when either value is NaN, fcmpl and dcmpl push -1
+fn compare_floating(ordering: Option<Ordering>, nan_greater: bool) -> i32 {
+  match ordering {
+    Some(ordering) => ordering as i32,
+    None if nan_greater => 1,
+    None => -1,
+  }
+}
+
+fn load_constant(constant_pool: &ConstantPool, index: u16) -> Value {
+  let constant = match constant_pool.get(index) {
+    Ok(constant) => constant,
+    Err(e) => panic!("{}", e),
   };
-  // TODO Probably a better way of slicing
-  let operands = (0..arity)
-    .map(|x| code[pc + 1 + x])
-    .collect();
-  envt.pc = pc + 1 + arity;
-  Op {
-    opcode: opcode,
-    operands: operands,
-  }
-}
-
-fn as_u2(byte1: u8, byte2: u8) -> u16 {
-  let byte1_u16: u16 = byte1.into();
-  let byte2_u16: u16 = byte2.into();
-  let byte1_shifted: u16 = byte1_u16 * (2^8);
-  byte1_shifted + byte2_u16
+  match constant {
+    ConstantPoolEnum::ConstantIntegerItem(x) => Value::Int(x.value),
+    ConstantPoolEnum::ConstantFloatItem(x) => Value::Float(x.value),
+    ConstantPoolEnum::ConstantLongItem(x) => Value::Long(x.value),
+    ConstantPoolEnum::ConstantDoubleItem(x) => Value::Double(x.value),
+    ConstantPoolEnum::ConstantStringItem(x) => {
+      let name = match constant_pool.utf8(x.string_index) {
+        Ok(name) => name,
+        Err(e) => panic!("{}", e),
+      };
+      info!("name: {}", name);
+      Value::object(Object::String(name.to_string()))
+    },
+    _ => panic!("Cannot load constant {}", index),
+  }
+}
+
+fn print(frame: &mut Frame, method_name: &str, descriptor: &str) -> Result<(), Rc<Object>> {
+  let arg = if descriptor == "()V" { None } else { Some(frame.pop()) };
+  let object = receiver_object(frame.pop())?;
+  let stream = match object.as_ref() {
+    Object::PrintStream(stream) => *stream,
+    _ => panic!("{} called on {}", method_name, object),
+  };
+  let mut text = match arg {
+    Some(arg) => format_argument(&arg, descriptor),
+    None => String::new(),
+  };
+  if method_name == "println" {
+    text.push('\n');
+  }
+  match stream {
+    Stream::Out => print!("{}", text),
+    Stream::Err => eprint!("{}", text),
+  }
+  Ok(())
+}
+
+// The object an instance method is invoked on, which must not be null
+fn receiver_object(value: Value) -> Result<Rc<Object>, Rc<Object>> {
+  match value {
+    Value::Reference(Some(object)) => Ok(object),
+    Value::Reference(None) => Err(exception_without_message("java/lang/NullPointerException")),
+    other => panic!("Expected reference, found {:?}", other),
+  }
+}
+
+fn exception(class_name: &str, message: &str) -> Rc<Object> {
+  Rc::new(Object::Throwable {
+    class_name: class_name.to_string(),
+    message: Some(message.to_string()),
+  })
+}
+
+fn exception_without_message(class_name: &str) -> Rc<Object> {
+  Rc::new(Object::Throwable {
+    class_name: class_name.to_string(),
+    message: None,
+  })
+}
+
+/// Formats a `print`/`println` argument the way `String.valueOf` would.
+fn format_argument(arg: &Value, descriptor: &str) -> String {
+  match (descriptor, arg) {
+    ("(Z)V", Value::Int(x)) => (*x != 0).to_string(),
+    ("(C)V", Value::Int(x)) => String::from_utf16_lossy(&[*x as u16]),
+    (_, Value::Int(x)) => x.to_string(),
+    (_, Value::Long(x)) => x.to_string(),
+    (_, Value::Float(x)) => format_float(*x),
+    (_, Value::Double(x)) => format_double(*x),
+    // Strings are quoted, as they always have been
+    (_, Value::Reference(Some(object))) => match &**object {
+      Object::String(s) => format!("{:?}", s),
+      other => other.to_string(),
+    },
+    (_, Value::Reference(None)) => String::from("null"),
+    (_, other) => panic!("Cannot print {:?}", other),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::assembler::ClassBuilder;
+  use crate::assembler::CodeBuilder;
+  use crate::assembler::ACC_PUBLIC;
+  use crate::assembler::ACC_STATIC;
+
+  // Runs `body` as `static int f()` of class `Test`, with `main` passing its
+  // result to `System.exit`, and returns the exit status
+  fn run(body: impl FnOnce(&mut ClassBuilder, &mut CodeBuilder)) -> i32 {
+    let mut class = ClassBuilder::new("Test", "java/lang/Object");
+    let mut code = CodeBuilder::new();
+    body(&mut class, &mut code);
+    code.emit(Instruction::Ireturn);
+    class.add_method(ACC_STATIC, "f", "()I", code).unwrap();
+    let mut main = CodeBuilder::new();
+    main.emit(Instruction::Invokestatic(class.method_ref("Test", "f", "()I").unwrap()));
+    main.emit(Instruction::Invokestatic(class.method_ref("java/lang/System", "exit", "(I)V").unwrap()));
+    main.emit(Instruction::Return);
+    class.add_method(ACC_PUBLIC | ACC_STATIC, "main", "([Ljava/lang/String;)V", main).unwrap();
+    execute(class.build())
+  }
+
+  fn int(class: &mut ClassBuilder, code: &mut CodeBuilder, value: i32) {
+    code.emit(Instruction::LdcW(class.integer(value).unwrap()));
+  }
+
+  fn long(class: &mut ClassBuilder, code: &mut CodeBuilder, value: i64) {
+    code.emit(Instruction::Ldc2W(class.long(value).unwrap()));
+  }
+
+  fn float(class: &mut ClassBuilder, code: &mut CodeBuilder, value: f32) {
+    code.emit(Instruction::LdcW(class.float(value).unwrap()));
+  }
+
+  fn double(class: &mut ClassBuilder, code: &mut CodeBuilder, value: f64) {
+    code.emit(Instruction::Ldc2W(class.double(value).unwrap()));
+  }
+
+  // The high half of the long on the stack, as an int
+  fn high_int(code: &mut CodeBuilder) {
+    code.emit(Instruction::Bipush(32));
+    code.emit(Instruction::Lshr);
+    code.emit(Instruction::L2i);
+  }
+
+  fn int_operation(value1: i32, value2: i32, instruction: Instruction) -> i32 {
+    run(|class, code| {
+      int(class, code, value1);
+      int(class, code, value2);
+      code.emit(instruction);
+    })
+  }
+
+  #[test]
+  fn wraps_int_arithmetic() {
+    assert_eq!(int_operation(i32::MAX, 1, Instruction::Iadd), i32::MIN);
+    assert_eq!(int_operation(i32::MIN, 1, Instruction::Isub), i32::MAX);
+    assert_eq!(int_operation(0x10000, 0x10000, Instruction::Imul), 0);
+    assert_eq!(int_operation(i32::MIN, -1, Instruction::Idiv), i32::MIN);
+    assert_eq!(int_operation(i32::MIN, -1, Instruction::Irem), 0);
+    assert_eq!(int_operation(-7, 2, Instruction::Idiv), -3);
+    assert_eq!(int_operation(-7, 2, Instruction::Irem), -1);
+    assert_eq!(run(|class, code| {
+      int(class, code, i32::MIN);
+      code.emit(Instruction::Ineg);
+    }), i32::MIN);
+    assert_eq!(run(|class, code| {
+      int(class, code, 0x1ff);
+      code.emit(Instruction::I2b);
+    }), -1);
+  }
+
+  #[test]
+  fn wraps_long_arithmetic() {
+    assert_eq!(run(|class, code| {
+      long(class, code, i64::MAX);
+      code.emit(Instruction::Lconst1);
+      code.emit(Instruction::Ladd);
+      high_int(code);
+    }), i32::MIN);
+    assert_eq!(run(|class, code| {
+      long(class, code, i64::MIN);
+      long(class, code, -1);
+      code.emit(Instruction::Ldiv);
+      high_int(code);
+    }), i32::MIN);
+    assert_eq!(run(|class, code| {
+      long(class, code, 1 << 40);
+      long(class, code, 1 << 40);
+      code.emit(Instruction::Lmul);
+      code.emit(Instruction::L2i);
+    }), 0);
+  }
+
+  #[test]
+  fn throws_on_division_by_zero() {
+    assert_eq!(int_operation(1, 0, Instruction::Idiv), 1);
+    assert_eq!(int_operation(1, 0, Instruction::Irem), 1);
+    assert_eq!(run(|_, code| {
+      code.emit(Instruction::Lconst1);
+      code.emit(Instruction::Lconst0);
+      code.emit(Instruction::Lrem);
+      code.emit(Instruction::L2i);
+    }), 1);
+
+    // The exception itself, from a method with room for two longs
+    let mut class = ClassBuilder::new("Test", "java/lang/Object");
+    let mut code = CodeBuilder::new();
+    code.emit(Instruction::Lconst1);
+    code.emit(Instruction::Lconst0);
+    code.emit(Instruction::Ldiv);
+    code.emit(Instruction::L2i);
+    code.emit(Instruction::Ireturn);
+    class.add_method(ACC_STATIC, "f", "()I", code).unwrap();
+    let class = class.build();
+    let mut envt = Envt {
+      frames: vec![Frame::new(&class, class.get_method("f", "()I").unwrap())],
+      exit_status: None,
+    };
+    envt.frames[0].push(Value::Long(1));
+    envt.frames[0].push(Value::Long(0));
+    let exception = execute_instruction(0, Instruction::Ldiv, &mut envt).unwrap_err();
+    assert_eq!(exception.to_string(), "java.lang.ArithmeticException: / by zero");
+  }
+
+  #[test]
+  fn masks_shift_distances() {
+    assert_eq!(int_operation(1, 33, Instruction::Ishl), 2);
+    assert_eq!(int_operation(-16, 34, Instruction::Ishr), -4);
+    assert_eq!(int_operation(-1, 60, Instruction::Iushr), 15);
+    assert_eq!(int_operation(1, -1, Instruction::Ishl), i32::MIN);
+    assert_eq!(run(|_, code| {
+      code.emit(Instruction::Lconst1);
+      code.emit(Instruction::Bipush(65));
+      code.emit(Instruction::Lshl);
+      code.emit(Instruction::L2i);
+    }), 2);
+    assert_eq!(run(|class, code| {
+      long(class, code, -1);
+      code.emit(Instruction::Bipush(124));
+      code.emit(Instruction::Lushr);
+      code.emit(Instruction::L2i);
+    }), 15);
+  }
+
+  #[test]
+  fn compares_nan() {
+    for (instruction, expected) in [(Instruction::Fcmpl, -1), (Instruction::Fcmpg, 1)] {
+      assert_eq!(run(|class, code| {
+        float(class, code, f32::NAN);
+        code.emit(Instruction::Fconst0);
+        code.emit(instruction);
+      }), expected);
+    }
+    for (instruction, expected) in [(Instruction::Dcmpl, -1), (Instruction::Dcmpg, 1)] {
+      assert_eq!(run(|class, code| {
+        code.emit(Instruction::Dconst0);
+        double(class, code, f64::NAN);
+        code.emit(instruction);
+      }), expected);
+    }
+    assert_eq!(run(|class, code| {
+      float(class, code, -0.0);
+      code.emit(Instruction::Fconst0);
+      code.emit(Instruction::Fcmpg);
+    }), 0);
+  }
+
+  #[test]
+  fn saturates_float_conversions() {
+    assert_eq!(run(|class, code| {
+      float(class, code, 1e20);
+      code.emit(Instruction::F2i);
+    }), i32::MAX);
+    assert_eq!(run(|class, code| {
+      float(class, code, f32::NEG_INFINITY);
+      code.emit(Instruction::F2i);
+    }), i32::MIN);
+    assert_eq!(run(|class, code| {
+      float(class, code, f32::NAN);
+      code.emit(Instruction::F2i);
+    }), 0);
+    assert_eq!(run(|class, code| {
+      double(class, code, 1e30);
+      code.emit(Instruction::D2l);
+      high_int(code);
+    }), i32::MAX);
+    assert_eq!(run(|class, code| {
+      double(class, code, -1e30);
+      code.emit(Instruction::D2l);
+      high_int(code);
+    }), i32::MIN);
+    assert_eq!(run(|class, code| {
+      double(class, code, f64::NAN);
+      code.emit(Instruction::D2l);
+      code.emit(Instruction::L2i);
+    }), 0);
+  }
+
+  // int sum = 0; for (int i = 10; i != 0; i--) sum += i; return sum;
+  #[test]
+  fn follows_branch_offsets() {
+    for wide in [false, true] {
+      assert_eq!(run(|_, code| {
+        let top = code.new_label();
+        let done = code.new_label();
+        code.emit(Instruction::Iconst0);
+        code.emit(Instruction::Istore0);
+        code.emit(Instruction::Bipush(10));
+        code.emit(Instruction::Istore1);
+        code.place(top);
+        code.emit(Instruction::Iload1);
+        code.branch(Instruction::Ifeq, done);
+        code.emit(Instruction::Iload0);
+        code.emit(Instruction::Iload1);
+        code.emit(Instruction::Iadd);
+        code.emit(Instruction::Istore0);
+        code.emit(Instruction::Iinc(1, -1));
+        if wide {
+          code.branch_wide(Instruction::GotoW, top);
+        } else {
+          code.branch(Instruction::Goto, top);
+        }
+        code.place(done);
+        code.emit(Instruction::Iload0);
+      }), 55);
+    }
+  }
+
+  // Every alignment of the switch, so that each amount of padding is read
+  #[test]
+  fn pads_switches() {
+    for nops in 0..4 {
+      for key in -1..4 {
+        let table = run(|_, code| {
+          let cases = vec![code.new_label(), code.new_label(), code.new_label()];
+          let default = code.new_label();
+          code.emit(Instruction::Bipush(key as i8));
+          for _ in 0..nops {
+            code.emit(Instruction::Nop);
+          }
+          code.tableswitch(0, cases.clone(), default);
+          for (i, case) in cases.into_iter().enumerate() {
+            code.place(case);
+            code.emit(Instruction::Bipush(10 * i as i8));
+            code.emit(Instruction::Ireturn);
+          }
+          code.place(default);
+          code.emit(Instruction::IconstM1);
+        });
+        assert_eq!(table, if (0..3).contains(&key) { 10 * key } else { -1 });
+
+        let lookup = run(|class, code| {
+          let low = code.new_label();
+          let high = code.new_label();
+          let default = code.new_label();
+          code.emit(Instruction::Bipush(key as i8));
+          for _ in 0..nops {
+            code.emit(Instruction::Nop);
+          }
+          code.lookupswitch(vec![(3, high), (-1, low)], default);
+          code.place(low);
+          int(class, code, 100);
+          code.emit(Instruction::Ireturn);
+          code.place(high);
+          int(class, code, 300);
+          code.emit(Instruction::Ireturn);
+          code.place(default);
+          code.emit(Instruction::Iconst0);
+        });
+        assert_eq!(lookup, match key { -1 => 100, 3 => 300, _ => 0 });
+      }
+    }
+  }
+
+  #[test]
+  fn executes_wide_instructions() {
+    assert_eq!(run(|class, code| {
+      int(class, code, 1000);
+      code.emit(Instruction::Istore(300));
+      code.emit(Instruction::Iinc(300, 2000));
+      code.emit(Instruction::Iload(300));
+      code.emit(Instruction::Iconst2);
+      code.emit(Instruction::Wide(Box::new(Instruction::Istore(3))));
+      code.emit(Instruction::Wide(Box::new(Instruction::Iinc(3, 1))));
+      code.emit(Instruction::Wide(Box::new(Instruction::Iload(3))));
+      code.emit(Instruction::Iadd);
+    }), 3003);
+  }
+
+  // static int factorial(int n) { return n == 0 ? 1 : n * factorial(n - 1); }
+  #[test]
+  fn invokes_static_methods_recursively() {
+    assert_eq!(run(|class, code| {
+      let factorial = class.method_ref("Test", "factorial", "(I)I").unwrap();
+      let mut body = CodeBuilder::new();
+      let recurse = body.new_label();
+      body.emit(Instruction::Iload0);
+      body.branch(Instruction::Ifne, recurse);
+      body.emit(Instruction::Iconst1);
+      body.emit(Instruction::Ireturn);
+      body.place(recurse);
+      body.emit(Instruction::Iload0);
+      body.emit(Instruction::Iload0);
+      body.emit(Instruction::Iconst1);
+      body.emit(Instruction::Isub);
+      body.emit(Instruction::Invokestatic(factorial));
+      body.emit(Instruction::Imul);
+      body.emit(Instruction::Ireturn);
+      class.add_method(ACC_STATIC, "factorial", "(I)I", body).unwrap();
+
+      code.emit(Instruction::Bipush(10));
+      code.emit(Instruction::Invokestatic(factorial));
+    }), 3628800);
+  }
+
+  // Long and double arguments take two locals in the callee
+  #[test]
+  fn passes_category_2_arguments() {
+    assert_eq!(run(|class, code| {
+      let method = class.method_ref("Test", "g", "(JID)I").unwrap();
+      let mut body = CodeBuilder::new();
+      body.emit(Instruction::Lload0);
+      body.emit(Instruction::L2i);
+      body.emit(Instruction::Iload2);
+      body.emit(Instruction::Iadd);
+      body.emit(Instruction::Dload3);
+      body.emit(Instruction::D2i);
+      body.emit(Instruction::Iadd);
+      body.emit(Instruction::Ireturn);
+      class.add_method(ACC_STATIC, "g", "(JID)I", body).unwrap();
+
+      long(class, code, 100);
+      code.emit(Instruction::Bipush(20));
+      double(class, code, 3.5);
+      code.emit(Instruction::Invokestatic(method));
+    }), 123);
+  }
+
+  // Exiting from a callee stops the caller from exiting with its result
+  #[test]
+  fn exits_with_status() {
+    assert_eq!(run(|class, code| {
+      code.emit(Instruction::Bipush(7));
+      code.emit(Instruction::Invokestatic(class.method_ref("java/lang/System", "exit", "(I)V").unwrap()));
+      code.emit(Instruction::Iconst0);
+    }), 7);
+  }
+
+  // Pops values of `types`, given bottom first as `I` or `J`, and returns
+  // the decimal number with one digit for each
+  fn digits(code: &mut CodeBuilder, types: &str) {
+    for (local, t) in types.bytes().enumerate().rev() {
+      if t == b'J' {
+        code.emit(Instruction::L2i);
+      }
+      code.emit(Instruction::Istore(local as u16));
+    }
+    code.emit(Instruction::Iconst0);
+    for local in 0..types.len() {
+      code.emit(Instruction::Bipush(10));
+      code.emit(Instruction::Imul);
+      code.emit(Instruction::Iload(local as u16));
+      code.emit(Instruction::Iadd);
+    }
+  }
+
+  // Each form of each instruction, with the long 1 pushed by `lconst_1` and
+  // the long 2 by `iconst_2; i2l`
+  #[test]
+  fn manipulates_stack() {
+    use Instruction::*;
+    let cases = vec![
+      (vec![Iconst1, Iconst2], Pop, "I", 1),
+      (vec![Iconst1, Iconst2, Iconst3], Pop2, "I", 1),
+      (vec![Iconst1, Lconst1], Pop2, "I", 1),
+      (vec![Iconst1, Iconst2], Dup, "III", 122),
+      (vec![Iconst1, Iconst2], DupX1, "III", 212),
+      (vec![Iconst1, Iconst2, Iconst3], DupX2, "IIII", 3123),
+      (vec![Lconst1, Iconst2], DupX2, "IJI", 212),
+      (vec![Iconst1, Iconst2], Dup2, "IIII", 1212),
+      (vec![Iconst3, Lconst1], Dup2, "IJJ", 311),
+      (vec![Iconst1, Iconst2, Iconst3], Dup2X1, "IIIII", 23123),
+      (vec![Iconst3, Lconst1], Dup2X1, "JIJ", 131),
+      (vec![Iconst1, Iconst2, Iconst3, Iconst4], Dup2X2, "IIIIII", 341234),
+      (vec![Iconst2, Iconst3, Lconst1], Dup2X2, "JIIJ", 1231),
+      (vec![Lconst1, Iconst2, Iconst3], Dup2X2, "IIJII", 23123),
+      (vec![Lconst1, Iconst2, I2l], Dup2X2, "JJJ", 212),
+      (vec![Iconst1, Iconst2], Swap, "II", 21),
+    ];
+    for (setup, instruction, types, expected) in cases {
+      let mnemonic = instruction.mnemonic();
+      let status = run(|_, code| {
+        for setup_instruction in setup {
+          code.emit(setup_instruction);
+        }
+        code.emit(instruction);
+        digits(code, types);
+      });
+      assert_eq!(status, expected, "{} leaving {}", mnemonic, types);
+    }
+  }
 }
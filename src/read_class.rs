@@ -1,9 +1,13 @@
 use byteorder::{BigEndian, ReadBytesExt};
+use std::error;
+use std::fmt;
 use std::fs::File;
 use std::io;
+use std::io::Read;
 use std::string::String;
 use log::info;
 
+use crate::mutf8;
 use crate::class::BytecodeClass;
 use crate::class::ConstantPoolEnum;
 use crate::class::ConstantUtf8Class;
@@ -12,258 +16,1757 @@ use crate::class::ConstantStringClass;
 use crate::class::ConstantFieldrefClass;
 use crate::class::ConstantMethodrefClass;
 use crate::class::ConstantNameAndTypeClass;
+use crate::class::ConstantIntegerClass;
+use crate::class::ConstantFloatClass;
+use crate::class::ConstantLongClass;
+use crate::class::ConstantDoubleClass;
+use crate::class::ConstantInterfaceMethodrefClass;
+use crate::class::ConstantMethodHandleClass;
+use crate::class::ConstantMethodTypeClass;
+use crate::class::ConstantDynamicClass;
+use crate::class::ConstantInvokeDynamicClass;
+use crate::class::ConstantModuleClass;
+use crate::class::ConstantPackageClass;
+use crate::class::FieldInfo;
 use crate::class::MethodInfo;
 use crate::class::AttributeEnum;
 use crate::class::CodeClass;
 use crate::class::LineNumberTableClass;
 use crate::class::StackMapTableClass;
+use crate::class::StackMapFrame;
+use crate::class::VerificationTypeInfo;
 use crate::class::SourceFileClass;
+use crate::class::ConstantValueClass;
+use crate::class::ExceptionsClass;
+use crate::class::InnerClassesClass;
+use crate::class::InnerClassesElem;
+use crate::class::EnclosingMethodClass;
+use crate::class::SignatureClass;
+use crate::class::LocalVariableTableClass;
+use crate::class::LocalVariableTableElem;
+use crate::class::LocalVariableTypeTableClass;
+use crate::class::LocalVariableTypeTableElem;
+use crate::class::BootstrapMethodsClass;
+use crate::class::BootstrapMethodElem;
+use crate::class::MethodParametersClass;
+use crate::class::MethodParameterElem;
+use crate::class::NestHostClass;
+use crate::class::NestMembersClass;
+use crate::class::RecordClass;
+use crate::class::RecordComponentInfo;
+use crate::class::PermittedSubclassesClass;
+use crate::class::DeprecatedClass;
+use crate::class::SyntheticClass;
+use crate::class::SourceDebugExtensionClass;
+use crate::class::UnknownClass;
+use crate::class::AnnotationsClass;
+use crate::class::ParameterAnnotationsClass;
+use crate::class::ParameterAnnotations;
+use crate::class::TypeAnnotationsClass;
+use crate::class::AnnotationDefaultClass;
+use crate::class::Annotation;
+use crate::class::ElementValuePair;
+use crate::class::ElementValue;
+use crate::class::TypeAnnotation;
+use crate::class::TargetInfo;
+use crate::class::LocalvarTargetElem;
+use crate::class::TypePath;
+use crate::class::TypePathElem;
+use crate::class::ModuleClass;
+use crate::class::ModuleRequires;
+use crate::class::ModuleExports;
+use crate::class::ModuleOpens;
+use crate::class::ModuleProvides;
+use crate::class::ModulePackagesClass;
+use crate::class::ModuleMainClassClass;
 use crate::class::LineNumberTableElem;
-use crate::class::ExceptionTable;
-
-pub fn parse(path: String) -> BytecodeClass {
-    let file = File::open(path).unwrap();
-    let mut con = io::BufReader::new(file);
-    let magic = con.read_u32::<BigEndian>().unwrap();
-    let minor_version = con.read_u16::<BigEndian>().unwrap();
-    let major_version = con.read_u16::<BigEndian>().unwrap();
-    let constant_pool_count = con.read_u16::<BigEndian>().unwrap();
+use crate::class::ExceptionTableEntry;
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::ConstantPoolError;
+use crate::constant_pool::Utf8Index;
+use crate::constant_pool::ClassIndex;
+use crate::constant_pool::NameAndTypeIndex;
+use crate::constant_pool::MemberRefIndex;
+use crate::constant_pool::ModuleIndex;
+use crate::constant_pool::PackageIndex;
+
+/// Why a class file could not be parsed. Every variant except `Io` carries
+/// the byte offset in the class file at which the problem was found.
+#[derive(Debug)]
+pub enum ClassFormatError {
+    Io(io::Error),
+    Truncated { offset: usize },
+    BadMagic { offset: usize, magic: u32 },
+    BadConstantTag { offset: usize, tag: u8 },
+    BadConstantIndex { offset: usize, index: u16 },
+    WrongConstantKind { offset: usize, index: u16, expected: &'static str },
+    /// A long or double in the last constant pool entry, whose second
+    /// entry would be past the largest possible index
+    TooManyConstants { offset: usize },
+    /// A `CONSTANT_Utf8` entry that is not valid modified UTF-8
+    InvalidUtf8 { offset: usize },
+    BadAttributeLength { offset: usize, name: String, attribute_length: u32, actual_length: usize },
+    BadStackMapFrame { offset: usize, frame_type: u8 },
+    BadVerificationType { offset: usize, tag: u8 },
+    BadElementValueTag { offset: usize, tag: u8 },
+    BadTargetType { offset: usize, target_type: u8 },
+    /// Annotations or arrays nested in an element value deeper than
+    /// `MAX_ELEMENT_VALUE_DEPTH`
+    ElementValueTooDeep { offset: usize },
+    /// Attributes nested in `Code` or `Record` attributes deeper than
+    /// `MAX_ATTRIBUTE_DEPTH`
+    AttributeTooDeep { offset: usize },
+    Unsupported { offset: usize, feature: &'static str },
+    TrailingBytes { offset: usize },
+}
+
+impl fmt::Display for ClassFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClassFormatError::Io(e) => write!(f, "{}", e),
+            ClassFormatError::Truncated { offset } =>
+                write!(f, "Class file ends unexpectedly at byte {}", offset),
+            ClassFormatError::BadMagic { offset, magic } =>
+                write!(f, "Bad magic number {:#010x} at byte {}", magic, offset),
+            ClassFormatError::BadConstantTag { offset, tag } =>
+                write!(f, "Bad constant pool tag {} at byte {}", tag, offset),
+            ClassFormatError::BadConstantIndex { offset, index } =>
+                write!(f, "Constant pool index {} out of range at byte {}", index, offset),
+            ClassFormatError::WrongConstantKind { offset, index, expected } =>
+                write!(f, "Constant pool entry {} is not a {} at byte {}", index, expected, offset),
+            ClassFormatError::TooManyConstants { offset } =>
+                write!(f, "Constant pool has more than 65534 entries at byte {}", offset),
+            ClassFormatError::InvalidUtf8 { offset } =>
+                write!(f, "Invalid modified UTF-8 constant at byte {}", offset),
+            ClassFormatError::BadAttributeLength { offset, name, attribute_length, actual_length } =>
+                write!(f, "{} attribute at byte {} has attribute_length {} but is {} bytes long",
+                    name, offset, attribute_length, actual_length),
+            ClassFormatError::BadStackMapFrame { offset, frame_type } =>
+                write!(f, "Bad stack map frame type {} at byte {}", frame_type, offset),
+            ClassFormatError::BadVerificationType { offset, tag } =>
+                write!(f, "Bad verification type tag {} at byte {}", tag, offset),
+            ClassFormatError::BadElementValueTag { offset, tag } =>
+                write!(f, "Bad element value tag {} at byte {}", tag, offset),
+            ClassFormatError::BadTargetType { offset, target_type } =>
+                write!(f, "Bad type annotation target type {:#04x} at byte {}", target_type, offset),
+            ClassFormatError::ElementValueTooDeep { offset } =>
+                write!(f, "Element value nested too deeply at byte {}", offset),
+            ClassFormatError::AttributeTooDeep { offset } =>
+                write!(f, "Attribute nested too deeply at byte {}", offset),
+            ClassFormatError::Unsupported { offset, feature } =>
+                write!(f, "{} not implemented, at byte {}", feature, offset),
+            ClassFormatError::TrailingBytes { offset } =>
+                write!(f, "Unexpected bytes after the end of the class at byte {}", offset),
+        }
+    }
+}
+
+impl error::Error for ClassFormatError {}
+
+// Reads big-endian values while keeping track of the offset for errors
+struct ClassReader<'a> {
+    con: &'a mut dyn Read,
+    offset: usize,
+}
+
+impl ClassReader<'_> {
+    fn read<T>(&mut self, size: usize, read: fn(&mut dyn Read) -> io::Result<T>)
+        -> Result<T, ClassFormatError> {
+        match read(&mut self.con) {
+            Ok(value) => {
+                self.offset += size;
+                Ok(value)
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof =>
+                Err(ClassFormatError::Truncated { offset: self.offset }),
+            Err(e) => Err(ClassFormatError::Io(e)),
+        }
+    }
+
+    fn u1(&mut self) -> Result<u8, ClassFormatError> {
+        self.read(1, |con| con.read_u8())
+    }
+
+    fn u2(&mut self) -> Result<u16, ClassFormatError> {
+        self.read(2, |con| con.read_u16::<BigEndian>())
+    }
+
+    fn u4(&mut self) -> Result<u32, ClassFormatError> {
+        self.read(4, |con| con.read_u32::<BigEndian>())
+    }
+
+    fn i4(&mut self) -> Result<i32, ClassFormatError> {
+        self.read(4, |con| con.read_i32::<BigEndian>())
+    }
+
+    fn f4(&mut self) -> Result<f32, ClassFormatError> {
+        self.read(4, |con| con.read_f32::<BigEndian>())
+    }
+
+    fn i8(&mut self) -> Result<i64, ClassFormatError> {
+        self.read(8, |con| con.read_i64::<BigEndian>())
+    }
+
+    fn f8(&mut self) -> Result<f64, ClassFormatError> {
+        self.read(8, |con| con.read_f64::<BigEndian>())
+    }
+
+    // Grows the buffer as bytes arrive, since `length` is read from the
+    // class file and may be far more than there is
+    fn bytes(&mut self, length: usize) -> Result<Vec<u8>, ClassFormatError> {
+        let mut bytes = vec![];
+        match (&mut self.con).take(length as u64).read_to_end(&mut bytes) {
+            Ok(read) if read == length => {
+                self.offset += length;
+                Ok(bytes)
+            },
+            Ok(_) => Err(ClassFormatError::Truncated { offset: self.offset }),
+            Err(e) => Err(ClassFormatError::Io(e)),
+        }
+    }
+
+    fn at_end(&mut self) -> Result<bool, ClassFormatError> {
+        let mut byte = [0];
+        match self.con.read(&mut byte) {
+            Ok(n) => Ok(n == 0),
+            Err(e) => Err(ClassFormatError::Io(e)),
+        }
+    }
+}
+
+// Places an unresolvable index at `offset` in the class file
+fn constant_error(offset: usize) -> impl Fn(ConstantPoolError) -> ClassFormatError {
+    move |e| match e {
+        ConstantPoolError::BadIndex { index } => ClassFormatError::BadConstantIndex { offset, index },
+        ConstantPoolError::WrongKind { index, expected } =>
+            ClassFormatError::WrongConstantKind { offset, index, expected },
+        ConstantPoolError::Full => ClassFormatError::TooManyConstants { offset },
+    }
+}
+
+fn utf8(constant_pool: &ConstantPool, index: Utf8Index, offset: usize) -> Result<String, ClassFormatError> {
+    constant_pool.utf8(index).map(String::from).map_err(constant_error(offset))
+}
+
+fn class_name(constant_pool: &ConstantPool, index: ClassIndex, offset: usize) -> Result<String, ClassFormatError> {
+    constant_pool.class_name(index).map(String::from).map_err(constant_error(offset))
+}
+
+fn module_name(constant_pool: &ConstantPool, index: ModuleIndex, offset: usize) -> Result<String, ClassFormatError> {
+    constant_pool.module_name(index).map(String::from).map_err(constant_error(offset))
+}
+
+fn package_name(constant_pool: &ConstantPool, index: PackageIndex, offset: usize) -> Result<String, ClassFormatError> {
+    constant_pool.package_name(index).map(String::from).map_err(constant_error(offset))
+}
+
+// An index of 0 stands for "none" in several attributes
+fn optional_utf8(constant_pool: &ConstantPool, index: Utf8Index, offset: usize)
+    -> Result<Option<String>, ClassFormatError> {
+    match index {
+        Utf8Index(0) => Ok(None),
+        _ => utf8(constant_pool, index, offset).map(Some),
+    }
+}
+
+fn optional_class_name(constant_pool: &ConstantPool, index: ClassIndex, offset: usize)
+    -> Result<Option<String>, ClassFormatError> {
+    match index {
+        ClassIndex(0) => Ok(None),
+        _ => class_name(constant_pool, index, offset).map(Some),
+    }
+}
+
+fn name_and_type(constant_pool: &ConstantPool, index: NameAndTypeIndex, offset: usize)
+    -> Result<(String, String), ClassFormatError> {
+    let (name, descriptor) = constant_pool.name_and_type(index).map_err(constant_error(offset))?;
+    Ok((name.to_string(), descriptor.to_string()))
+}
+
+// Reads `count` class indexes, as in the interfaces table
+fn read_classes(con: &mut ClassReader, constant_pool: &ConstantPool, count: u16)
+    -> Result<(Vec<ClassIndex>, Vec<String>), ClassFormatError> {
+    read_names(con, constant_pool, count, ClassIndex, class_name)
+}
+
+// Reads `count` constant pool indexes, made typed by `index`, and resolves
+// each with `name`
+fn read_names<I: Copy>(con: &mut ClassReader, constant_pool: &ConstantPool, count: u16, index: fn(u16) -> I,
+    name: fn(&ConstantPool, I, usize) -> Result<String, ClassFormatError>)
+    -> Result<(Vec<I>, Vec<String>), ClassFormatError> {
+    let mut indexes: Vec<I> = vec![];
+    let mut names: Vec<String> = vec![];
+    for _ in 0..count {
+        let offset = con.offset;
+        let index = index(con.u2()?);
+        names.push(name(constant_pool, index, offset)?);
+        indexes.push(index);
+    }
+    Ok((indexes, names))
+}
+
+/// Parses the class file at `path`. Anything after the end of the class
+/// is an error.
+pub fn parse(path: String) -> Result<BytecodeClass, ClassFormatError> {
+    let file = File::open(path).map_err(ClassFormatError::Io)?;
+    parse_exactly(io::BufReader::new(file))
+}
+
+/// Parses a class file held in memory, e.g. an entry read from a JAR.
+/// Anything after the end of the class is an error.
+pub fn parse_bytes(bytes: &[u8]) -> Result<BytecodeClass, ClassFormatError> {
+    parse_exactly(bytes)
+}
+
+/// Parses a class file from the start of `reader`, reading nothing past the
+/// end of the class, so that a stream which goes on after it can be passed
+/// as `&mut reader` and read further. Unbuffered readers should be wrapped
+/// in a `BufReader`.
+pub fn parse_reader<R: Read>(mut reader: R) -> Result<BytecodeClass, ClassFormatError> {
+    read_class(&mut ClassReader { con: &mut reader, offset: 0 })
+}
+
+// Parses `reader`, which must hold exactly one class
+fn parse_exactly<R: Read>(mut reader: R) -> Result<BytecodeClass, ClassFormatError> {
+    let mut con = ClassReader { con: &mut reader, offset: 0 };
+    let class = read_class(&mut con)?;
+    if !con.at_end()? {
+        return Err(ClassFormatError::TrailingBytes { offset: con.offset });
+    }
+    Ok(class)
+}
+
+fn read_class(con: &mut ClassReader) -> Result<BytecodeClass, ClassFormatError> {
+    let magic = con.u4()?;
+    if magic != 0xCAFEBABE {
+        return Err(ClassFormatError::BadMagic { offset: 0, magic });
+    }
+    let minor_version = con.u2()?;
+    let major_version = con.u2()?;
+    let constant_pool_count = con.u2()?;
     info!("Magic: {}", magic);
     info!("Minor version: {}", minor_version);
     info!("Major version: {}", major_version);
     info!("Constant pool count: {}", constant_pool_count);
-    let constant_pool: Vec<ConstantPoolEnum> = (0..(constant_pool_count - 1))
-        .map(|_x| read_cp_info(&mut con))
-        .collect();
-    let access_flags = con.read_u16::<BigEndian>().unwrap();
-    let this_class = con.read_u16::<BigEndian>().unwrap();
+    let mut constant_pool = ConstantPool::new();
+    while constant_pool.len() < constant_pool_count.saturating_sub(1) as usize {
+        let offset = con.offset;
+        constant_pool.push(read_cp_info(con)?).map_err(constant_error(offset))?;
+    }
+    let access_flags = con.u2()?;
     info!("Access flags: {}", access_flags);
-    // TODO Deal better with constant pool being 1-indexed
-    let this_class_class: &ConstantPoolEnum = &constant_pool[(this_class - 1) as usize];
-    info!("This class index (should be 5): {}", this_class);
-    let this_class_utf8 = match this_class_class {
-        ConstantPoolEnum::ConstantClassItem(x) => 
-            &constant_pool[(x.name_index - 1) as usize],
-        _ => panic!("Something went wrong!"),
-    };
-    let this_class_name = match this_class_utf8 {
-        ConstantPoolEnum::ConstantUtf8Item(x) => x.bytes.clone(),
-        _ => panic!("Something went wrong!"),
-    };
+    let offset = con.offset;
+    let this_class = ClassIndex(con.u2()?);
+    info!("This class index: {}", this_class.0);
+    let this_class_name = class_name(&constant_pool, this_class, offset)?;
     info!("This class name: {}", this_class_name);
-    let super_class = con.read_u16::<BigEndian>().unwrap();
-    info!("Super class index (should be 6): {}", super_class);
-    let super_class_class: &ConstantPoolEnum = &constant_pool[(super_class - 1) as usize];
-    let super_class_utf8 = match super_class_class {
-        ConstantPoolEnum::ConstantClassItem(x) => 
-            &constant_pool[(x.name_index - 1) as usize],
-        _ => panic!("Something went wrong!"),
-    };
-    let super_class_name = match super_class_utf8 {
-        ConstantPoolEnum::ConstantUtf8Item(x) => x.bytes.clone(),
-        _ => panic!("Something went wrong!"),
+    let offset = con.offset;
+    let super_class = ClassIndex(con.u2()?);
+    info!("Super class index: {}", super_class.0);
+    // Only java/lang/Object has no superclass
+    let super_class_name = if super_class == ClassIndex(0) {
+        String::new()
+    } else {
+        class_name(&constant_pool, super_class, offset)?
     };
     info!("Super class name: {}", super_class_name);
     
-    let interfaces_count = con.read_u16::<BigEndian>().unwrap();
-    // TODO
-    if interfaces_count > 0 {
-        panic!("Interfaces not implemented")
-    }
+    let interfaces_count = con.u2()?;
+    let (interfaces, interface_names) = read_classes(con, &constant_pool, interfaces_count)?;
     info!("Read interfaces");
-    let fields_count = con.read_u16::<BigEndian>().unwrap();
-    // TODO
-    if fields_count > 0 {
-        panic!("Fields not implemented")
-    }
+    let fields_count = con.u2()?;
+    info!("Fields: {}", fields_count);
+    let fields: Vec<FieldInfo> = (0..fields_count)
+        .map(|_x| read_field_info(con, &constant_pool))
+        .collect::<Result<Vec<FieldInfo>, ClassFormatError>>()?;
     info!("Read fields");
-    let methods_count = con.read_u16::<BigEndian>().unwrap();
+    let methods_count = con.u2()?;
     info!("Methods: {}", methods_count);
     let methods: Vec<MethodInfo> = (0..methods_count)
-        .map(|_x| read_method_info(&mut con, &constant_pool))
-        .collect();
+        .map(|_x| read_method_info(con, &constant_pool))
+        .collect::<Result<Vec<MethodInfo>, ClassFormatError>>()?;
     info!("Read methods");
-    let attributes_count = con.read_u16::<BigEndian>().unwrap();
+    let attributes_count = con.u2()?;
     let attributes: Vec<AttributeEnum> = (0..attributes_count)
-        .map(|_x| read_attribute(&mut con, &constant_pool))
-        .collect();
-
-    return BytecodeClass {
-        magic: magic,
-        minor_version: minor_version,
-        major_version: major_version,
-        constant_pool: constant_pool,
-        access_flags: access_flags,
-        this_class: this_class,
-        this_class_name: this_class_name,
-        super_class: super_class,
-        super_class_name: super_class_name,
-        methods: methods,
-        attributes: attributes,
-    };
+        .map(|_x| read_attribute(con, &constant_pool, 0))
+        .collect::<Result<Vec<AttributeEnum>, ClassFormatError>>()?;
+
+    Ok(BytecodeClass {
+        magic,
+        minor_version,
+        major_version,
+        constant_pool,
+        access_flags,
+        this_class,
+        this_class_name,
+        super_class,
+        super_class_name,
+        interfaces,
+        interface_names,
+        fields,
+        methods,
+        attributes,
+    })
 }
 
-fn read_cp_info(con: &mut io::BufReader<File>) -> ConstantPoolEnum {
-    // Was con.read_u8::<BigEndian>().unwrap() 
-    let tag: u8 = con.read_u8().unwrap();
+fn read_cp_info(con: &mut ClassReader) -> Result<ConstantPoolEnum, ClassFormatError> {
+    let offset = con.offset;
+    let tag: u8 = con.u1()?;
     info!("Tag: {}", tag);
-    let info = match tag {
+    let cp_info = match tag {
         1 => {
-            let n_bytes = con.read_u16::<BigEndian>().unwrap();
-            let byte_slice_vec: Vec<u8> = (0..n_bytes)
-                .map(|_x| con.read_u8().unwrap())
-                .collect();
-            let bytes = String::from_utf8(byte_slice_vec).unwrap();
-            return ConstantPoolEnum::ConstantUtf8Item(
-                ConstantUtf8Class { length: n_bytes, bytes: bytes });
+            let n_bytes = con.u2()?;
+            let start = con.offset;
+            let byte_slice_vec: Vec<u8> = con.bytes(n_bytes as usize)?;
+            let bytes = match mutf8::decode(&byte_slice_vec) {
+                Ok(bytes) => bytes,
+                Err(e) => return Err(ClassFormatError::InvalidUtf8 { offset: start + e.position }),
+            };
+            // Only non-ASCII strings can have more than one encoding
+            let original = if byte_slice_vec.is_ascii() || mutf8::encode(&bytes) == byte_slice_vec {
+                None
+            } else {
+                Some(byte_slice_vec)
+            };
+            ConstantPoolEnum::ConstantUtf8Item(
+                ConstantUtf8Class { length: n_bytes, bytes, original })
         },
+        3 => ConstantPoolEnum::ConstantIntegerItem(
+            ConstantIntegerClass { value: con.i4()? }),
+        4 => ConstantPoolEnum::ConstantFloatItem(
+            ConstantFloatClass { value: con.f4()? }),
+        5 => ConstantPoolEnum::ConstantLongItem(
+            ConstantLongClass { value: con.i8()? }),
+        6 => ConstantPoolEnum::ConstantDoubleItem(
+            ConstantDoubleClass { value: con.f8()? }),
         7 => ConstantPoolEnum::ConstantClassItem(
-            ConstantClassClass { name_index: con.read_u16::<BigEndian>().unwrap() }),
+            ConstantClassClass { name_index: Utf8Index(con.u2()?) }),
         8 => ConstantPoolEnum::ConstantStringItem(
-            ConstantStringClass { string_index: con.read_u16::<BigEndian>().unwrap() }),
+            ConstantStringClass { string_index: Utf8Index(con.u2()?) }),
         9 => ConstantPoolEnum::ConstantFieldrefItem(
             ConstantFieldrefClass { 
-                class_index: con.read_u16::<BigEndian>().unwrap(),
-                name_and_type_index: con.read_u16::<BigEndian>().unwrap() }),
+                class_index: ClassIndex(con.u2()?),
+                name_and_type_index: NameAndTypeIndex(con.u2()?) }),
         10 => ConstantPoolEnum::ConstantMethodrefItem(
             ConstantMethodrefClass {
-                class_index: con.read_u16::<BigEndian>().unwrap(),
-                name_and_type_index: con.read_u16::<BigEndian>().unwrap() }),
+                class_index: ClassIndex(con.u2()?),
+                name_and_type_index: NameAndTypeIndex(con.u2()?) }),
+        11 => ConstantPoolEnum::ConstantInterfaceMethodrefItem(
+            ConstantInterfaceMethodrefClass {
+                class_index: ClassIndex(con.u2()?),
+                name_and_type_index: NameAndTypeIndex(con.u2()?) }),
         12 => ConstantPoolEnum::ConstantNameAndTypeItem(
             ConstantNameAndTypeClass {
-                name_index: con.read_u16::<BigEndian>().unwrap(),
-                descriptor_index: con.read_u16::<BigEndian>().unwrap() }),
-        _ => panic!("Something went wrong!"),
+                name_index: Utf8Index(con.u2()?),
+                descriptor_index: Utf8Index(con.u2()?) }),
+        15 => ConstantPoolEnum::ConstantMethodHandleItem(
+            ConstantMethodHandleClass {
+                reference_kind: con.u1()?,
+                reference_index: MemberRefIndex(con.u2()?) }),
+        16 => ConstantPoolEnum::ConstantMethodTypeItem(
+            ConstantMethodTypeClass { descriptor_index: Utf8Index(con.u2()?) }),
+        17 => ConstantPoolEnum::ConstantDynamicItem(
+            ConstantDynamicClass {
+                bootstrap_method_attr_index: con.u2()?,
+                name_and_type_index: NameAndTypeIndex(con.u2()?) }),
+        18 => ConstantPoolEnum::ConstantInvokeDynamicItem(
+            ConstantInvokeDynamicClass {
+                bootstrap_method_attr_index: con.u2()?,
+                name_and_type_index: NameAndTypeIndex(con.u2()?) }),
+        19 => ConstantPoolEnum::ConstantModuleItem(
+            ConstantModuleClass { name_index: Utf8Index(con.u2()?) }),
+        20 => ConstantPoolEnum::ConstantPackageItem(
+            ConstantPackageClass { name_index: Utf8Index(con.u2()?) }),
+        _ => return Err(ClassFormatError::BadConstantTag { offset, tag }),
     };
-    return info;
+    Ok(cp_info)
+}
+
+fn read_field_info(con: &mut ClassReader, constant_pool: &ConstantPool) -> Result<FieldInfo, ClassFormatError> {
+    let access_flags = con.u2()?;
+    let offset = con.offset;
+    let name_index = Utf8Index(con.u2()?);
+    let name = utf8(constant_pool, name_index, offset)?;
+    info!("Read name {}", name);
+    let offset = con.offset;
+    let descriptor_index = Utf8Index(con.u2()?);
+    let descriptor = utf8(constant_pool, descriptor_index, offset)?;
+    info!("Read descriptor {}", descriptor);
+    let attributes_count = con.u2()?;
+    let attributes: Vec<AttributeEnum> = (0..attributes_count)
+        .map(|_x| read_attribute(con, constant_pool, 0))
+        .collect::<Result<Vec<AttributeEnum>, ClassFormatError>>()?;
+    Ok(FieldInfo {
+        access_flags,
+        name_index,
+        name,
+        descriptor_index,
+        descriptor,
+        attributes_count,
+        attributes
+    })
 }
 
-fn read_method_info(con: &mut io::BufReader<File>, constant_pool: &Vec<ConstantPoolEnum>) -> MethodInfo {
-    let access_flags = con.read_u16::<BigEndian>().unwrap();
+fn read_method_info(con: &mut ClassReader, constant_pool: &ConstantPool) -> Result<MethodInfo, ClassFormatError> {
+    let access_flags = con.u2()?;
     info!("Read access_flag");
-    let name_index = con.read_u16::<BigEndian>().unwrap();
+    let offset = con.offset;
+    let name_index = Utf8Index(con.u2()?);
     info!("Read name_index");
-    let name_index_utf8: &ConstantPoolEnum = &constant_pool[(name_index - 1) as usize];
-    let name = match name_index_utf8 {
-        ConstantPoolEnum::ConstantUtf8Item(x) => x.bytes.clone(),
-        _ => panic!("Something went wrong!"),
-    };
+    let name = utf8(constant_pool, name_index, offset)?;
     info!("Read name {}", name);
-    let descriptor_index = con.read_u16::<BigEndian>().unwrap();
-        let descriptor_index_utf8: &ConstantPoolEnum = &constant_pool[(descriptor_index - 1) as usize];
-    let descriptor = match descriptor_index_utf8 {
-        ConstantPoolEnum::ConstantUtf8Item(x) => x.bytes.clone(),
-        _ => panic!("Something went wrong!"),
-    };
+    let offset = con.offset;
+    let descriptor_index = Utf8Index(con.u2()?);
+    let descriptor = utf8(constant_pool, descriptor_index, offset)?;
     info!("Read descriptor {}", descriptor);
-    let attributes_count = con.read_u16::<BigEndian>().unwrap();
+    let attributes_count = con.u2()?;
     info!("Read attributes_count {}", attributes_count);
     let attributes: Vec<AttributeEnum> = (0..attributes_count)
-        .map(|_x| read_attribute(con, &constant_pool))
-        .collect();
+        .map(|_x| read_attribute(con, constant_pool, 0))
+        .collect::<Result<Vec<AttributeEnum>, ClassFormatError>>()?;
     info!("Read attributes");
-        MethodInfo {
-        access_flags: access_flags,
-        name_index: name_index,
-        name: name,
-        descriptor_index: descriptor_index,
-        descriptor: descriptor,
-        attributes_count: attributes_count,
-        attributes: attributes
+    Ok(MethodInfo {
+        access_flags,
+        name_index,
+        name,
+        descriptor_index,
+        descriptor,
+        attributes_count,
+        attributes
+    })
+}
+
+fn read_line_number_table(con: &mut ClassReader) -> Result<LineNumberTableElem, ClassFormatError> {
+    Ok(LineNumberTableElem {
+        start_pc: con.u2()?,
+        line_number: con.u2()?,
+    })
+}
+
+fn read_exception_table_entry(con: &mut ClassReader, constant_pool: &ConstantPool) -> Result<ExceptionTableEntry, ClassFormatError> {
+    let start_pc = con.u2()?;
+    let end_pc = con.u2()?;
+    let handler_pc = con.u2()?;
+    let offset = con.offset;
+    let catch_type = ClassIndex(con.u2()?);
+    let catch_type_name = optional_class_name(constant_pool, catch_type, offset)?;
+    Ok(ExceptionTableEntry {
+        start_pc,
+        end_pc,
+        handler_pc,
+        catch_type,
+        catch_type_name,
+    })
+}
+
+fn read_inner_class(con: &mut ClassReader, constant_pool: &ConstantPool) -> Result<InnerClassesElem, ClassFormatError> {
+    let offset = con.offset;
+    let inner_class_info_index = ClassIndex(con.u2()?);
+    let inner_class_name = class_name(constant_pool, inner_class_info_index, offset)?;
+    let offset = con.offset;
+    let outer_class_info_index = ClassIndex(con.u2()?);
+    let outer_class_name = optional_class_name(constant_pool, outer_class_info_index, offset)?;
+    let offset = con.offset;
+    let inner_name_index = Utf8Index(con.u2()?);
+    let inner_name = optional_utf8(constant_pool, inner_name_index, offset)?;
+    Ok(InnerClassesElem {
+        inner_class_info_index,
+        inner_class_name,
+        outer_class_info_index,
+        outer_class_name,
+        inner_name_index,
+        inner_name,
+        inner_class_access_flags: con.u2()?,
+    })
+}
+
+fn read_local_variable(con: &mut ClassReader, constant_pool: &ConstantPool) -> Result<LocalVariableTableElem, ClassFormatError> {
+    let start_pc = con.u2()?;
+    let length = con.u2()?;
+    let offset = con.offset;
+    let name_index = Utf8Index(con.u2()?);
+    let name = utf8(constant_pool, name_index, offset)?;
+    let offset = con.offset;
+    let descriptor_index = Utf8Index(con.u2()?);
+    let descriptor = utf8(constant_pool, descriptor_index, offset)?;
+    Ok(LocalVariableTableElem {
+        start_pc,
+        length,
+        name_index,
+        name,
+        descriptor_index,
+        descriptor,
+        index: con.u2()?,
+    })
+}
+
+fn read_local_variable_type(con: &mut ClassReader, constant_pool: &ConstantPool) -> Result<LocalVariableTypeTableElem, ClassFormatError> {
+    let start_pc = con.u2()?;
+    let length = con.u2()?;
+    let offset = con.offset;
+    let name_index = Utf8Index(con.u2()?);
+    let name = utf8(constant_pool, name_index, offset)?;
+    let offset = con.offset;
+    let signature_index = Utf8Index(con.u2()?);
+    let signature = utf8(constant_pool, signature_index, offset)?;
+    Ok(LocalVariableTypeTableElem {
+        start_pc,
+        length,
+        name_index,
+        name,
+        signature_index,
+        signature,
+        index: con.u2()?,
+    })
+}
+
+fn read_bootstrap_method(con: &mut ClassReader) -> Result<BootstrapMethodElem, ClassFormatError> {
+    let bootstrap_method_ref = con.u2()?;
+    let num_bootstrap_arguments = con.u2()?;
+    let bootstrap_arguments: Vec<u16> = (0..num_bootstrap_arguments)
+        .map(|_x| con.u2())
+        .collect::<Result<Vec<u16>, ClassFormatError>>()?;
+    Ok(BootstrapMethodElem {
+        bootstrap_method_ref,
+        num_bootstrap_arguments,
+        bootstrap_arguments,
+    })
+}
+
+fn read_method_parameter(con: &mut ClassReader, constant_pool: &ConstantPool) -> Result<MethodParameterElem, ClassFormatError> {
+    let offset = con.offset;
+    let name_index = Utf8Index(con.u2()?);
+    Ok(MethodParameterElem {
+        name_index,
+        name: optional_utf8(constant_pool, name_index, offset)?,
+        access_flags: con.u2()?,
+    })
+}
+
+// `depth` is the number of attributes the component is nested in
+fn read_record_component_info(con: &mut ClassReader, constant_pool: &ConstantPool, depth: usize)
+    -> Result<RecordComponentInfo, ClassFormatError> {
+    let offset = con.offset;
+    let name_index = Utf8Index(con.u2()?);
+    let name = utf8(constant_pool, name_index, offset)?;
+    let offset = con.offset;
+    let descriptor_index = Utf8Index(con.u2()?);
+    let descriptor = utf8(constant_pool, descriptor_index, offset)?;
+    let attributes_count = con.u2()?;
+    let attributes: Vec<AttributeEnum> = (0..attributes_count)
+        .map(|_x| read_attribute(con, constant_pool, depth))
+        .collect::<Result<Vec<AttributeEnum>, ClassFormatError>>()?;
+    Ok(RecordComponentInfo {
+        name_index,
+        name,
+        descriptor_index,
+        descriptor,
+        attributes_count,
+        attributes,
+    })
+}
+
+fn read_annotations(con: &mut ClassReader, constant_pool: &ConstantPool,
+    attribute_name_index: Utf8Index, attribute_name: &str, attribute_length: u32)
+    -> Result<AnnotationsClass, ClassFormatError> {
+    let num_annotations = con.u2()?;
+    let annotations: Vec<Annotation> = (0..num_annotations)
+        .map(|_x| read_annotation(con, constant_pool, 0))
+        .collect::<Result<Vec<Annotation>, ClassFormatError>>()?;
+    Ok(AnnotationsClass {
+        attribute_name_index,
+        attribute_name: attribute_name.to_string(),
+        attribute_length,
+        num_annotations,
+        annotations,
+    })
+}
+
+fn read_parameter_annotations(con: &mut ClassReader, constant_pool: &ConstantPool,
+    attribute_name_index: Utf8Index, attribute_name: &str, attribute_length: u32)
+    -> Result<ParameterAnnotationsClass, ClassFormatError> {
+    let num_parameters = con.u1()?;
+    let mut parameter_annotations: Vec<ParameterAnnotations> = vec![];
+    for _ in 0..num_parameters {
+        let num_annotations = con.u2()?;
+        let annotations: Vec<Annotation> = (0..num_annotations)
+            .map(|_x| read_annotation(con, constant_pool, 0))
+            .collect::<Result<Vec<Annotation>, ClassFormatError>>()?;
+        parameter_annotations.push(ParameterAnnotations { num_annotations, annotations });
     }
+    Ok(ParameterAnnotationsClass {
+        attribute_name_index,
+        attribute_name: attribute_name.to_string(),
+        attribute_length,
+        num_parameters,
+        parameter_annotations,
+    })
 }
 
-fn read_line_number_table(con: &mut io::BufReader<File>) -> LineNumberTableElem {
-    LineNumberTableElem {
-        start_pc: con.read_u16::<BigEndian>().unwrap(),
-        line_number: con.read_u16::<BigEndian>().unwrap(),
+fn read_type_annotations(con: &mut ClassReader, constant_pool: &ConstantPool,
+    attribute_name_index: Utf8Index, attribute_name: &str, attribute_length: u32)
+    -> Result<TypeAnnotationsClass, ClassFormatError> {
+    let num_annotations = con.u2()?;
+    let annotations: Vec<TypeAnnotation> = (0..num_annotations)
+        .map(|_x| read_type_annotation(con, constant_pool))
+        .collect::<Result<Vec<TypeAnnotation>, ClassFormatError>>()?;
+    Ok(TypeAnnotationsClass {
+        attribute_name_index,
+        attribute_name: attribute_name.to_string(),
+        attribute_length,
+        num_annotations,
+        annotations,
+    })
+}
+
+// `depth` is the number of element values the annotation is nested in
+fn read_annotation(con: &mut ClassReader, constant_pool: &ConstantPool, depth: usize)
+    -> Result<Annotation, ClassFormatError> {
+    let offset = con.offset;
+    let type_index = Utf8Index(con.u2()?);
+    let type_name = utf8(constant_pool, type_index, offset)?;
+    let num_element_value_pairs = con.u2()?;
+    let mut element_value_pairs: Vec<ElementValuePair> = vec![];
+    for _ in 0..num_element_value_pairs {
+        let offset = con.offset;
+        let element_name_index = Utf8Index(con.u2()?);
+        element_value_pairs.push(ElementValuePair {
+            element_name_index,
+            element_name: utf8(constant_pool, element_name_index, offset)?,
+            value: read_element_value(con, constant_pool, depth)?,
+        });
     }
+    Ok(Annotation {
+        type_index,
+        type_name,
+        num_element_value_pairs,
+        element_value_pairs,
+    })
 }
 
-fn read_attribute(con: &mut io::BufReader<File>, constant_pool: &Vec<ConstantPoolEnum>) -> AttributeEnum {
-    let attribute_name_index = con.read_u16::<BigEndian>().unwrap();
-    info!("Read attribute_name_index {}", attribute_name_index);
-    let attribute_length = con.read_u32::<BigEndian>().unwrap();
-    info!("Read attribute_length {}", attribute_length);
-    // TODO This pattern appears a lot - refactor?
-    let attribute_name_index_utf8: &ConstantPoolEnum = &constant_pool[(attribute_name_index - 1) as usize];
-    let attribute_name: String = match attribute_name_index_utf8 {
-        ConstantPoolEnum::ConstantUtf8Item(x) => x.bytes.clone(),
-        _ => panic!("Something went wrong!"),
+// Element values nest through annotations and arrays, and a class file
+// could nest them deeply enough to overflow the stack
+const MAX_ELEMENT_VALUE_DEPTH: usize = 256;
+
+fn read_element_value(con: &mut ClassReader, constant_pool: &ConstantPool, depth: usize)
+    -> Result<ElementValue, ClassFormatError> {
+    let offset = con.offset;
+    if depth >= MAX_ELEMENT_VALUE_DEPTH {
+        return Err(ClassFormatError::ElementValueTooDeep { offset });
+    }
+    let tag = con.u1()?;
+    let element_value = match tag {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' =>
+            ElementValue::Const { tag, const_value_index: con.u2()? },
+        b'e' => {
+            let offset = con.offset;
+            let type_name_index = Utf8Index(con.u2()?);
+            let type_name = utf8(constant_pool, type_name_index, offset)?;
+            let offset = con.offset;
+            let const_name_index = Utf8Index(con.u2()?);
+            let const_name = utf8(constant_pool, const_name_index, offset)?;
+            ElementValue::Enum { type_name_index, type_name, const_name_index, const_name }
+        },
+        b'c' => {
+            let offset = con.offset;
+            let class_info_index = Utf8Index(con.u2()?);
+            ElementValue::Class { class_info_index, class_info: utf8(constant_pool, class_info_index, offset)? }
+        },
+        b'@' => ElementValue::Annotation(read_annotation(con, constant_pool, depth + 1)?),
+        b'[' => {
+            let num_values = con.u2()?;
+            let values: Vec<ElementValue> = (0..num_values)
+                .map(|_x| read_element_value(con, constant_pool, depth + 1))
+                .collect::<Result<Vec<ElementValue>, ClassFormatError>>()?;
+            ElementValue::Array { num_values, values }
+        },
+        _ => return Err(ClassFormatError::BadElementValueTag { offset, tag }),
+    };
+    Ok(element_value)
+}
+
+fn read_type_annotation(con: &mut ClassReader, constant_pool: &ConstantPool) -> Result<TypeAnnotation, ClassFormatError> {
+    let offset = con.offset;
+    let target_type = con.u1()?;
+    let target_info = match target_type {
+        0x00 | 0x01 => TargetInfo::TypeParameter { type_parameter_index: con.u1()? },
+        0x10 => TargetInfo::Supertype { supertype_index: con.u2()? },
+        0x11 | 0x12 => TargetInfo::TypeParameterBound {
+            type_parameter_index: con.u1()?,
+            bound_index: con.u1()?,
+        },
+        0x13..=0x15 => TargetInfo::Empty,
+        0x16 => TargetInfo::FormalParameter { formal_parameter_index: con.u1()? },
+        0x17 => TargetInfo::Throws { throws_type_index: con.u2()? },
+        0x40 | 0x41 => {
+            let table_length = con.u2()?;
+            let mut table: Vec<LocalvarTargetElem> = vec![];
+            for _ in 0..table_length {
+                table.push(LocalvarTargetElem {
+                    start_pc: con.u2()?,
+                    length: con.u2()?,
+                    index: con.u2()?,
+                });
+            }
+            TargetInfo::Localvar { table_length, table }
+        },
+        0x42 => TargetInfo::Catch { exception_table_index: con.u2()? },
+        0x43..=0x46 => TargetInfo::Offset { offset: con.u2()? },
+        0x47..=0x4b => TargetInfo::TypeArgument {
+            offset: con.u2()?,
+            type_argument_index: con.u1()?,
+        },
+        _ => return Err(ClassFormatError::BadTargetType { offset, target_type }),
     };
+    let path_length = con.u1()?;
+    let mut path: Vec<TypePathElem> = vec![];
+    for _ in 0..path_length {
+        path.push(TypePathElem {
+            type_path_kind: con.u1()?,
+            type_argument_index: con.u1()?,
+        });
+    }
+    Ok(TypeAnnotation {
+        target_type,
+        target_info,
+        target_path: TypePath { path_length, path },
+        annotation: read_annotation(con, constant_pool, 0)?,
+    })
+}
+
+fn read_module(con: &mut ClassReader, constant_pool: &ConstantPool,
+    attribute_name_index: Utf8Index, attribute_name: &str, attribute_length: u32)
+    -> Result<ModuleClass, ClassFormatError> {
+    let offset = con.offset;
+    let module_name_index = ModuleIndex(con.u2()?);
+    let name = module_name(constant_pool, module_name_index, offset)?;
+    info!("Read module_name {}", name);
+    let module_flags = con.u2()?;
+    let offset = con.offset;
+    let module_version_index = Utf8Index(con.u2()?);
+    let module_version = optional_utf8(constant_pool, module_version_index, offset)?;
+
+    let requires_count = con.u2()?;
+    let mut requires: Vec<ModuleRequires> = vec![];
+    for _ in 0..requires_count {
+        let offset = con.offset;
+        let requires_index = ModuleIndex(con.u2()?);
+        let requires_name = module_name(constant_pool, requires_index, offset)?;
+        let requires_flags = con.u2()?;
+        let offset = con.offset;
+        let requires_version_index = Utf8Index(con.u2()?);
+        requires.push(ModuleRequires {
+            requires_index,
+            requires_name,
+            requires_flags,
+            requires_version_index,
+            requires_version: optional_utf8(constant_pool, requires_version_index, offset)?,
+        });
+    }
+
+    let exports_count = con.u2()?;
+    let mut exports: Vec<ModuleExports> = vec![];
+    for _ in 0..exports_count {
+        let offset = con.offset;
+        let exports_index = PackageIndex(con.u2()?);
+        let exports_name = package_name(constant_pool, exports_index, offset)?;
+        let exports_flags = con.u2()?;
+        let exports_to_count = con.u2()?;
+        let (exports_to_index, exports_to_names) = read_names(con, constant_pool, exports_to_count, ModuleIndex, module_name)?;
+        exports.push(ModuleExports {
+            exports_index,
+            exports_name,
+            exports_flags,
+            exports_to_count,
+            exports_to_index,
+            exports_to_names,
+        });
+    }
+
+    let opens_count = con.u2()?;
+    let mut opens: Vec<ModuleOpens> = vec![];
+    for _ in 0..opens_count {
+        let offset = con.offset;
+        let opens_index = PackageIndex(con.u2()?);
+        let opens_name = package_name(constant_pool, opens_index, offset)?;
+        let opens_flags = con.u2()?;
+        let opens_to_count = con.u2()?;
+        let (opens_to_index, opens_to_names) = read_names(con, constant_pool, opens_to_count, ModuleIndex, module_name)?;
+        opens.push(ModuleOpens {
+            opens_index,
+            opens_name,
+            opens_flags,
+            opens_to_count,
+            opens_to_index,
+            opens_to_names,
+        });
+    }
+
+    let uses_count = con.u2()?;
+    let (uses_index, uses_names) = read_classes(con, constant_pool, uses_count)?;
+
+    let provides_count = con.u2()?;
+    let mut provides: Vec<ModuleProvides> = vec![];
+    for _ in 0..provides_count {
+        let offset = con.offset;
+        let provides_index = ClassIndex(con.u2()?);
+        let provides_name = class_name(constant_pool, provides_index, offset)?;
+        let provides_with_count = con.u2()?;
+        let (provides_with_index, provides_with_names) = read_classes(con, constant_pool, provides_with_count)?;
+        provides.push(ModuleProvides {
+            provides_index,
+            provides_name,
+            provides_with_count,
+            provides_with_index,
+            provides_with_names,
+        });
+    }
+
+    Ok(ModuleClass {
+        attribute_name_index,
+        attribute_name: attribute_name.to_string(),
+        attribute_length,
+        module_name_index,
+        module_name: name,
+        module_flags,
+        module_version_index,
+        module_version,
+        requires_count,
+        requires,
+        exports_count,
+        exports,
+        opens_count,
+        opens,
+        uses_count,
+        uses_index,
+        uses_names,
+        provides_count,
+        provides,
+    })
+}
+
+fn read_stack_map_frame(con: &mut ClassReader, constant_pool: &ConstantPool) -> Result<StackMapFrame, ClassFormatError> {
+    let offset = con.offset;
+    let frame_type = con.u1()?;
+    let frame = match frame_type {
+        0..=63 => StackMapFrame::SameFrame { frame_type },
+        64..=127 => StackMapFrame::SameLocals1StackItemFrame {
+            frame_type,
+            stack: read_verification_type_info(con, constant_pool)?,
+        },
+        247 => StackMapFrame::SameLocals1StackItemFrameExtended {
+            frame_type,
+            offset_delta: con.u2()?,
+            stack: read_verification_type_info(con, constant_pool)?,
+        },
+        248..=250 => StackMapFrame::ChopFrame { frame_type, offset_delta: con.u2()? },
+        251 => StackMapFrame::SameFrameExtended { frame_type, offset_delta: con.u2()? },
+        252..=254 => {
+            let offset_delta = con.u2()?;
+            let locals: Vec<VerificationTypeInfo> = (0..frame_type - 251)
+                .map(|_x| read_verification_type_info(con, constant_pool))
+                .collect::<Result<Vec<VerificationTypeInfo>, ClassFormatError>>()?;
+            StackMapFrame::AppendFrame { frame_type, offset_delta, locals }
+        },
+        255 => {
+            let offset_delta = con.u2()?;
+            let number_of_locals = con.u2()?;
+            let locals: Vec<VerificationTypeInfo> = (0..number_of_locals)
+                .map(|_x| read_verification_type_info(con, constant_pool))
+                .collect::<Result<Vec<VerificationTypeInfo>, ClassFormatError>>()?;
+            let number_of_stack_items = con.u2()?;
+            let stack: Vec<VerificationTypeInfo> = (0..number_of_stack_items)
+                .map(|_x| read_verification_type_info(con, constant_pool))
+                .collect::<Result<Vec<VerificationTypeInfo>, ClassFormatError>>()?;
+            StackMapFrame::FullFrame {
+                frame_type,
+                offset_delta,
+                number_of_locals,
+                locals,
+                number_of_stack_items,
+                stack,
+            }
+        },
+        // 128-246 are reserved
+        _ => return Err(ClassFormatError::BadStackMapFrame { offset, frame_type }),
+    };
+    Ok(frame)
+}
+
+fn read_verification_type_info(con: &mut ClassReader, constant_pool: &ConstantPool)
+    -> Result<VerificationTypeInfo, ClassFormatError> {
+    let offset = con.offset;
+    let tag = con.u1()?;
+    let verification_type_info = match tag {
+        0 => VerificationTypeInfo::Top,
+        1 => VerificationTypeInfo::Integer,
+        2 => VerificationTypeInfo::Float,
+        3 => VerificationTypeInfo::Double,
+        4 => VerificationTypeInfo::Long,
+        5 => VerificationTypeInfo::Null,
+        6 => VerificationTypeInfo::UninitializedThis,
+        7 => {
+            let offset = con.offset;
+            let cpool_index = ClassIndex(con.u2()?);
+            VerificationTypeInfo::Object {
+                cpool_index,
+                class_name: class_name(constant_pool, cpool_index, offset)?,
+            }
+        },
+        8 => VerificationTypeInfo::Uninitialized { offset: con.u2()? },
+        _ => return Err(ClassFormatError::BadVerificationType { offset, tag }),
+    };
+    Ok(verification_type_info)
+}
+
+// Attributes nest through `Code` and `Record` attributes, which javac only
+// ever does one level deep. Reading one takes far more stack than an element
+// value, so the limit is lower.
+const MAX_ATTRIBUTE_DEPTH: usize = 16;
+
+// `depth` is the number of attributes this one is nested in
+fn read_attribute(con: &mut ClassReader, constant_pool: &ConstantPool, depth: usize)
+    -> Result<AttributeEnum, ClassFormatError> {
+    let offset = con.offset;
+    if depth >= MAX_ATTRIBUTE_DEPTH {
+        return Err(ClassFormatError::AttributeTooDeep { offset });
+    }
+    let attribute_name_index = Utf8Index(con.u2()?);
+    info!("Read attribute_name_index {}", attribute_name_index.0);
+    let attribute_length = con.u4()?;
+    info!("Read attribute_length {}", attribute_length);
+    let attribute_name: String = utf8(constant_pool, attribute_name_index, offset)?;
     info!("Read attribute_name {}", attribute_name);
+    let start = con.offset;
     let attribute = match attribute_name.as_ref() {
         "Code" => {
-            let max_stack = con.read_u16::<BigEndian>().unwrap();
+            let max_stack = con.u2()?;
             info!("Read max_stack: {}", max_stack);
-            let max_locals = con.read_u16::<BigEndian>().unwrap();
+            let max_locals = con.u2()?;
             info!("Read max_locals: {}", max_locals);
-            let code_length = con.read_u32::<BigEndian>().unwrap();
+            let code_length = con.u4()?;
             info!("Read code_length: {}", code_length);
-            let code: Vec<u8> = (0..code_length)
-                .map(|_x| con.read_u8().unwrap())
-                .collect();
+            let code: Vec<u8> = con.bytes(code_length as usize)?;
             info!("Read byte_slice_vec");
-            let exception_table_length = con.read_u16::<BigEndian>().unwrap();
-            // TODO Handle exception table of non-0 length
-            if exception_table_length > 0 {
-                panic!("Exception table of length 1+ not implemented");
-            }
-            let exception_table = ExceptionTable {};
-            let attributes_count = con.read_u16::<BigEndian>().unwrap();
+            let exception_table_length = con.u2()?;
+            info!("Read exception_table_length: {}", exception_table_length);
+            let exception_table: Vec<ExceptionTableEntry> = (0..exception_table_length)
+                .map(|_x| read_exception_table_entry(con, constant_pool))
+                .collect::<Result<Vec<ExceptionTableEntry>, ClassFormatError>>()?;
+            let attributes_count = con.u2()?;
             let attributes: Vec<AttributeEnum> = (0..attributes_count)
-                .map(|_x| read_attribute(con, &constant_pool))
-                .collect();
+                .map(|_x| read_attribute(con, constant_pool, depth + 1))
+                .collect::<Result<Vec<AttributeEnum>, ClassFormatError>>()?;
             info!("About to create CodeItem");
             AttributeEnum::CodeItem(
                 CodeClass {
-                    attribute_name_index: attribute_name_index,
-                    attribute_name: attribute_name,
-                    attribute_length: attribute_length,
-                    max_stack: max_stack,
-                    max_locals: max_locals,
-                    code_length: code_length,
-                    code: code,
-                    exception_table_length: exception_table_length,
-                    exception_table: exception_table,
-                    attributes_count: attributes_count,
-                    attributes: attributes
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    max_stack,
+                    max_locals,
+                    code_length,
+                    code,
+                    exception_table_length,
+                    exception_table,
+                    attributes_count,
+                    attributes
                 })
         },
         "LineNumberTable" => {
-            let line_number_table_length = con.read_u16::<BigEndian>().unwrap();
+            let line_number_table_length = con.u2()?;
             let line_number_table: Vec<LineNumberTableElem> = (0..line_number_table_length)
                 .map(|_x| read_line_number_table(con))
-                .collect();
+                .collect::<Result<Vec<LineNumberTableElem>, ClassFormatError>>()?;
             AttributeEnum::LineNumberTableItem(
                 LineNumberTableClass {
-                    attribute_name_index: attribute_name_index,
-                    attribute_name: attribute_name,
-                    attribute_length: attribute_length,
-                    line_number_table_length: line_number_table_length,
-                    line_number_table: line_number_table
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    line_number_table_length,
+                    line_number_table
                 })
         },
         "StackMapTable" => {
-            // TODO Parse stack_map_frame
-            let _: Vec<u16> = (0..attribute_length)
-                .map(|_x| con.read_u16::<BigEndian>().unwrap())
-                .collect();
+            let number_of_entries = con.u2()?;
+            info!("Read number_of_entries: {}", number_of_entries);
+            let entries: Vec<StackMapFrame> = (0..number_of_entries)
+                .map(|_x| read_stack_map_frame(con, constant_pool))
+                .collect::<Result<Vec<StackMapFrame>, ClassFormatError>>()?;
             AttributeEnum::StackMapTableItem(
                 StackMapTableClass {
-                    attribute_name_index: attribute_name_index,
-                    attribute_name: attribute_name,
-                    attribute_length: attribute_length
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    number_of_entries,
+                    entries
                 })
         },
         "SourceFile" => 
             AttributeEnum::SourceFileItem(
                 SourceFileClass {
-                    attribute_name_index: attribute_name_index,
-                    attribute_name: attribute_name,
-                    sourcefile_index: con.read_u16::<BigEndian>().unwrap()
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    sourcefile_index: Utf8Index(con.u2()?)
+            }),
+        "ConstantValue" =>
+            AttributeEnum::ConstantValueItem(
+                ConstantValueClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    constantvalue_index: con.u2()?
+            }),
+        "Exceptions" => {
+            let number_of_exceptions = con.u2()?;
+            let (exception_index_table, exception_names) = read_classes(con, constant_pool, number_of_exceptions)?;
+            AttributeEnum::ExceptionsItem(
+                ExceptionsClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    number_of_exceptions,
+                    exception_index_table,
+                    exception_names
+                })
+        },
+        "InnerClasses" => {
+            let number_of_classes = con.u2()?;
+            let classes: Vec<InnerClassesElem> = (0..number_of_classes)
+                .map(|_x| read_inner_class(con, constant_pool))
+                .collect::<Result<Vec<InnerClassesElem>, ClassFormatError>>()?;
+            AttributeEnum::InnerClassesItem(
+                InnerClassesClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    number_of_classes,
+                    classes
+                })
+        },
+        "EnclosingMethod" => {
+            let offset = con.offset;
+            let class_index = ClassIndex(con.u2()?);
+            let class_name = class_name(constant_pool, class_index, offset)?;
+            let offset = con.offset;
+            let method_index = NameAndTypeIndex(con.u2()?);
+            let (method_name, method_descriptor) = match method_index {
+                NameAndTypeIndex(0) => (None, None),
+                _ => {
+                    let (name, descriptor) = name_and_type(constant_pool, method_index, offset)?;
+                    (Some(name), Some(descriptor))
+                },
+            };
+            AttributeEnum::EnclosingMethodItem(
+                EnclosingMethodClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    class_index,
+                    class_name,
+                    method_index,
+                    method_name,
+                    method_descriptor
+                })
+        },
+        "Signature" => {
+            let offset = con.offset;
+            let signature_index = Utf8Index(con.u2()?);
+            AttributeEnum::SignatureItem(
+                SignatureClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    signature_index,
+                    signature: utf8(constant_pool, signature_index, offset)?
+                })
+        },
+        "LocalVariableTable" => {
+            let local_variable_table_length = con.u2()?;
+            let local_variable_table: Vec<LocalVariableTableElem> = (0..local_variable_table_length)
+                .map(|_x| read_local_variable(con, constant_pool))
+                .collect::<Result<Vec<LocalVariableTableElem>, ClassFormatError>>()?;
+            AttributeEnum::LocalVariableTableItem(
+                LocalVariableTableClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    local_variable_table_length,
+                    local_variable_table
+                })
+        },
+        "LocalVariableTypeTable" => {
+            let local_variable_type_table_length = con.u2()?;
+            let local_variable_type_table: Vec<LocalVariableTypeTableElem> = (0..local_variable_type_table_length)
+                .map(|_x| read_local_variable_type(con, constant_pool))
+                .collect::<Result<Vec<LocalVariableTypeTableElem>, ClassFormatError>>()?;
+            AttributeEnum::LocalVariableTypeTableItem(
+                LocalVariableTypeTableClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    local_variable_type_table_length,
+                    local_variable_type_table
+                })
+        },
+        "BootstrapMethods" => {
+            let num_bootstrap_methods = con.u2()?;
+            let bootstrap_methods: Vec<BootstrapMethodElem> = (0..num_bootstrap_methods)
+                .map(|_x| read_bootstrap_method(con))
+                .collect::<Result<Vec<BootstrapMethodElem>, ClassFormatError>>()?;
+            AttributeEnum::BootstrapMethodsItem(
+                BootstrapMethodsClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    num_bootstrap_methods,
+                    bootstrap_methods
+                })
+        },
+        "MethodParameters" => {
+            let parameters_count = con.u1()?;
+            let parameters: Vec<MethodParameterElem> = (0..parameters_count)
+                .map(|_x| read_method_parameter(con, constant_pool))
+                .collect::<Result<Vec<MethodParameterElem>, ClassFormatError>>()?;
+            AttributeEnum::MethodParametersItem(
+                MethodParametersClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    parameters_count,
+                    parameters
+                })
+        },
+        "NestHost" => {
+            let offset = con.offset;
+            let host_class_index = ClassIndex(con.u2()?);
+            AttributeEnum::NestHostItem(
+                NestHostClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    host_class_index,
+                    host_class_name: class_name(constant_pool, host_class_index, offset)?
+                })
+        },
+        "NestMembers" => {
+            let number_of_classes = con.u2()?;
+            let (classes, class_names) = read_classes(con, constant_pool, number_of_classes)?;
+            AttributeEnum::NestMembersItem(
+                NestMembersClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    number_of_classes,
+                    classes,
+                    class_names
+                })
+        },
+        "Record" => {
+            let components_count = con.u2()?;
+            let components: Vec<RecordComponentInfo> = (0..components_count)
+                .map(|_x| read_record_component_info(con, constant_pool, depth + 1))
+                .collect::<Result<Vec<RecordComponentInfo>, ClassFormatError>>()?;
+            AttributeEnum::RecordItem(
+                RecordClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    components_count,
+                    components
+                })
+        },
+        "PermittedSubclasses" => {
+            let number_of_classes = con.u2()?;
+            let (classes, class_names) = read_classes(con, constant_pool, number_of_classes)?;
+            AttributeEnum::PermittedSubclassesItem(
+                PermittedSubclassesClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    number_of_classes,
+                    classes,
+                    class_names
+                })
+        },
+        "Deprecated" =>
+            AttributeEnum::DeprecatedItem(
+                DeprecatedClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length
+            }),
+        "Synthetic" =>
+            AttributeEnum::SyntheticItem(
+                SyntheticClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length
+            }),
+        "SourceDebugExtension" =>
+            AttributeEnum::SourceDebugExtensionItem(
+                SourceDebugExtensionClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    debug_extension: con.bytes(attribute_length as usize)?
+            }),
+        "RuntimeVisibleAnnotations" =>
+            AttributeEnum::RuntimeVisibleAnnotationsItem(
+                read_annotations(con, constant_pool, attribute_name_index, &attribute_name, attribute_length)?),
+        "RuntimeInvisibleAnnotations" =>
+            AttributeEnum::RuntimeInvisibleAnnotationsItem(
+                read_annotations(con, constant_pool, attribute_name_index, &attribute_name, attribute_length)?),
+        "RuntimeVisibleParameterAnnotations" =>
+            AttributeEnum::RuntimeVisibleParameterAnnotationsItem(
+                read_parameter_annotations(con, constant_pool, attribute_name_index, &attribute_name, attribute_length)?),
+        "RuntimeInvisibleParameterAnnotations" =>
+            AttributeEnum::RuntimeInvisibleParameterAnnotationsItem(
+                read_parameter_annotations(con, constant_pool, attribute_name_index, &attribute_name, attribute_length)?),
+        "RuntimeVisibleTypeAnnotations" =>
+            AttributeEnum::RuntimeVisibleTypeAnnotationsItem(
+                read_type_annotations(con, constant_pool, attribute_name_index, &attribute_name, attribute_length)?),
+        "RuntimeInvisibleTypeAnnotations" =>
+            AttributeEnum::RuntimeInvisibleTypeAnnotationsItem(
+                read_type_annotations(con, constant_pool, attribute_name_index, &attribute_name, attribute_length)?),
+        "AnnotationDefault" =>
+            AttributeEnum::AnnotationDefaultItem(
+                AnnotationDefaultClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    default_value: read_element_value(con, constant_pool, 0)?
+            }),
+        "Module" => AttributeEnum::ModuleItem(
+            read_module(con, constant_pool, attribute_name_index, &attribute_name, attribute_length)?),
+        "ModulePackages" => {
+            let package_count = con.u2()?;
+            let (package_index, package_names) = read_names(con, constant_pool, package_count, PackageIndex, package_name)?;
+            AttributeEnum::ModulePackagesItem(
+                ModulePackagesClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    package_count,
+                    package_index,
+                    package_names
+                })
+        },
+        "ModuleMainClass" => {
+            let offset = con.offset;
+            let main_class_index = ClassIndex(con.u2()?);
+            AttributeEnum::ModuleMainClassItem(
+                ModuleMainClassClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    main_class_index,
+                    main_class_name: class_name(constant_pool, main_class_index, offset)?
+                })
+        },
+        _ =>
+            AttributeEnum::UnknownItem(
+                UnknownClass {
+                    attribute_name_index,
+                    attribute_name: attribute_name.clone(),
+                    attribute_length,
+                    bytes: con.bytes(attribute_length as usize)?
             }),
-        _ => panic!("Unknown attribute_name"),
     };
-    attribute
+    let actual_length = con.offset - start;
+    if actual_length != attribute_length as usize {
+        return Err(ClassFormatError::BadAttributeLength {
+            offset,
+            name: attribute_name,
+            attribute_length,
+            actual_length,
+        });
+    }
+    Ok(attribute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_WORLD: &[u8] = include_bytes!("../HelloWorld.class");
+    const FIXTURE: &[u8] = include_bytes!("../fixtures/Fixture.class");
+    const POINT: &[u8] = include_bytes!("../fixtures/Fixture$Point.class");
+    const MARKER: &[u8] = include_bytes!("../fixtures/Fixture$Marker.class");
+    const MODULE_INFO: &[u8] = include_bytes!("../fixtures/module/module-info.class");
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = HELLO_WORLD.to_vec();
+        bytes[3] = 0xBF;
+        assert!(matches!(parse_bytes(&bytes), Err(ClassFormatError::BadMagic { offset: 0, magic: 0xCAFEBABF })));
+    }
+
+    #[test]
+    fn rejects_bad_constant_tag() {
+        let mut bytes = HELLO_WORLD.to_vec();
+        // The first constant follows the magic, versions and count
+        bytes[10] = 2;
+        assert!(matches!(parse_bytes(&bytes), Err(ClassFormatError::BadConstantTag { offset: 10, tag: 2 })));
+    }
+
+    #[test]
+    fn reports_truncation() {
+        for length in 0..HELLO_WORLD.len() {
+            match parse_bytes(&HELLO_WORLD[..length]) {
+                Err(ClassFormatError::Truncated { offset }) => assert!(offset <= length),
+                other => panic!("{:?} for {} bytes", other.map(|_| ()), length),
+            }
+        }
+    }
+
+    // The class ends with its one attribute, SourceFile, which is replaced
+    // by one claiming 4 GiB of data
+    #[test]
+    fn reports_truncation_of_huge_attribute() {
+        let end = HELLO_WORLD.len() - 8;
+        let mut bytes = HELLO_WORLD[..end].to_vec();
+        bytes.extend_from_slice(&[0, 14, 0xff, 0xff, 0xff, 0xff]);
+        bytes.extend_from_slice(b"abc");
+        assert!(matches!(parse_bytes(&bytes), Err(ClassFormatError::Truncated { offset }) if offset == end + 6));
+    }
+
+    // The SourceFile attribute is replaced by Code attributes, each holding
+    // no code and the next as its only attribute
+    #[test]
+    fn limits_attribute_nesting() {
+        let start = HELLO_WORLD.len() - 8;
+        let mut bytes = HELLO_WORLD[..start].to_vec();
+        for _ in 0..=MAX_ATTRIBUTE_DEPTH {
+            // Constant 9 is "Code"
+            bytes.extend_from_slice(&[0, 9, 0, 0, 0, 0]);
+            bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        }
+        let offset = start + MAX_ATTRIBUTE_DEPTH * 18;
+        assert!(matches!(parse_bytes(&bytes), Err(ClassFormatError::AttributeTooDeep { offset: o }) if o == offset));
+    }
+
+    #[test]
+    fn reads_nothing_past_the_class() {
+        let mut bytes = HELLO_WORLD.to_vec();
+        bytes.extend_from_slice(HELLO_WORLD);
+        bytes.extend_from_slice(b"rest");
+        let mut reader: &[u8] = &bytes;
+        for _ in 0..2 {
+            let class = parse_reader(&mut reader).unwrap();
+            assert_eq!(class.this_class_name, "HelloWorld");
+        }
+        assert_eq!(reader, b"rest");
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        let mut bytes = HELLO_WORLD.to_vec();
+        bytes.push(0);
+        assert!(matches!(parse_bytes(&bytes), Err(ClassFormatError::TrailingBytes { offset }) if offset == HELLO_WORLD.len()));
+    }
+
+    // The indexes are those javap reports for the fixtures
+    #[test]
+    fn parses_every_kind_of_constant() {
+        let class = parse_bytes(FIXTURE).unwrap();
+        let pool = &class.constant_pool;
+        assert!(matches!(pool.get(74), Ok(ConstantPoolEnum::ConstantIntegerItem(x)) if x.value == 0xff0000));
+        assert!(matches!(pool.get(126), Ok(ConstantPoolEnum::ConstantLongItem(x)) if x.value == 1 << 40));
+        assert!(matches!(pool.get(127), Ok(ConstantPoolEnum::ConstantUnusableItem)));
+        assert_eq!(pool.member_ref(MemberRefIndex(16)).unwrap(), ("java/util/List", "iterator", "()Ljava/util/Iterator;"));
+        match pool.get(217) {
+            Ok(ConstantPoolEnum::ConstantMethodHandleItem(x)) => {
+                assert_eq!(x.reference_kind, 6);
+                let (class_name, name, _) = pool.member_ref(x.reference_index).unwrap();
+                assert_eq!((class_name, name), ("java/lang/invoke/LambdaMetafactory", "metafactory"));
+            }
+            _ => panic!("constant 217 is not a MethodHandle"),
+        }
+        match pool.get(224) {
+            Ok(ConstantPoolEnum::ConstantMethodTypeItem(x)) =>
+                assert_eq!(pool.utf8(x.descriptor_index).unwrap(), "(Ljava/lang/Object;)Ljava/lang/Object;"),
+            _ => panic!("constant 224 is not a MethodType"),
+        }
+        match pool.get(76) {
+            Ok(ConstantPoolEnum::ConstantInvokeDynamicItem(x)) => {
+                assert_eq!(x.bootstrap_method_attr_index, 0);
+                assert_eq!(pool.name_and_type(x.name_and_type_index).unwrap(), ("apply", "(I)Ljava/util/function/Function;"));
+            }
+            _ => panic!("constant 76 is not an InvokeDynamic"),
+        }
+
+        let module_info = parse_bytes(MODULE_INFO).unwrap();
+        let pool = &module_info.constant_pool;
+        assert_eq!(pool.module_name(ModuleIndex(11)).unwrap(), "java.logging");
+        assert_eq!(pool.package_name(PackageIndex(13)).unwrap(), "fixture/api");
+    }
+
+    #[test]
+    fn parses_fields_and_interfaces() {
+        let class = parse_bytes(FIXTURE).unwrap();
+        let fields: Vec<(u16, &str, &str)> = class.fields.iter()
+            .map(|field| (field.access_flags, field.name.as_ref(), field.descriptor.as_ref()))
+            .collect();
+        assert_eq!(fields, [
+            (0x0012, "items", "Ljava/util/List;"),
+            (0x0018, "BIG", "J"),
+            (0x0018, "NAME", "Ljava/lang/String;"),
+        ]);
+        assert_eq!(class.fields[0].get_constant_value(), None);
+        let big = class.fields[1].get_constant_value().unwrap();
+        assert!(matches!(class.constant_pool.get(big), Ok(ConstantPoolEnum::ConstantLongItem(x)) if x.value == 1 << 40));
+        let name = class.fields[2].get_constant_value().unwrap();
+        match class.constant_pool.get(name) {
+            Ok(ConstantPoolEnum::ConstantStringItem(x)) =>
+                assert_eq!(class.constant_pool.utf8(x.string_index).unwrap(), "fixture"),
+            _ => panic!("NAME is not a String constant"),
+        }
+        assert!(class.interface_names.is_empty());
+
+        let point = parse_bytes(POINT).unwrap();
+        assert_eq!(point.super_class_name, "java/lang/Record");
+        assert_eq!(point.interface_names, ["java/lang/Comparable"]);
+    }
+
+    // try { return Integer.parseInt(s); }
+    // catch (NumberFormatException e) { return -1; }
+    // finally { System.out.println(s); }
+    #[test]
+    fn parses_exception_tables() {
+        let class = parse_bytes(FIXTURE).unwrap();
+        let code = class.get_method("parse", "(Ljava/lang/String;)I").unwrap().get_code_attribute();
+        let table: Vec<(u16, u16, u16, Option<&str>)> = code.exception_table.iter()
+            .map(|entry| (entry.start_pc, entry.end_pc, entry.handler_pc, entry.catch_type_name.as_deref()))
+            .collect();
+        assert_eq!(table, [
+            (0, 5, 14, Some("java/lang/NumberFormatException")),
+            (0, 5, 26, None),
+            (14, 17, 26, None),
+        ]);
+        assert_eq!(code.exception_table[1].catch_type.0, 0);
+        assert!(code.exception_table[0].covers(4));
+        assert!(!code.exception_table[0].covers(5));
+    }
+
+    fn stack_map_frames<'a>(class: &'a BytecodeClass, name: &str, descriptor: &str) -> &'a [StackMapFrame] {
+        let code = class.get_method(name, descriptor).unwrap().get_code_attribute();
+        code.attributes.iter()
+            .find_map(|attribute| match attribute {
+                AttributeEnum::StackMapTableItem(table) => Some(table.entries.as_ref()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn type_names(types: &[VerificationTypeInfo]) -> Vec<&str> {
+        types.iter()
+            .map(|info| match info {
+                VerificationTypeInfo::Object { class_name, .. } => class_name.as_ref(),
+                VerificationTypeInfo::Integer => "int",
+                VerificationTypeInfo::Long => "long",
+                VerificationTypeInfo::Double => "double",
+                _ => panic!("unexpected verification type {}", info.tag()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_stack_map_frames() {
+        let class = parse_bytes(FIXTURE).unwrap();
+        let frames = stack_map_frames(&class, "average", "([D)D");
+        let kinds: Vec<(u8, u16)> = frames.iter().map(|frame| (frame.frame_type(), frame.offset_delta())).collect();
+        assert_eq!(kinds, [(255, 15), (252, 23), (250, 8), (248, 5), (9, 9), (67, 3)]);
+        match &frames[0] {
+            StackMapFrame::FullFrame { number_of_locals: 6, locals, number_of_stack_items: 0, stack, .. } => {
+                assert_eq!(type_names(locals), ["[D", "double", "long", "[D", "int", "int"]);
+                assert!(stack.is_empty());
+            }
+            _ => panic!("first frame is not a full frame"),
+        }
+        match &frames[1] {
+            StackMapFrame::AppendFrame { locals, .. } => assert_eq!(type_names(locals), ["double"]),
+            _ => panic!("second frame is not an append frame"),
+        }
+        assert!(matches!(frames[2], StackMapFrame::ChopFrame { .. }));
+        assert!(matches!(frames[4], StackMapFrame::SameFrame { .. }));
+        assert!(matches!(frames[5], StackMapFrame::SameLocals1StackItemFrame { stack: VerificationTypeInfo::Double, .. }));
+
+        let frames = stack_map_frames(&class, "parse", "(Ljava/lang/String;)I");
+        match &frames[0] {
+            StackMapFrame::SameLocals1StackItemFrame { frame_type: 78, stack } =>
+                assert_eq!(type_names(std::slice::from_ref(stack)), ["java/lang/NumberFormatException"]),
+            _ => panic!("first frame does not hold the caught exception"),
+        }
+    }
+
+    #[test]
+    fn parses_class_and_member_attributes() {
+        let class = parse_bytes(FIXTURE).unwrap();
+        let signatures: Vec<&str> = class.attributes.iter()
+            .filter_map(|attribute| match attribute {
+                AttributeEnum::SignatureItem(x) => Some(x.signature.as_ref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(signatures, ["<T::Ljava/lang/Comparable<TT;>;>Ljava/lang/Object;"]);
+
+        let inner_classes = class.attributes.iter()
+            .find_map(|attribute| match attribute {
+                AttributeEnum::InnerClassesItem(x) => Some(&x.classes),
+                _ => None,
+            })
+            .unwrap();
+        let names: Vec<(&str, Option<&str>, Option<&str>)> = inner_classes.iter()
+            .map(|x| (x.inner_class_name.as_ref(), x.outer_class_name.as_deref(), x.inner_name.as_deref()))
+            .collect();
+        assert_eq!(names, [
+            ("Fixture$1", None, None),
+            ("Fixture$Color", Some("Fixture"), Some("Color")),
+            ("Fixture$Inner", Some("Fixture"), Some("Inner")),
+            ("Fixture$Point", Some("Fixture"), Some("Point")),
+            ("Fixture$Hidden", Some("Fixture"), Some("Hidden")),
+            ("Fixture$Marker", Some("Fixture"), Some("Marker")),
+            ("java/lang/invoke/MethodHandles$Lookup", Some("java/lang/invoke/MethodHandles"), Some("Lookup")),
+        ]);
+        assert_eq!(inner_classes[2].inner_class_access_flags, 0);
+        assert_eq!(inner_classes[0].outer_class_info_index.0, 0);
+        assert_eq!(inner_classes[0].inner_name_index.0, 0);
+
+        let max = class.get_method("max", "(Ljava/util/Comparator;)Ljava/lang/Comparable;").unwrap();
+        let mut seen = vec![];
+        for attribute in &max.attributes {
+            match attribute {
+                AttributeEnum::ExceptionsItem(x) => {
+                    assert_eq!(x.exception_names, ["java/lang/IllegalStateException"]);
+                    seen.push("Exceptions");
+                }
+                AttributeEnum::MethodParametersItem(x) => {
+                    let names: Vec<Option<&str>> = x.parameters.iter().map(|p| p.name.as_deref()).collect();
+                    assert_eq!(names, [Some("comparator")]);
+                    seen.push("MethodParameters");
+                }
+                AttributeEnum::SignatureItem(x) => {
+                    assert_eq!(x.signature, "(Ljava/util/Comparator<-TT;>;)TT;");
+                    seen.push("Signature");
+                }
+                AttributeEnum::DeprecatedItem(x) => {
+                    assert_eq!(x.attribute_length, 0);
+                    seen.push("Deprecated");
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(seen, ["Exceptions", "MethodParameters", "Deprecated", "Signature"]);
+    }
+
+    fn const_utf8(class: &BytecodeClass, value: &ElementValue) -> String {
+        match value {
+            ElementValue::Const { tag: b's', const_value_index } =>
+                class.constant_pool.utf8(Utf8Index(*const_value_index)).unwrap().to_string(),
+            _ => panic!("element value with tag {} is not a string", value.tag() as char),
+        }
+    }
+
+    // @Marker(name = "fixture", values = {1, 2}, kind = ElementType.TYPE,
+    //         type = String.class, nested = @Deprecated)
+    #[test]
+    fn parses_annotations() {
+        let class = parse_bytes(FIXTURE).unwrap();
+        let marker = class.get_annotations().next().unwrap();
+        assert_eq!(marker.type_name, "LFixture$Marker;");
+        let names: Vec<&str> = marker.element_value_pairs.iter().map(|pair| pair.element_name.as_ref()).collect();
+        assert_eq!(names, ["name", "values", "kind", "type", "nested"]);
+        let values: Vec<&ElementValue> = marker.element_value_pairs.iter().map(|pair| &pair.value).collect();
+        assert_eq!(const_utf8(&class, values[0]), "fixture");
+        match values[1] {
+            ElementValue::Array { num_values: 2, values } =>
+                assert!(values.iter().all(|value| value.tag() == b'I')),
+            _ => panic!("values is not an array of two"),
+        }
+        assert!(matches!(values[2], ElementValue::Enum { type_name, const_name, .. }
+            if type_name == "Ljava/lang/annotation/ElementType;" && const_name == "TYPE"));
+        assert!(matches!(values[3], ElementValue::Class { class_info, .. } if class_info == "Ljava/lang/String;"));
+        assert!(matches!(values[4], ElementValue::Annotation(nested)
+            if nested.type_name == "Ljava/lang/Deprecated;" && nested.element_value_pairs.is_empty()));
+
+        let max = class.get_method("max", "(Ljava/util/Comparator;)Ljava/lang/Comparable;").unwrap();
+        let types: Vec<&str> = max.get_annotations().map(|annotation| annotation.type_name.as_ref()).collect();
+        assert_eq!(types, ["Ljava/lang/Deprecated;", "LFixture$Marker;"]);
+        let mut parameter_annotations: Vec<&str> = vec![];
+        let mut type_annotations = vec![];
+        for attribute in &max.attributes {
+            match attribute {
+                AttributeEnum::RuntimeVisibleParameterAnnotationsItem(x)
+                | AttributeEnum::RuntimeInvisibleParameterAnnotationsItem(x) => {
+                    assert_eq!(x.num_parameters, 1);
+                    parameter_annotations.push(x.parameter_annotations[0].annotations[0].type_name.as_ref());
+                }
+                AttributeEnum::RuntimeVisibleTypeAnnotationsItem(x)
+                | AttributeEnum::RuntimeInvisibleTypeAnnotationsItem(x) => type_annotations.extend(&x.annotations),
+                _ => {}
+            }
+        }
+        assert_eq!(parameter_annotations, ["LFixture$Marker;", "LFixture$Hidden;"]);
+        let targets: Vec<u8> = type_annotations.iter().map(|x| x.target_type).collect();
+        assert_eq!(targets, [0x14, 0x16, 0x16]);
+        assert!(matches!(type_annotations[1].target_info, TargetInfo::FormalParameter { formal_parameter_index: 0 }));
+
+        // List<@Hidden T> items
+        let items = class.fields[0].attributes.iter()
+            .find_map(|attribute| match attribute {
+                AttributeEnum::RuntimeInvisibleTypeAnnotationsItem(x) => Some(&x.annotations[0]),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(items.target_type, 0x13);
+        assert!(matches!(items.target_info, TargetInfo::Empty));
+        let path: Vec<(u8, u8)> = items.target_path.path.iter()
+            .map(|elem| (elem.type_path_kind, elem.type_argument_index))
+            .collect();
+        assert_eq!(path, [(3, 0)]);
+        assert_eq!(items.annotation.type_name, "LFixture$Hidden;");
+    }
+
+    #[test]
+    fn parses_annotation_defaults() {
+        let class = parse_bytes(MARKER).unwrap();
+        let defaults: Vec<(&str, u8)> = class.methods.iter()
+            .flat_map(|method| method.attributes.iter().filter_map(move |attribute| match attribute {
+                AttributeEnum::AnnotationDefaultItem(x) => Some((method.name.as_ref(), x.default_value.tag())),
+                _ => None,
+            }))
+            .collect();
+        assert_eq!(defaults, [("name", b's'), ("values", b'['), ("kind", b'e'), ("type", b'c'), ("nested", b'@')]);
+    }
+
+    #[test]
+    fn parses_module_descriptors() {
+        let class = parse_bytes(MODULE_INFO).unwrap();
+        assert!(class.is_module());
+        assert_eq!(class.this_class_name, "module-info");
+        let module = class.get_module().unwrap();
+        assert_eq!(module.module_name, "fixture");
+        assert_eq!(module.module_version, None);
+        let requires: Vec<(&str, u16)> = module.requires.iter()
+            .map(|x| (x.requires_name.as_ref(), x.requires_flags))
+            .collect();
+        // java.base is required implicitly, so ACC_MANDATED
+        assert_eq!(requires, [("java.base", 0x8000), ("java.logging", 0)]);
+        assert!(module.requires.iter().all(|x| x.requires_version.is_some()));
+        assert_eq!(module.exports.len(), 1);
+        assert_eq!(module.exports[0].exports_name, "fixture/api");
+        assert!(module.exports[0].exports_to_names.is_empty());
+        assert_eq!(module.opens.len(), 1);
+        assert_eq!(module.opens[0].opens_name, "fixture/impl");
+        assert_eq!(module.opens[0].opens_to_names, ["java.base"]);
+        assert_eq!(module.uses_names, ["java/lang/Runnable"]);
+        assert_eq!(module.provides.len(), 1);
+        assert_eq!(module.provides[0].provides_name, "java/lang/Runnable");
+        assert_eq!(module.provides[0].provides_with_names, ["fixture/impl/Task"]);
+
+        let class = parse_bytes(FIXTURE).unwrap();
+        assert!(!class.is_module());
+        assert!(class.get_module().is_none());
+    }
 }
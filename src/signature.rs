@@ -0,0 +1,478 @@
+//! Generic signatures (JVMS 4.7.9.1), as found in `Signature` attributes,
+//! e.g. `<T:Ljava/lang/Object;>(Ljava/util/List<+TT;>;)V`.
+//!
+//! Every type formats as it would be written in Java source, with package
+//! names separated by `.`, so the signature above becomes
+//! `<T> void name(java.util.List<? extends T>)`.
+
+use std::error;
+use std::fmt;
+
+use crate::descriptor::BaseType;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JavaTypeSignature {
+    Base(BaseType),
+    Reference(ReferenceTypeSignature),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReferenceTypeSignature {
+    Class(ClassTypeSignature),
+    /// A use of a type parameter, by name
+    TypeVariable(String),
+    Array(Box<JavaTypeSignature>),
+}
+
+/// A possibly parameterized class type such as `java/util/Map<TK;TV;>.Entry`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassTypeSignature {
+    /// The package in internal form with a trailing `/`, such as
+    /// `java/util/`, or empty for the unnamed package
+    pub package: String,
+    pub class: SimpleClassTypeSignature,
+    /// Member classes, outermost first
+    pub suffixes: Vec<SimpleClassTypeSignature>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimpleClassTypeSignature {
+    pub identifier: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypeArgument {
+    /// `?`
+    Any,
+    Exact(ReferenceTypeSignature),
+    /// `? extends`
+    Extends(ReferenceTypeSignature),
+    /// `? super`
+    Super(ReferenceTypeSignature),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeParameter {
+    pub identifier: String,
+    /// Absent when the parameter is only bounded by interfaces
+    pub class_bound: Option<ReferenceTypeSignature>,
+    pub interface_bounds: Vec<ReferenceTypeSignature>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub superclass: ClassTypeSignature,
+    pub superinterfaces: Vec<ClassTypeSignature>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<JavaTypeSignature>,
+    /// `None` for `void`
+    pub result: Option<JavaTypeSignature>,
+    /// Class types or type variables
+    pub throws: Vec<ReferenceTypeSignature>,
+}
+
+impl ClassSignature {
+    pub fn parse(signature: &str) -> Result<ClassSignature, SignatureError> {
+        let mut parser = Parser { signature, position: 0, depth: 0 };
+        let type_parameters = parser.type_parameters()?;
+        let superclass = parser.class_type()?;
+        let mut superinterfaces = vec![];
+        while parser.peek().is_some() {
+            superinterfaces.push(parser.class_type()?);
+        }
+        Ok(ClassSignature { type_parameters, superclass, superinterfaces })
+    }
+
+    /// Formats a class called `name` with this signature as in Java source,
+    /// e.g. `Box<T> extends java.lang.Object implements java.io.Serializable`.
+    pub fn to_java(&self, name: &str) -> String {
+        let mut java = format!("{}{} extends {}", name, TypeParameters(&self.type_parameters), self.superclass);
+        if !self.superinterfaces.is_empty() {
+            java.push_str(" implements ");
+            java.push_str(&join(&self.superinterfaces, ", "));
+        }
+        java
+    }
+}
+
+impl MethodSignature {
+    pub fn parse(signature: &str) -> Result<MethodSignature, SignatureError> {
+        let mut parser = Parser { signature, position: 0, depth: 0 };
+        let type_parameters = parser.type_parameters()?;
+        parser.expect(b'(')?;
+        let mut parameters = vec![];
+        while parser.peek() != Some(b')') {
+            parameters.push(parser.java_type()?);
+        }
+        parser.expect(b')')?;
+        let result = if parser.peek() == Some(b'V') {
+            parser.position += 1;
+            None
+        } else {
+            Some(parser.java_type()?)
+        };
+        let mut throws = vec![];
+        while parser.peek() == Some(b'^') {
+            parser.position += 1;
+            throws.push(match parser.peek() {
+                Some(b'L') => ReferenceTypeSignature::Class(parser.class_type()?),
+                Some(b'T') => parser.reference_type()?,
+                _ => return Err(parser.error()),
+            });
+        }
+        parser.end()?;
+        Ok(MethodSignature { type_parameters, parameters, result, throws })
+    }
+
+    /// Formats a method called `name` with this signature as in Java
+    /// source, e.g. `<T> T max(java.util.Collection<? extends T>)`.
+    pub fn to_java(&self, name: &str) -> String {
+        let mut java = String::new();
+        if !self.type_parameters.is_empty() {
+            java.push_str(&format!("{} ", TypeParameters(&self.type_parameters)));
+        }
+        match &self.result {
+            Some(result) => java.push_str(&result.to_string()),
+            None => java.push_str("void"),
+        }
+        java.push_str(&format!(" {}({})", name, join(&self.parameters, ", ")));
+        if !self.throws.is_empty() {
+            java.push_str(" throws ");
+            java.push_str(&join(&self.throws, ", "));
+        }
+        java
+    }
+}
+
+/// Parses the signature of a field, which is a single reference type.
+pub fn parse_field_signature(signature: &str) -> Result<ReferenceTypeSignature, SignatureError> {
+    let mut parser = Parser { signature, position: 0, depth: 0 };
+    let field_type = parser.reference_type()?;
+    parser.end()?;
+    Ok(field_type)
+}
+
+fn join<T: fmt::Display>(items: &[T], separator: &str) -> String {
+    items.iter().map(T::to_string).collect::<Vec<String>>().join(separator)
+}
+
+impl fmt::Display for JavaTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JavaTypeSignature::Base(base_type) => write!(f, "{}", base_type.java_name()),
+            JavaTypeSignature::Reference(reference) => write!(f, "{}", reference),
+        }
+    }
+}
+
+impl fmt::Display for ReferenceTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReferenceTypeSignature::Class(class) => write!(f, "{}", class),
+            ReferenceTypeSignature::TypeVariable(identifier) => write!(f, "{}", identifier),
+            ReferenceTypeSignature::Array(component) => write!(f, "{}[]", component),
+        }
+    }
+}
+
+impl fmt::Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.package.replace('/', "."), self.class)?;
+        for suffix in &self.suffixes {
+            write!(f, ".{}", suffix)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SimpleClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.identifier)?;
+        if !self.type_arguments.is_empty() {
+            write!(f, "<{}>", join(&self.type_arguments, ", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeArgument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeArgument::Any => write!(f, "?"),
+            TypeArgument::Exact(reference) => write!(f, "{}", reference),
+            TypeArgument::Extends(reference) => write!(f, "? extends {}", reference),
+            TypeArgument::Super(reference) => write!(f, "? super {}", reference),
+        }
+    }
+}
+
+/// Formats the parameter with its bounds, leaving out the implicit
+/// `extends java.lang.Object`.
+impl fmt::Display for TypeParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.identifier)?;
+        let class_bound = self.class_bound.iter().filter(|bound| match bound {
+            ReferenceTypeSignature::Class(class) => !(class.package == "java/lang/"
+                && class.class.identifier == "Object" && class.suffixes.is_empty()),
+            _ => true,
+        });
+        let bounds: Vec<String> = class_bound.chain(&self.interface_bounds).map(|bound| bound.to_string()).collect();
+        if !bounds.is_empty() {
+            write!(f, " extends {}", bounds.join(" & "))?;
+        }
+        Ok(())
+    }
+}
+
+// `<T, U extends T>`, or nothing when there are no type parameters
+struct TypeParameters<'a>(&'a [TypeParameter]);
+
+impl<'a> fmt::Display for TypeParameters<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            [] => Ok(()),
+            type_parameters => write!(f, "<{}>", join(type_parameters, ", ")),
+        }
+    }
+}
+
+/// A signature that does not follow the grammar, with the byte offset at
+/// which parsing failed.
+#[derive(Debug, PartialEq)]
+pub struct SignatureError {
+    pub signature: String,
+    pub position: usize,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bad signature {} at position {}", self.signature, self.position)
+    }
+}
+
+impl error::Error for SignatureError {}
+
+// Type arguments may nest no deeper than this, so that parsing cannot
+// overflow the stack
+const MAX_TYPE_ARGUMENT_DEPTH: usize = 255;
+
+struct Parser<'a> {
+    signature: &'a str,
+    position: usize,
+    // The number of type argument lists the parser is inside
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self) -> SignatureError {
+        SignatureError { signature: self.signature.to_string(), position: self.position }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.signature.as_bytes().get(self.position).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), SignatureError> {
+        if self.peek() != Some(c) {
+            return Err(self.error());
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn end(&self) -> Result<(), SignatureError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error()),
+        }
+    }
+
+    // A name, which runs up to the next character with a meaning of its own
+    fn identifier(&mut self) -> Result<&'a str, SignatureError> {
+        let start = self.position;
+        let rest = &self.signature[start..];
+        let length = rest.find(['.', ';', '[', '/', '<', '>', ':']).unwrap_or(rest.len());
+        if length == 0 {
+            return Err(self.error());
+        }
+        self.position += length;
+        Ok(&rest[..length])
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>, SignatureError> {
+        let mut type_parameters = vec![];
+        if self.peek() != Some(b'<') {
+            return Ok(type_parameters);
+        }
+        self.position += 1;
+        loop {
+            let identifier = self.identifier()?.to_string();
+            self.expect(b':')?;
+            // The class bound may be empty, as in `T::Ljava/lang/Runnable;`
+            let class_bound = match self.peek() {
+                Some(b'L') | Some(b'T') | Some(b'[') => Some(self.reference_type()?),
+                _ => None,
+            };
+            let mut interface_bounds = vec![];
+            while self.peek() == Some(b':') {
+                self.position += 1;
+                interface_bounds.push(self.reference_type()?);
+            }
+            type_parameters.push(TypeParameter { identifier, class_bound, interface_bounds });
+            if self.peek() == Some(b'>') {
+                self.position += 1;
+                return Ok(type_parameters);
+            }
+        }
+    }
+
+    fn java_type(&mut self) -> Result<JavaTypeSignature, SignatureError> {
+        match self.peek() {
+            Some(b'L') | Some(b'T') | Some(b'[') => Ok(JavaTypeSignature::Reference(self.reference_type()?)),
+            Some(c) => {
+                let base_type = BaseType::from_char(c).ok_or_else(|| self.error())?;
+                self.position += 1;
+                Ok(JavaTypeSignature::Base(base_type))
+            },
+            None => Err(self.error()),
+        }
+    }
+
+    fn reference_type(&mut self) -> Result<ReferenceTypeSignature, SignatureError> {
+        match self.peek() {
+            Some(b'L') => Ok(ReferenceTypeSignature::Class(self.class_type()?)),
+            Some(b'T') => {
+                self.position += 1;
+                let identifier = self.identifier()?.to_string();
+                self.expect(b';')?;
+                Ok(ReferenceTypeSignature::TypeVariable(identifier))
+            },
+            Some(b'[') => {
+                let mut dimensions: usize = 0;
+                while self.peek() == Some(b'[') {
+                    dimensions += 1;
+                    self.position += 1;
+                }
+                // JVMS 4.3.2: an array type has at most 255 dimensions
+                if dimensions > 255 {
+                    return Err(self.error());
+                }
+                let mut array = ReferenceTypeSignature::Array(Box::new(self.java_type()?));
+                for _ in 1..dimensions {
+                    array = ReferenceTypeSignature::Array(Box::new(JavaTypeSignature::Reference(array)));
+                }
+                Ok(array)
+            },
+            _ => Err(self.error()),
+        }
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature, SignatureError> {
+        self.expect(b'L')?;
+        let mut package = String::new();
+        let mut identifier = self.identifier()?;
+        while self.peek() == Some(b'/') {
+            self.position += 1;
+            package.push_str(identifier);
+            package.push('/');
+            identifier = self.identifier()?;
+        }
+        let class = self.simple_class_type(identifier)?;
+        let mut suffixes = vec![];
+        while self.peek() == Some(b'.') {
+            self.position += 1;
+            let identifier = self.identifier()?;
+            suffixes.push(self.simple_class_type(identifier)?);
+        }
+        self.expect(b';')?;
+        Ok(ClassTypeSignature { package, class, suffixes })
+    }
+
+    fn simple_class_type(&mut self, identifier: &str) -> Result<SimpleClassTypeSignature, SignatureError> {
+        let mut type_arguments = vec![];
+        if self.peek() == Some(b'<') {
+            if self.depth == MAX_TYPE_ARGUMENT_DEPTH {
+                return Err(self.error());
+            }
+            self.depth += 1;
+            self.position += 1;
+            loop {
+                type_arguments.push(match self.peek() {
+                    Some(b'*') => {
+                        self.position += 1;
+                        TypeArgument::Any
+                    },
+                    Some(b'+') => {
+                        self.position += 1;
+                        TypeArgument::Extends(self.reference_type()?)
+                    },
+                    Some(b'-') => {
+                        self.position += 1;
+                        TypeArgument::Super(self.reference_type()?)
+                    },
+                    _ => TypeArgument::Exact(self.reference_type()?),
+                });
+                if self.peek() == Some(b'>') {
+                    self.position += 1;
+                    break;
+                }
+            }
+            self.depth -= 1;
+        }
+        Ok(SimpleClassTypeSignature { identifier: identifier.to_string(), type_arguments })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_as_java() {
+        let field = parse_field_signature("Ljava/util/Map<TK;[TV;>.Entry<*+Ljava/lang/Number;>;").unwrap();
+        assert_eq!(field.to_string(), "java.util.Map<K, V[]>.Entry<?, ? extends java.lang.Number>");
+
+        let method = MethodSignature::parse(
+            "<T:Ljava/lang/Object;>(Ljava/util/List<-TT;>;[[I)TT;^Ljava/io/IOException;").unwrap();
+        assert_eq!(method.to_java("first"),
+            "<T> T first(java.util.List<? super T>, int[][]) throws java.io.IOException");
+
+        let class = ClassSignature::parse(
+            "<K::Ljava/lang/Comparable<TK;>;>Ljava/lang/Object;Ljava/io/Serializable;").unwrap();
+        assert_eq!(class.to_java("Tree"),
+            "Tree<K extends java.lang.Comparable<K>> extends java.lang.Object implements java.io.Serializable");
+    }
+
+    #[test]
+    fn parses_arrays_as_nested_components() {
+        let array = parse_field_signature("[[I").unwrap();
+        let int_array = ReferenceTypeSignature::Array(Box::new(JavaTypeSignature::Base(BaseType::Int)));
+        assert_eq!(array, ReferenceTypeSignature::Array(Box::new(JavaTypeSignature::Reference(int_array))));
+    }
+
+    #[test]
+    fn limits_array_dimensions() {
+        let array = format!("{}Ljava/lang/String;", "[".repeat(255));
+        assert_eq!(parse_field_signature(&array).unwrap().to_string(),
+            format!("java.lang.String{}", "[]".repeat(255)));
+        for dimensions in [256, 5000] {
+            let array = format!("{}I", "[".repeat(dimensions));
+            assert_eq!(parse_field_signature(&array).unwrap_err().position, dimensions);
+        }
+    }
+
+    #[test]
+    fn limits_type_argument_nesting() {
+        let nested = format!("{}La;{}", "La<".repeat(255), ">;".repeat(255));
+        assert!(parse_field_signature(&nested).is_ok());
+        let nested = format!("{}La;{}", "La<".repeat(20000), ">;".repeat(20000));
+        // The 256th `<`
+        assert_eq!(parse_field_signature(&nested).unwrap_err().position, 3 * 255 + 2);
+    }
+}
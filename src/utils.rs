@@ -0,0 +1,42 @@
+/// Formats a `double` the way `Double.toString` does: plain decimal notation
+/// between 10^-3 and 10^7, computerized scientific notation outside it.
+pub fn format_double(x: f64) -> String {
+    if x.is_nan() {
+        return String::from("NaN");
+    }
+    if x.is_infinite() {
+        return String::from(if x > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    let magnitude = x.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        format!("{:?}", x)
+    } else {
+        scientific(format!("{:e}", x))
+    }
+}
+
+/// Formats a `float` the way `Float.toString` does.
+pub fn format_float(x: f32) -> String {
+    if x.is_nan() {
+        return String::from("NaN");
+    }
+    if x.is_infinite() {
+        return String::from(if x > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    let magnitude = x.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        format!("{:?}", x)
+    } else {
+        scientific(format!("{:e}", x))
+    }
+}
+
+// Turns Rust's `1.5e-7` into Java's `1.5E-7`, and `1e10` into `1.0E10`
+fn scientific(formatted: String) -> String {
+    let (mantissa, exponent) = formatted.split_at(formatted.find('e').unwrap());
+    if mantissa.contains('.') {
+        format!("{}E{}", mantissa, &exponent[1..])
+    } else {
+        format!("{}.0E{}", mantissa, &exponent[1..])
+    }
+}
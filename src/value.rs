@@ -0,0 +1,101 @@
+use std::fmt;
+use std::rc::Rc;
+
+/// A value held in a local variable or on the operand stack.
+///
+/// `Long` and `Double` are category 2 values: they occupy two local
+/// variable slots and count as two units of operand stack depth. Everything
+/// else is category 1.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    /// A reference to a heap object, or `None` for `null`.
+    Reference(Option<Rc<Object>>),
+    ReturnAddress(usize),
+}
+
+impl Value {
+    pub fn null() -> Value {
+        Value::Reference(None)
+    }
+
+    pub fn object(object: Object) -> Value {
+        Value::Reference(Some(Rc::new(object)))
+    }
+
+    pub fn category(&self) -> usize {
+        match self {
+            Value::Long(_) | Value::Double(_) => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn as_int(&self) -> i32 {
+        match self {
+            Value::Int(x) => *x,
+            _ => panic!("Expected int, found {:?}", self),
+        }
+    }
+
+    pub fn as_long(&self) -> i64 {
+        match self {
+            Value::Long(x) => *x,
+            _ => panic!("Expected long, found {:?}", self),
+        }
+    }
+
+    pub fn as_float(&self) -> f32 {
+        match self {
+            Value::Float(x) => *x,
+            _ => panic!("Expected float, found {:?}", self),
+        }
+    }
+
+    pub fn as_double(&self) -> f64 {
+        match self {
+            Value::Double(x) => *x,
+            _ => panic!("Expected double, found {:?}", self),
+        }
+    }
+
+    pub fn as_reference(&self) -> &Option<Rc<Object>> {
+        match self {
+            Value::Reference(x) => x,
+            _ => panic!("Expected reference, found {:?}", self),
+        }
+    }
+}
+
+/// The objects the interpreter knows how to allocate.
+#[derive(Debug)]
+pub enum Object {
+    String(String),
+    PrintStream(Stream),
+    Throwable { class_name: String, message: Option<String> },
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Stream {
+    Out,
+    Err,
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Object::String(s) => write!(f, "{}", s),
+            Object::PrintStream(Stream::Out) => write!(f, "java.io.PrintStream(out)"),
+            Object::PrintStream(Stream::Err) => write!(f, "java.io.PrintStream(err)"),
+            Object::Throwable { class_name, message } => {
+                write!(f, "{}", class_name.replace('/', "."))?;
+                match message {
+                    Some(message) => write!(f, ": {}", message),
+                    None => Ok(()),
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,695 @@
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+
+use crate::mutf8;
+use crate::class::BytecodeClass;
+use crate::class::ConstantPoolEnum;
+use crate::class::FieldInfo;
+use crate::class::MethodInfo;
+use crate::class::AttributeEnum;
+use crate::class::StackMapFrame;
+use crate::class::VerificationTypeInfo;
+use crate::class::Annotation;
+use crate::class::ElementValue;
+use crate::class::TypeAnnotation;
+use crate::class::TargetInfo;
+use crate::constant_pool::Utf8Index;
+
+/// Why a class could not be written.
+#[derive(Debug)]
+pub enum ClassWriteError {
+    Io(io::Error),
+    /// A table, string or attribute is too long for the field that holds
+    /// its size
+    TooLarge { what: &'static str, size: usize },
+}
+
+impl fmt::Display for ClassWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClassWriteError::Io(e) => write!(f, "{}", e),
+            ClassWriteError::TooLarge { what, size } =>
+                write!(f, "{} of size {} does not fit in a class file", what, size),
+        }
+    }
+}
+
+impl error::Error for ClassWriteError {}
+
+// Writes big-endian values. Counts and lengths are taken from the data
+// rather than from the `_count` and `_length` fields of the class
+struct ClassWriter {
+    bytes: Vec<u8>,
+}
+
+impl ClassWriter {
+    fn u1(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u2(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u4(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn count_u1(&mut self, size: usize, what: &'static str) -> Result<(), ClassWriteError> {
+        match u8::try_from(size) {
+            Ok(count) => {
+                self.u1(count);
+                Ok(())
+            },
+            Err(_) => Err(ClassWriteError::TooLarge { what, size }),
+        }
+    }
+
+    fn count(&mut self, size: usize, what: &'static str) -> Result<(), ClassWriteError> {
+        match u16::try_from(size) {
+            Ok(count) => {
+                self.u2(count);
+                Ok(())
+            },
+            Err(_) => Err(ClassWriteError::TooLarge { what, size }),
+        }
+    }
+
+    // Writes the attribute header, then the body, then fills in the length
+    fn attribute(&mut self, attribute_name_index: Utf8Index,
+        body: impl FnOnce(&mut ClassWriter) -> Result<(), ClassWriteError>) -> Result<(), ClassWriteError> {
+        self.u2(attribute_name_index.0);
+        let length_at = self.bytes.len();
+        self.u4(0);
+        body(self)?;
+        let size = self.bytes.len() - length_at - 4;
+        match u32::try_from(size) {
+            Ok(length) => {
+                self.bytes[length_at..length_at + 4].copy_from_slice(&length.to_be_bytes());
+                Ok(())
+            },
+            Err(_) => Err(ClassWriteError::TooLarge { what: "Attribute", size }),
+        }
+    }
+}
+
+/// Writes `class` to the file at `path`.
+pub fn write(class: &BytecodeClass, path: String) -> Result<(), ClassWriteError> {
+    let bytes = to_bytes(class)?;
+    let mut file = File::create(path).map_err(ClassWriteError::Io)?;
+    file.write_all(&bytes).map_err(ClassWriteError::Io)
+}
+
+/// Serializes `class` as a class file. A class returned by
+/// `read_class::parse` and not modified since is written back byte for byte.
+pub fn to_bytes(class: &BytecodeClass) -> Result<Vec<u8>, ClassWriteError> {
+    let mut con = ClassWriter { bytes: vec![] };
+    con.u4(class.magic);
+    con.u2(class.minor_version);
+    con.u2(class.major_version);
+    con.count(class.constant_pool.len() + 1, "Constant pool")?;
+    for cp_info in &class.constant_pool {
+        write_cp_info(&mut con, cp_info)?;
+    }
+    con.u2(class.access_flags);
+    con.u2(class.this_class.0);
+    con.u2(class.super_class.0);
+    con.count(class.interfaces.len(), "Interfaces")?;
+    for interface in &class.interfaces {
+        con.u2(interface.0);
+    }
+    con.count(class.fields.len(), "Fields")?;
+    for field in &class.fields {
+        write_field_info(&mut con, field)?;
+    }
+    con.count(class.methods.len(), "Methods")?;
+    for method in &class.methods {
+        write_method_info(&mut con, method)?;
+    }
+    write_attributes(&mut con, &class.attributes)?;
+    Ok(con.bytes)
+}
+
+fn write_cp_info(con: &mut ClassWriter, cp_info: &ConstantPoolEnum) -> Result<(), ClassWriteError> {
+    match cp_info {
+        ConstantPoolEnum::ConstantUtf8Item(x) => {
+            // The original bytes are only used while they still mean `bytes`
+            let bytes = match &x.original {
+                Some(original) if mutf8::decode(original).as_ref() == Ok(&x.bytes) => original.clone(),
+                _ => mutf8::encode(&x.bytes),
+            };
+            con.u1(1);
+            con.count(bytes.len(), "Utf8 constant")?;
+            con.bytes(&bytes);
+        },
+        ConstantPoolEnum::ConstantIntegerItem(x) => {
+            con.u1(3);
+            con.bytes(&x.value.to_be_bytes());
+        },
+        ConstantPoolEnum::ConstantFloatItem(x) => {
+            con.u1(4);
+            con.bytes(&x.value.to_be_bytes());
+        },
+        ConstantPoolEnum::ConstantLongItem(x) => {
+            con.u1(5);
+            con.bytes(&x.value.to_be_bytes());
+        },
+        ConstantPoolEnum::ConstantDoubleItem(x) => {
+            con.u1(6);
+            con.bytes(&x.value.to_be_bytes());
+        },
+        ConstantPoolEnum::ConstantClassItem(x) => {
+            con.u1(7);
+            con.u2(x.name_index.0);
+        },
+        ConstantPoolEnum::ConstantStringItem(x) => {
+            con.u1(8);
+            con.u2(x.string_index.0);
+        },
+        ConstantPoolEnum::ConstantFieldrefItem(x) => {
+            con.u1(9);
+            con.u2(x.class_index.0);
+            con.u2(x.name_and_type_index.0);
+        },
+        ConstantPoolEnum::ConstantMethodrefItem(x) => {
+            con.u1(10);
+            con.u2(x.class_index.0);
+            con.u2(x.name_and_type_index.0);
+        },
+        ConstantPoolEnum::ConstantInterfaceMethodrefItem(x) => {
+            con.u1(11);
+            con.u2(x.class_index.0);
+            con.u2(x.name_and_type_index.0);
+        },
+        ConstantPoolEnum::ConstantNameAndTypeItem(x) => {
+            con.u1(12);
+            con.u2(x.name_index.0);
+            con.u2(x.descriptor_index.0);
+        },
+        ConstantPoolEnum::ConstantMethodHandleItem(x) => {
+            con.u1(15);
+            con.u1(x.reference_kind);
+            con.u2(x.reference_index.0);
+        },
+        ConstantPoolEnum::ConstantMethodTypeItem(x) => {
+            con.u1(16);
+            con.u2(x.descriptor_index.0);
+        },
+        ConstantPoolEnum::ConstantDynamicItem(x) => {
+            con.u1(17);
+            con.u2(x.bootstrap_method_attr_index);
+            con.u2(x.name_and_type_index.0);
+        },
+        ConstantPoolEnum::ConstantInvokeDynamicItem(x) => {
+            con.u1(18);
+            con.u2(x.bootstrap_method_attr_index);
+            con.u2(x.name_and_type_index.0);
+        },
+        ConstantPoolEnum::ConstantModuleItem(x) => {
+            con.u1(19);
+            con.u2(x.name_index.0);
+        },
+        ConstantPoolEnum::ConstantPackageItem(x) => {
+            con.u1(20);
+            con.u2(x.name_index.0);
+        },
+        // Occupies an index but has no bytes of its own
+        ConstantPoolEnum::ConstantUnusableItem => {},
+    }
+    Ok(())
+}
+
+fn write_field_info(con: &mut ClassWriter, field: &FieldInfo) -> Result<(), ClassWriteError> {
+    con.u2(field.access_flags);
+    con.u2(field.name_index.0);
+    con.u2(field.descriptor_index.0);
+    write_attributes(con, &field.attributes)
+}
+
+fn write_method_info(con: &mut ClassWriter, method: &MethodInfo) -> Result<(), ClassWriteError> {
+    con.u2(method.access_flags);
+    con.u2(method.name_index.0);
+    con.u2(method.descriptor_index.0);
+    write_attributes(con, &method.attributes)
+}
+
+fn write_attributes(con: &mut ClassWriter, attributes: &[AttributeEnum]) -> Result<(), ClassWriteError> {
+    con.count(attributes.len(), "Attributes")?;
+    for attribute in attributes {
+        write_attribute(con, attribute)?;
+    }
+    Ok(())
+}
+
+fn write_attribute(con: &mut ClassWriter, attribute: &AttributeEnum) -> Result<(), ClassWriteError> {
+    match attribute {
+        AttributeEnum::CodeItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.u2(x.max_stack);
+            con.u2(x.max_locals);
+            match u32::try_from(x.code.len()) {
+                Ok(code_length) => con.u4(code_length),
+                Err(_) => return Err(ClassWriteError::TooLarge { what: "Code", size: x.code.len() }),
+            }
+            con.bytes(&x.code);
+            con.count(x.exception_table.len(), "Exception table")?;
+            for entry in &x.exception_table {
+                con.u2(entry.start_pc);
+                con.u2(entry.end_pc);
+                con.u2(entry.handler_pc);
+                con.u2(entry.catch_type.0);
+            }
+            write_attributes(con, &x.attributes)
+        }),
+        AttributeEnum::LineNumberTableItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count(x.line_number_table.len(), "LineNumberTable")?;
+            for entry in &x.line_number_table {
+                con.u2(entry.start_pc);
+                con.u2(entry.line_number);
+            }
+            Ok(())
+        }),
+        AttributeEnum::StackMapTableItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count(x.entries.len(), "StackMapTable")?;
+            for frame in &x.entries {
+                write_stack_map_frame(con, frame)?;
+            }
+            Ok(())
+        }),
+        AttributeEnum::SourceFileItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.u2(x.sourcefile_index.0);
+            Ok(())
+        }),
+        AttributeEnum::ConstantValueItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.u2(x.constantvalue_index);
+            Ok(())
+        }),
+        AttributeEnum::ExceptionsItem(x) => con.attribute(x.attribute_name_index, |con| {
+            write_indexes(con, &x.exception_index_table, "Exceptions")
+        }),
+        AttributeEnum::InnerClassesItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count(x.classes.len(), "InnerClasses")?;
+            for class in &x.classes {
+                con.u2(class.inner_class_info_index.0);
+                con.u2(class.outer_class_info_index.0);
+                con.u2(class.inner_name_index.0);
+                con.u2(class.inner_class_access_flags);
+            }
+            Ok(())
+        }),
+        AttributeEnum::EnclosingMethodItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.u2(x.class_index.0);
+            con.u2(x.method_index.0);
+            Ok(())
+        }),
+        AttributeEnum::SignatureItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.u2(x.signature_index.0);
+            Ok(())
+        }),
+        AttributeEnum::LocalVariableTableItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count(x.local_variable_table.len(), "LocalVariableTable")?;
+            for entry in &x.local_variable_table {
+                con.u2(entry.start_pc);
+                con.u2(entry.length);
+                con.u2(entry.name_index.0);
+                con.u2(entry.descriptor_index.0);
+                con.u2(entry.index);
+            }
+            Ok(())
+        }),
+        AttributeEnum::LocalVariableTypeTableItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count(x.local_variable_type_table.len(), "LocalVariableTypeTable")?;
+            for entry in &x.local_variable_type_table {
+                con.u2(entry.start_pc);
+                con.u2(entry.length);
+                con.u2(entry.name_index.0);
+                con.u2(entry.signature_index.0);
+                con.u2(entry.index);
+            }
+            Ok(())
+        }),
+        AttributeEnum::BootstrapMethodsItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count(x.bootstrap_methods.len(), "BootstrapMethods")?;
+            for method in &x.bootstrap_methods {
+                con.u2(method.bootstrap_method_ref);
+                write_indexes(con, &method.bootstrap_arguments, "Bootstrap arguments")?;
+            }
+            Ok(())
+        }),
+        AttributeEnum::MethodParametersItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count_u1(x.parameters.len(), "MethodParameters")?;
+            for parameter in &x.parameters {
+                con.u2(parameter.name_index.0);
+                con.u2(parameter.access_flags);
+            }
+            Ok(())
+        }),
+        AttributeEnum::NestHostItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.u2(x.host_class_index.0);
+            Ok(())
+        }),
+        AttributeEnum::NestMembersItem(x) => con.attribute(x.attribute_name_index, |con| {
+            write_indexes(con, &x.classes, "NestMembers")
+        }),
+        AttributeEnum::RecordItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count(x.components.len(), "Record")?;
+            for component in &x.components {
+                con.u2(component.name_index.0);
+                con.u2(component.descriptor_index.0);
+                write_attributes(con, &component.attributes)?;
+            }
+            Ok(())
+        }),
+        AttributeEnum::PermittedSubclassesItem(x) => con.attribute(x.attribute_name_index, |con| {
+            write_indexes(con, &x.classes, "PermittedSubclasses")
+        }),
+        AttributeEnum::DeprecatedItem(x) => con.attribute(x.attribute_name_index, |_con| Ok(())),
+        AttributeEnum::SyntheticItem(x) => con.attribute(x.attribute_name_index, |_con| Ok(())),
+        AttributeEnum::SourceDebugExtensionItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.bytes(&x.debug_extension);
+            Ok(())
+        }),
+        AttributeEnum::RuntimeVisibleAnnotationsItem(x)
+        | AttributeEnum::RuntimeInvisibleAnnotationsItem(x) => con.attribute(x.attribute_name_index, |con| {
+            write_annotations(con, &x.annotations)
+        }),
+        AttributeEnum::RuntimeVisibleParameterAnnotationsItem(x)
+        | AttributeEnum::RuntimeInvisibleParameterAnnotationsItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count_u1(x.parameter_annotations.len(), "Parameter annotations")?;
+            for parameter in &x.parameter_annotations {
+                write_annotations(con, &parameter.annotations)?;
+            }
+            Ok(())
+        }),
+        AttributeEnum::RuntimeVisibleTypeAnnotationsItem(x)
+        | AttributeEnum::RuntimeInvisibleTypeAnnotationsItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.count(x.annotations.len(), "Type annotations")?;
+            for annotation in &x.annotations {
+                write_type_annotation(con, annotation)?;
+            }
+            Ok(())
+        }),
+        AttributeEnum::AnnotationDefaultItem(x) => con.attribute(x.attribute_name_index, |con| {
+            write_element_value(con, &x.default_value)
+        }),
+        AttributeEnum::ModuleItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.u2(x.module_name_index.0);
+            con.u2(x.module_flags);
+            con.u2(x.module_version_index.0);
+            con.count(x.requires.len(), "Module requires")?;
+            for requires in &x.requires {
+                con.u2(requires.requires_index.0);
+                con.u2(requires.requires_flags);
+                con.u2(requires.requires_version_index.0);
+            }
+            con.count(x.exports.len(), "Module exports")?;
+            for exports in &x.exports {
+                con.u2(exports.exports_index.0);
+                con.u2(exports.exports_flags);
+                write_indexes(con, &exports.exports_to_index, "Module exports_to")?;
+            }
+            con.count(x.opens.len(), "Module opens")?;
+            for opens in &x.opens {
+                con.u2(opens.opens_index.0);
+                con.u2(opens.opens_flags);
+                write_indexes(con, &opens.opens_to_index, "Module opens_to")?;
+            }
+            write_indexes(con, &x.uses_index, "Module uses")?;
+            con.count(x.provides.len(), "Module provides")?;
+            for provides in &x.provides {
+                con.u2(provides.provides_index.0);
+                write_indexes(con, &provides.provides_with_index, "Module provides_with")?;
+            }
+            Ok(())
+        }),
+        AttributeEnum::ModulePackagesItem(x) => con.attribute(x.attribute_name_index, |con| {
+            write_indexes(con, &x.package_index, "ModulePackages")
+        }),
+        AttributeEnum::ModuleMainClassItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.u2(x.main_class_index.0);
+            Ok(())
+        }),
+        AttributeEnum::UnknownItem(x) => con.attribute(x.attribute_name_index, |con| {
+            con.bytes(&x.bytes);
+            Ok(())
+        }),
+    }
+}
+
+// Writes a u2 count followed by u2 constant pool indexes
+fn write_indexes<I: Copy + Into<u16>>(con: &mut ClassWriter, indexes: &[I], what: &'static str)
+    -> Result<(), ClassWriteError> {
+    con.count(indexes.len(), what)?;
+    for index in indexes {
+        con.u2((*index).into());
+    }
+    Ok(())
+}
+
+fn write_stack_map_frame(con: &mut ClassWriter, frame: &StackMapFrame) -> Result<(), ClassWriteError> {
+    con.u1(frame.frame_type());
+    match frame {
+        StackMapFrame::SameFrame { .. } => {},
+        StackMapFrame::SameLocals1StackItemFrame { stack, .. } => write_verification_type_info(con, stack),
+        StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, stack, .. } => {
+            con.u2(*offset_delta);
+            write_verification_type_info(con, stack);
+        },
+        StackMapFrame::ChopFrame { offset_delta, .. }
+        | StackMapFrame::SameFrameExtended { offset_delta, .. } => con.u2(*offset_delta),
+        StackMapFrame::AppendFrame { offset_delta, locals, .. } => {
+            con.u2(*offset_delta);
+            for local in locals {
+                write_verification_type_info(con, local);
+            }
+        },
+        StackMapFrame::FullFrame { offset_delta, locals, stack, .. } => {
+            con.u2(*offset_delta);
+            con.count(locals.len(), "Stack map frame locals")?;
+            for local in locals {
+                write_verification_type_info(con, local);
+            }
+            con.count(stack.len(), "Stack map frame stack")?;
+            for item in stack {
+                write_verification_type_info(con, item);
+            }
+        },
+    }
+    Ok(())
+}
+
+fn write_verification_type_info(con: &mut ClassWriter, verification_type_info: &VerificationTypeInfo) {
+    con.u1(verification_type_info.tag());
+    match verification_type_info {
+        VerificationTypeInfo::Object { cpool_index, .. } => con.u2(cpool_index.0),
+        VerificationTypeInfo::Uninitialized { offset } => con.u2(*offset),
+        _ => {},
+    }
+}
+
+fn write_annotations(con: &mut ClassWriter, annotations: &[Annotation]) -> Result<(), ClassWriteError> {
+    con.count(annotations.len(), "Annotations")?;
+    for annotation in annotations {
+        write_annotation(con, annotation)?;
+    }
+    Ok(())
+}
+
+fn write_annotation(con: &mut ClassWriter, annotation: &Annotation) -> Result<(), ClassWriteError> {
+    con.u2(annotation.type_index.0);
+    con.count(annotation.element_value_pairs.len(), "Element value pairs")?;
+    for pair in &annotation.element_value_pairs {
+        con.u2(pair.element_name_index.0);
+        write_element_value(con, &pair.value)?;
+    }
+    Ok(())
+}
+
+fn write_element_value(con: &mut ClassWriter, element_value: &ElementValue) -> Result<(), ClassWriteError> {
+    con.u1(element_value.tag());
+    match element_value {
+        ElementValue::Const { const_value_index, .. } => con.u2(*const_value_index),
+        ElementValue::Enum { type_name_index, const_name_index, .. } => {
+            con.u2(type_name_index.0);
+            con.u2(const_name_index.0);
+        },
+        ElementValue::Class { class_info_index, .. } => con.u2(class_info_index.0),
+        ElementValue::Annotation(annotation) => write_annotation(con, annotation)?,
+        ElementValue::Array { values, .. } => {
+            con.count(values.len(), "Element value array")?;
+            for value in values {
+                write_element_value(con, value)?;
+            }
+        },
+    }
+    Ok(())
+}
+
+fn write_type_annotation(con: &mut ClassWriter, annotation: &TypeAnnotation) -> Result<(), ClassWriteError> {
+    con.u1(annotation.target_type);
+    match &annotation.target_info {
+        TargetInfo::TypeParameter { type_parameter_index } => con.u1(*type_parameter_index),
+        TargetInfo::Supertype { supertype_index } => con.u2(*supertype_index),
+        TargetInfo::TypeParameterBound { type_parameter_index, bound_index } => {
+            con.u1(*type_parameter_index);
+            con.u1(*bound_index);
+        },
+        TargetInfo::Empty => {},
+        TargetInfo::FormalParameter { formal_parameter_index } => con.u1(*formal_parameter_index),
+        TargetInfo::Throws { throws_type_index } => con.u2(*throws_type_index),
+        TargetInfo::Localvar { table, .. } => {
+            con.count(table.len(), "Localvar target table")?;
+            for entry in table {
+                con.u2(entry.start_pc);
+                con.u2(entry.length);
+                con.u2(entry.index);
+            }
+        },
+        TargetInfo::Catch { exception_table_index } => con.u2(*exception_table_index),
+        TargetInfo::Offset { offset } => con.u2(*offset),
+        TargetInfo::TypeArgument { offset, type_argument_index } => {
+            con.u2(*offset);
+            con.u1(*type_argument_index);
+        },
+    }
+    con.count_u1(annotation.target_path.path.len(), "Type path")?;
+    for elem in &annotation.target_path.path {
+        con.u1(elem.type_path_kind);
+        con.u1(elem.type_argument_index);
+    }
+    write_annotation(con, &annotation.annotation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::ConstantUtf8Class;
+    use crate::read_class;
+
+    const HELLO_WORLD: &[u8] = include_bytes!("../HelloWorld.class");
+
+    // Compiled from the sources next to them
+    const FIXTURES: &[(&str, &[u8])] = &[
+        ("Fixture", include_bytes!("../fixtures/Fixture.class")),
+        ("Fixture$1", include_bytes!("../fixtures/Fixture$1.class")),
+        ("Fixture$Color", include_bytes!("../fixtures/Fixture$Color.class")),
+        ("Fixture$Hidden", include_bytes!("../fixtures/Fixture$Hidden.class")),
+        ("Fixture$Inner", include_bytes!("../fixtures/Fixture$Inner.class")),
+        ("Fixture$Marker", include_bytes!("../fixtures/Fixture$Marker.class")),
+        ("Fixture$Point", include_bytes!("../fixtures/Fixture$Point.class")),
+        ("module-info", include_bytes!("../fixtures/module/module-info.class")),
+    ];
+
+    fn utf8_constant(bytes: &str, original: Option<Vec<u8>>) -> ConstantPoolEnum {
+        let length = original.as_ref().map_or_else(|| mutf8::encode(bytes).len(), Vec::len) as u16;
+        ConstantPoolEnum::ConstantUtf8Item(ConstantUtf8Class { length, bytes: bytes.to_string(), original })
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn round_trips_hello_world() {
+        let class = read_class::parse_bytes(HELLO_WORLD).unwrap();
+        assert_eq!(to_bytes(&class).unwrap(), HELLO_WORLD);
+    }
+
+    fn unknown_attributes(attributes: &[AttributeEnum], names: &mut Vec<String>) {
+        for attribute in attributes {
+            match attribute {
+                AttributeEnum::UnknownItem(x) => names.push(x.attribute_name.clone()),
+                AttributeEnum::CodeItem(x) => unknown_attributes(&x.attributes, names),
+                AttributeEnum::RecordItem(x) =>
+                    x.components.iter().for_each(|component| unknown_attributes(&component.attributes, names)),
+                _ => {},
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_javac_output() {
+        for (name, bytes) in FIXTURES {
+            let class = read_class::parse_bytes(bytes).unwrap();
+            let mut unknown: Vec<String> = vec![];
+            unknown_attributes(&class.attributes, &mut unknown);
+            class.fields.iter().for_each(|field| unknown_attributes(&field.attributes, &mut unknown));
+            class.methods.iter().for_each(|method| unknown_attributes(&method.attributes, &mut unknown));
+            assert!(unknown.is_empty(), "{} has attributes {:?} which were not decoded", name, unknown);
+            assert!(to_bytes(&class).unwrap() == *bytes, "{} changed when written", name);
+        }
+    }
+
+    // Checks that the fixtures still hold the attributes they are meant to
+    // cover, by their names in the constant pools
+    #[test]
+    fn fixtures_cover_attributes() {
+        let mut names: Vec<String> = vec![];
+        for (_, bytes) in FIXTURES {
+            let class = read_class::parse_bytes(bytes).unwrap();
+            for constant in &class.constant_pool {
+                if let ConstantPoolEnum::ConstantUtf8Item(x) = constant {
+                    names.push(x.bytes.clone());
+                }
+            }
+        }
+        for attribute in &[
+            "StackMapTable", "RuntimeVisibleAnnotations", "RuntimeInvisibleAnnotations",
+            "RuntimeVisibleParameterAnnotations", "RuntimeInvisibleParameterAnnotations",
+            "RuntimeVisibleTypeAnnotations", "RuntimeInvisibleTypeAnnotations", "AnnotationDefault",
+            "Module", "InnerClasses", "EnclosingMethod", "BootstrapMethods", "Record", "Signature",
+            "NestHost", "NestMembers", "MethodParameters", "Exceptions", "ConstantValue", "Deprecated",
+            "LocalVariableTable", "LocalVariableTypeTable",
+        ] {
+            assert!(names.iter().any(|name| name == attribute), "No fixture has {}", attribute);
+        }
+    }
+
+    #[test]
+    fn round_trips_unknown_attributes() {
+        // Adds an attribute named by constant 14, "HelloWorld.java", after
+        // SourceFile, which ends the class
+        let mut bytes = HELLO_WORLD.to_vec();
+        let attributes_count = bytes.len() - 10;
+        bytes[attributes_count + 1] = 2;
+        bytes.extend_from_slice(&[0, 14, 0, 0, 0, 3, 1, 2, 3]);
+        let class = read_class::parse_bytes(&bytes).unwrap();
+        assert!(matches!(class.attributes.last(),
+            Some(AttributeEnum::UnknownItem(x)) if x.attribute_name == "HelloWorld.java" && x.bytes == [1, 2, 3]));
+        assert_eq!(to_bytes(&class).unwrap(), bytes);
+    }
+
+    #[test]
+    fn round_trips_modified_utf8() {
+        let mut class = read_class::parse_bytes(HELLO_WORLD).unwrap();
+        class.constant_pool.push(utf8_constant("a\0b", None)).unwrap();
+        class.constant_pool.push(utf8_constant("\u{1f600}", None)).unwrap();
+        // An unpaired surrogate, which reads as U+FFFD
+        class.constant_pool.push(utf8_constant("\u{fffd}", Some(vec![0xed, 0xa0, 0x80]))).unwrap();
+        let bytes = to_bytes(&class).unwrap();
+        assert!(contains(&bytes, &[1, 0, 4, b'a', 0xc0, 0x80, b'b']));
+        assert!(contains(&bytes, &[1, 0, 6, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]));
+        assert!(contains(&bytes, &[1, 0, 3, 0xed, 0xa0, 0x80]));
+
+        let reread = read_class::parse_bytes(&bytes).unwrap();
+        let strings: Vec<(&str, Option<&Vec<u8>>)> = reread.constant_pool.iter().rev().take(3)
+            .map(|constant| match constant {
+                ConstantPoolEnum::ConstantUtf8Item(x) => (x.bytes.as_str(), x.original.as_ref()),
+                _ => panic!("not a Utf8 constant"),
+            })
+            .collect();
+        assert_eq!(strings, vec![
+            ("\u{fffd}", Some(&vec![0xed, 0xa0, 0x80])),
+            ("\u{1f600}", None),
+            ("a\0b", None),
+        ]);
+        assert_eq!(to_bytes(&reread).unwrap(), bytes);
+    }
+}